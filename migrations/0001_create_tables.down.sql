@@ -0,0 +1,3 @@
+DROP TABLE "order";
+DROP TABLE menu;
+DROP TABLE "table";
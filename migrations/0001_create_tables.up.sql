@@ -0,0 +1,24 @@
+-- The tables may already exist on databases created before migrations were introduced.
+CREATE TABLE IF NOT EXISTS "table" (
+    table_id SERIAL PRIMARY KEY,
+    table_description TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS menu (
+    menu_id SERIAL PRIMARY KEY,
+    menu_name VARCHAR(255) NOT NULL,
+    menu_description TEXT NOT NULL,
+    time_to_cook_in_minutes INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS "order" (
+    order_id SERIAL PRIMARY KEY,
+    table_id INTEGER NOT NULL REFERENCES "table"(table_id),
+    menu_id INTEGER NOT NULL REFERENCES menu(menu_id),
+    cooked_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    is_deleted BOOLEAN,
+    created_at TIMESTAMP WITHOUT TIME ZONE,
+    updated_at TIMESTAMP WITHOUT TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS idx_order_table_id ON "order"(table_id);
+CREATE INDEX IF NOT EXISTS idx_order_menu_id ON "order"(menu_id);
@@ -0,0 +1,6 @@
+DELETE FROM "order"
+WHERE table_id IN (1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 21)
+    OR menu_id BETWEEN 1 AND 20;
+
+DELETE FROM "table" WHERE table_id IN (1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 21);
+DELETE FROM menu WHERE menu_id BETWEEN 1 AND 20;
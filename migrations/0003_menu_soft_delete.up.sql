@@ -0,0 +1,2 @@
+-- Menu items referenced by past orders can't be removed, so they are marked as deleted instead.
+ALTER TABLE menu ADD COLUMN is_deleted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+ALTER TABLE "table"
+    DROP COLUMN capacity,
+    DROP COLUMN zone,
+    DROP COLUMN is_active;
@@ -0,0 +1,4 @@
+ALTER TABLE "table"
+    ADD COLUMN capacity INTEGER NOT NULL DEFAULT 4 CHECK (capacity > 0),
+    ADD COLUMN zone TEXT NOT NULL DEFAULT 'main' CHECK (zone IN ('patio', 'bar', 'main')),
+    ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
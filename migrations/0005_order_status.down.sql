@@ -0,0 +1,13 @@
+ALTER TABLE "order" ADD COLUMN is_deleted BOOLEAN;
+UPDATE "order" SET is_deleted = status IN ('cancelled', 'voided');
+
+DROP INDEX idx_order_status;
+ALTER TABLE "order"
+    DROP COLUMN status,
+    DROP COLUMN placed_at,
+    DROP COLUMN accepted_at,
+    DROP COLUMN cooking_at,
+    DROP COLUMN ready_at,
+    DROP COLUMN served_at,
+    DROP COLUMN cancelled_at,
+    DROP COLUMN voided_at;
@@ -0,0 +1,27 @@
+ALTER TABLE "order"
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'placed'
+        CHECK (status IN ('placed', 'accepted', 'cooking', 'ready', 'served', 'cancelled', 'voided')),
+    ADD COLUMN placed_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN accepted_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN cooking_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN ready_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN served_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN cancelled_at TIMESTAMP WITHOUT TIME ZONE,
+    ADD COLUMN voided_at TIMESTAMP WITHOUT TIME ZONE;
+
+-- Existing orders: the deleted ones are cancelled, the cooked ones are ready, the rest are just placed.
+UPDATE "order" SET
+    placed_at = COALESCE(created_at, cooked_at),
+    status = CASE
+        WHEN is_deleted THEN 'cancelled'
+        WHEN cooked_at <= NOW() AT TIME ZONE 'UTC' THEN 'ready'
+        ELSE 'placed'
+    END,
+    cancelled_at = CASE WHEN is_deleted THEN COALESCE(updated_at, created_at, cooked_at) END,
+    ready_at = CASE WHEN is_deleted IS NOT TRUE AND cooked_at <= NOW() AT TIME ZONE 'UTC' THEN cooked_at END;
+
+ALTER TABLE "order"
+    ALTER COLUMN placed_at SET NOT NULL,
+    DROP COLUMN is_deleted;
+
+CREATE INDEX idx_order_status ON "order"(status);
@@ -0,0 +1,3 @@
+ALTER TABLE "order"
+    DROP COLUMN priority,
+    DROP COLUMN time_to_cook_in_minutes;
@@ -0,0 +1,11 @@
+-- The cooking time is copied from the menu when the order is placed, so changing the menu doesn't affect the placed orders.
+ALTER TABLE "order"
+    ADD COLUMN priority INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN time_to_cook_in_minutes INTEGER;
+
+UPDATE "order" AS o
+SET time_to_cook_in_minutes = m.time_to_cook_in_minutes
+FROM menu AS m
+WHERE m.menu_id = o.menu_id;
+
+ALTER TABLE "order" ALTER COLUMN time_to_cook_in_minutes SET NOT NULL;
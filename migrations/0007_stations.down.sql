@@ -0,0 +1,4 @@
+DROP INDEX idx_order_station_id;
+ALTER TABLE "order" DROP COLUMN station_id;
+ALTER TABLE menu DROP COLUMN station_id;
+DROP TABLE station;
@@ -0,0 +1,34 @@
+CREATE TABLE station (
+    station_id SERIAL PRIMARY KEY,
+    station_name TEXT NOT NULL UNIQUE,
+    slots INTEGER NOT NULL CHECK (slots > 0)
+);
+
+INSERT INTO station (station_id, station_name, slots)
+VALUES
+    (1, 'grill', 2),
+    (2, 'fryer', 2),
+    (3, 'cold', 2),
+    (4, 'bar', 1);
+
+SELECT setval(pg_get_serial_sequence('station', 'station_id'), MAX(station_id)) FROM station;
+
+-- Menu items without a station are cooked by the general kitchen.
+ALTER TABLE menu ADD COLUMN station_id INTEGER REFERENCES station(station_id);
+
+UPDATE menu SET station_id = 1
+WHERE menu_name IN ('Grilled Salmon with Dill Sauce', 'Mexican Chicken Fajitas', 'American Cheeseburger');
+UPDATE menu SET station_id = 2
+WHERE menu_name IN ('English Fish and Chips');
+UPDATE menu SET station_id = 3
+WHERE menu_name IN ('Chicken Caesar Salad', 'Japanese Sushi Rolls');
+
+-- The station is copied from the menu when the order is placed, like the cooking time.
+ALTER TABLE "order" ADD COLUMN station_id INTEGER REFERENCES station(station_id);
+
+UPDATE "order" AS o
+SET station_id = m.station_id
+FROM menu AS m
+WHERE m.menu_id = o.menu_id;
+
+CREATE INDEX idx_order_station_id ON "order"(station_id);
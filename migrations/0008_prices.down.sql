@@ -0,0 +1,6 @@
+ALTER TABLE "order"
+    DROP COLUMN price,
+    DROP COLUMN currency;
+ALTER TABLE menu
+    DROP COLUMN price,
+    DROP COLUMN currency;
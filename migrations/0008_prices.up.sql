@@ -0,0 +1,45 @@
+-- Prices are stored in the minor units of the currency, e.g. cents, so no rounding is ever needed.
+ALTER TABLE menu
+    ADD COLUMN price INTEGER NOT NULL DEFAULT 0 CHECK (price >= 0),
+    ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD' CHECK (currency ~ '^[A-Z]{3}$');
+
+UPDATE menu AS m
+SET price = p.price
+FROM (
+    VALUES
+        ('Classic Margherita Pizza', 1200),
+        ('Spaghetti Carbonara', 1450),
+        ('Vegetarian Lasagna', 1350),
+        ('Thai Green Curry', 1500),
+        ('Chicken Caesar Salad', 1100),
+        ('Beef Stroganoff', 1800),
+        ('Grilled Salmon with Dill Sauce', 2200),
+        ('Moroccan Chickpea Stew', 1250),
+        ('Szechuan Tofu Stir-Fry', 1300),
+        ('Lamb Rogan Josh', 1900),
+        ('French Onion Soup', 850),
+        ('Korean Bibimbap', 1400),
+        ('Mexican Chicken Fajitas', 1600),
+        ('Italian Risotto', 1550),
+        ('Greek Moussaka', 1700),
+        ('Indian Butter Chicken', 1650),
+        ('Spanish Paella', 2100),
+        ('American Cheeseburger', 1350),
+        ('Japanese Sushi Rolls', 1800),
+        ('English Fish and Chips', 1500)
+) AS p(menu_name, price)
+WHERE m.menu_name = p.menu_name;
+
+-- The price is copied from the menu when the order is placed, so changing the menu doesn't rewrite the placed orders.
+ALTER TABLE "order"
+    ADD COLUMN price INTEGER,
+    ADD COLUMN currency TEXT;
+
+UPDATE "order" AS o
+SET price = m.price, currency = m.currency
+FROM menu AS m
+WHERE m.menu_id = o.menu_id;
+
+ALTER TABLE "order"
+    ALTER COLUMN price SET NOT NULL,
+    ALTER COLUMN currency SET NOT NULL;
@@ -0,0 +1,5 @@
+DROP INDEX idx_order_bill_id;
+ALTER TABLE "order" DROP COLUMN bill_id;
+DROP TABLE bill_tax;
+DROP TABLE bill_line;
+DROP TABLE bill;
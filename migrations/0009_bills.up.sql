@@ -0,0 +1,39 @@
+-- A bill is a snapshot of what a table has paid for: it's never changed once created,
+-- even if the menu, the prices or the billing rules change later.
+CREATE TABLE bill (
+    bill_id SERIAL PRIMARY KEY,
+    table_id INTEGER NOT NULL REFERENCES "table"(table_id),
+    currency TEXT NOT NULL,
+    subtotal BIGINT NOT NULL,
+    -- Rates are in basis points, hundredths of a percent.
+    service_charge_rate INTEGER NOT NULL,
+    service_charge BIGINT NOT NULL,
+    tip BIGINT NOT NULL,
+    total BIGINT NOT NULL,
+    created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+CREATE TABLE bill_line (
+    bill_id INTEGER NOT NULL REFERENCES bill(bill_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    order_id INTEGER NOT NULL,
+    menu_id INTEGER NOT NULL,
+    menu_name TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    PRIMARY KEY (bill_id, position)
+);
+
+CREATE TABLE bill_tax (
+    bill_id INTEGER NOT NULL REFERENCES bill(bill_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    tax_name TEXT NOT NULL,
+    rate INTEGER NOT NULL,
+    amount BIGINT NOT NULL,
+    PRIMARY KEY (bill_id, position)
+);
+
+-- The orders of a table that haven't been billed yet make its current session.
+ALTER TABLE "order" ADD COLUMN bill_id INTEGER REFERENCES bill(bill_id);
+
+CREATE INDEX idx_order_bill_id ON "order"(bill_id);
+CREATE INDEX idx_bill_table_id ON bill(table_id);
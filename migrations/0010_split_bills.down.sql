@@ -0,0 +1,3 @@
+ALTER TABLE bill_line DROP COLUMN amount;
+ALTER TABLE bill DROP COLUMN seat;
+ALTER TABLE "order" DROP COLUMN seat;
@@ -0,0 +1,10 @@
+-- The seat the item is for, so that the bill can be split by seat.
+ALTER TABLE "order" ADD COLUMN seat INTEGER CHECK (seat > 0);
+
+-- The seat a bill has been split for.
+ALTER TABLE bill ADD COLUMN seat INTEGER;
+
+-- The part of the price paid with the bill, less than the price if the item is shared with other bills.
+ALTER TABLE bill_line ADD COLUMN amount BIGINT;
+UPDATE bill_line SET amount = price;
+ALTER TABLE bill_line ALTER COLUMN amount SET NOT NULL;
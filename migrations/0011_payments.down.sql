@@ -0,0 +1,2 @@
+DROP TABLE refund;
+DROP TABLE payment;
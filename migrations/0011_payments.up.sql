@@ -0,0 +1,29 @@
+-- Every attempt to pay a bill is recorded, including the declined ones.
+CREATE TABLE payment (
+    payment_id SERIAL PRIMARY KEY,
+    bill_id INTEGER NOT NULL REFERENCES bill(bill_id),
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    currency TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('approved', 'declined', 'timed_out')),
+    provider TEXT NOT NULL,
+    -- The provider's reference of the charge, only the approved payments have one.
+    reference TEXT,
+    -- Only the last four digits of the card are kept.
+    card_last4 TEXT NOT NULL,
+    decline_reason TEXT,
+    -- The sum of the refunds, kept with the payment so that it can be checked under the row lock.
+    refunded BIGINT NOT NULL DEFAULT 0 CHECK (refunded >= 0 AND refunded <= amount),
+    created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+CREATE TABLE refund (
+    refund_id SERIAL PRIMARY KEY,
+    payment_id INTEGER NOT NULL REFERENCES payment(payment_id),
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    reason TEXT,
+    reference TEXT NOT NULL,
+    created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payment_bill_id ON payment(bill_id);
+CREATE INDEX idx_refund_payment_id ON refund(payment_id);
@@ -0,0 +1,4 @@
+ALTER TABLE bill_line DROP COLUMN quantity;
+DROP TABLE order_modifier;
+ALTER TABLE "order" DROP COLUMN notes, DROP COLUMN quantity;
+DROP TABLE menu_modifier;
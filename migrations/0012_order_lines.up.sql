@@ -0,0 +1,50 @@
+-- The options a menu item can be ordered with, e.g. extra cheese or no onions.
+CREATE TABLE menu_modifier (
+    modifier_id SERIAL PRIMARY KEY,
+    menu_id INTEGER NOT NULL REFERENCES menu(menu_id),
+    position INTEGER NOT NULL,
+    modifier_name VARCHAR(255) NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('add', 'remove')),
+    -- Added to the price of the item, in minor units.
+    price_delta INTEGER NOT NULL DEFAULT 0 CHECK (price_delta >= 0),
+    -- Added to the cooking time of the item, in minutes.
+    cook_time_delta INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (menu_id, position)
+);
+
+INSERT INTO menu_modifier (menu_id, position, modifier_name, kind, price_delta, cook_time_delta)
+SELECT m.menu_id, modifier.position, modifier.modifier_name, modifier.kind, modifier.price_delta, modifier.cook_time_delta
+FROM (
+    VALUES
+        ('Classic Margherita Pizza', 1, 'Extra mozzarella', 'add', 200, 1),
+        ('Classic Margherita Pizza', 2, 'No basil', 'remove', 0, 0),
+        ('Spaghetti Carbonara', 1, 'No bacon', 'remove', 0, 0),
+        ('Spaghetti Carbonara', 2, 'Extra parmesan', 'add', 150, 0),
+        ('Chicken Caesar Salad', 1, 'No croutons', 'remove', 0, 0),
+        ('Chicken Caesar Salad', 2, 'Extra chicken', 'add', 350, 1),
+        ('American Cheeseburger', 1, 'Add bacon', 'add', 250, 1),
+        ('American Cheeseburger', 2, 'No onions', 'remove', 0, 0)
+) AS modifier(menu_name, position, modifier_name, kind, price_delta, cook_time_delta)
+JOIN menu AS m ON m.menu_name = modifier.menu_name
+ORDER BY m.menu_id, modifier.position;
+
+-- An order row is a line now: a number of the same item, cooked and served together.
+ALTER TABLE "order"
+    ADD COLUMN quantity INTEGER NOT NULL DEFAULT 1 CHECK (quantity > 0),
+    ADD COLUMN notes TEXT;
+
+-- The modifiers of an order line, copied when the order is placed, like the price.
+CREATE TABLE order_modifier (
+    order_id INTEGER NOT NULL REFERENCES "order"(order_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    -- Not a reference: the menu item may have its modifiers replaced later.
+    modifier_id INTEGER NOT NULL,
+    modifier_name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    price_delta INTEGER NOT NULL,
+    cook_time_delta INTEGER NOT NULL,
+    PRIMARY KEY (order_id, position)
+);
+
+-- The price of a bill line is the price of one item with its modifiers.
+ALTER TABLE bill_line ADD COLUMN quantity INTEGER NOT NULL DEFAULT 1;
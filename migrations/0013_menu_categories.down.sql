@@ -0,0 +1,3 @@
+DROP INDEX idx_menu_category_id;
+ALTER TABLE menu DROP COLUMN display_order, DROP COLUMN category_id;
+DROP TABLE category;
@@ -0,0 +1,31 @@
+CREATE TABLE category (
+    category_id SERIAL PRIMARY KEY,
+    category_name TEXT NOT NULL UNIQUE,
+    display_order INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT INTO category (category_id, category_name, display_order)
+VALUES
+    (1, 'Starters', 1),
+    (2, 'Mains', 2),
+    (3, 'Desserts', 3),
+    (4, 'Drinks', 4);
+
+SELECT setval(pg_get_serial_sequence('category', 'category_id'), MAX(category_id)) FROM category;
+
+-- Menu items without a category are shown after all the categories.
+ALTER TABLE menu
+    ADD COLUMN category_id INTEGER REFERENCES category(category_id),
+    ADD COLUMN display_order INTEGER NOT NULL DEFAULT 0;
+
+UPDATE menu SET category_id = 1
+WHERE menu_name IN ('Chicken Caesar Salad', 'French Onion Soup', 'Japanese Sushi Rolls');
+UPDATE menu SET category_id = 2
+WHERE menu_name IN (
+    'Classic Margherita Pizza', 'Spaghetti Carbonara', 'Vegetarian Lasagna', 'Thai Green Curry', 'Beef Stroganoff',
+    'Grilled Salmon with Dill Sauce', 'Moroccan Chickpea Stew', 'Szechuan Tofu Stir-Fry', 'Lamb Rogan Josh',
+    'Korean Bibimbap', 'Mexican Chicken Fajitas', 'Italian Risotto', 'Greek Moussaka', 'Indian Butter Chicken',
+    'Spanish Paella', 'American Cheeseburger', 'English Fish and Chips'
+);
+
+CREATE INDEX idx_menu_category_id ON menu(category_id);
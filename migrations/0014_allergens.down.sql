@@ -0,0 +1,2 @@
+ALTER TABLE "table" DROP COLUMN allergies;
+ALTER TABLE menu DROP COLUMN diets, DROP COLUMN allergens;
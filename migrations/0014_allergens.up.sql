@@ -0,0 +1,41 @@
+-- The 14 allergens food businesses in the EU must declare, and the diets an item can be suitable for.
+ALTER TABLE menu
+    ADD COLUMN allergens TEXT[] NOT NULL DEFAULT '{}' CHECK (allergens <@ ARRAY[
+        'celery', 'crustaceans', 'eggs', 'fish', 'gluten', 'lupin', 'milk',
+        'molluscs', 'mustard', 'nuts', 'peanuts', 'sesame', 'soya', 'sulphites'
+    ]),
+    ADD COLUMN diets TEXT[] NOT NULL DEFAULT '{}' CHECK (diets <@ ARRAY['vegan', 'vegetarian', 'halal']);
+
+-- The allergies the guests at the table have declared, the orders for it warn about them.
+ALTER TABLE "table"
+    ADD COLUMN allergies TEXT[] NOT NULL DEFAULT '{}' CHECK (allergies <@ ARRAY[
+        'celery', 'crustaceans', 'eggs', 'fish', 'gluten', 'lupin', 'milk',
+        'molluscs', 'mustard', 'nuts', 'peanuts', 'sesame', 'soya', 'sulphites'
+    ]);
+
+UPDATE menu AS m
+SET allergens = item.allergens::TEXT[], diets = item.diets::TEXT[]
+FROM (
+    VALUES
+        ('Classic Margherita Pizza', '{gluten,milk}', '{vegetarian}'),
+        ('Spaghetti Carbonara', '{eggs,gluten,milk}', '{}'),
+        ('Vegetarian Lasagna', '{eggs,gluten,milk}', '{vegetarian}'),
+        ('Thai Green Curry', '{crustaceans,fish}', '{}'),
+        ('Chicken Caesar Salad', '{eggs,fish,gluten,milk}', '{}'),
+        ('Beef Stroganoff', '{gluten,milk}', '{}'),
+        ('Grilled Salmon with Dill Sauce', '{fish,milk}', '{}'),
+        ('Moroccan Chickpea Stew', '{celery}', '{vegan,vegetarian,halal}'),
+        ('Szechuan Tofu Stir-Fry', '{gluten,peanuts,sesame,soya}', '{vegan,vegetarian}'),
+        ('Lamb Rogan Josh', '{milk}', '{halal}'),
+        ('French Onion Soup', '{celery,gluten,milk}', '{}'),
+        ('Korean Bibimbap', '{eggs,sesame,soya}', '{}'),
+        ('Mexican Chicken Fajitas', '{gluten,milk}', '{}'),
+        ('Italian Risotto', '{celery,milk,sulphites}', '{vegetarian}'),
+        ('Greek Moussaka', '{eggs,gluten,milk}', '{}'),
+        ('Indian Butter Chicken', '{milk,nuts}', '{halal}'),
+        ('Spanish Paella', '{celery,crustaceans,fish,molluscs}', '{}'),
+        ('American Cheeseburger', '{gluten,milk,mustard,sesame}', '{}'),
+        ('Japanese Sushi Rolls', '{fish,gluten,sesame,soya}', '{}'),
+        ('English Fish and Chips', '{fish,gluten}', '{}')
+) AS item(menu_name, allergens, diets)
+WHERE m.menu_name = item.menu_name;
@@ -0,0 +1 @@
+ALTER TABLE bill_line ALTER COLUMN price TYPE INTEGER;
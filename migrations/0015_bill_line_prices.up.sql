@@ -0,0 +1,2 @@
+-- The price of a billed item includes its modifiers, so it may not fit in an INTEGER like the price of the menu item.
+ALTER TABLE bill_line ALTER COLUMN price TYPE BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE "order" DROP COLUMN legacy_ready_at;
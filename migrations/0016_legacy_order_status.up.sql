@@ -0,0 +1,9 @@
+-- Before the statuses, an order was prepared once its cooking time had passed. The orders still cooking when
+-- the statuses were added were left `placed`, and the clients that don't know the statuses can't move them on,
+-- so they stay prepared by their cooking time. It is copied, as the scheduler moves `cooked_at` of the waiting items.
+ALTER TABLE "order" ADD COLUMN legacy_ready_at TIMESTAMP WITHOUT TIME ZONE;
+
+UPDATE "order" SET legacy_ready_at = cooked_at
+WHERE status = 'placed'
+    AND accepted_at IS NULL
+    AND placed_at < (SELECT applied_at FROM schema_migrations WHERE version = 5);
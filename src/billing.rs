@@ -0,0 +1,259 @@
+//! Computes the charges added to a bill: the service charge, the taxes and the tip.
+//!
+//! Amounts are in the minor units of the currency and rates are in basis points (hundredths of a percent),
+//! so everything is computed with integers. Every charge is rounded half up to a whole minor unit.
+//!
+//! When a bill is split, every amount is divided so that the parts add up to it exactly:
+//! the parts get their rounded down shares, and the minor units left are given out one by one (largest remainder).
+
+/// 100% in basis points.
+pub const FULL_RATE: i32 = 10_000;
+
+/// A tax added to every bill, a `[[billing.taxes]]` entry of the configuration file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaxRule {
+    pub name: String,
+    /// Basis points of the taxed amount, a percentage in the configuration file.
+    #[serde(with = "percent")]
+    pub rate: i32,
+    /// Whether the service charge is taxed too, otherwise only the subtotal is.
+    #[serde(default)]
+    pub on_service_charge: bool,
+}
+
+/// The charges every bill gets on top of the items.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BillingRules {
+    /// Basis points of the subtotal.
+    pub service_charge_rate: i32,
+    /// Applied in this order, every one of them to the subtotal and possibly the service charge, never to the other taxes.
+    pub taxes: Vec<TaxRule>,
+}
+
+/// The tip the guests leave, never taxed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tip {
+    /// An amount in minor units.
+    Amount(i64),
+    /// Basis points of the subtotal.
+    Rate(i32),
+}
+
+/// The charges of a bill, in minor units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Charges {
+    pub service_charge: i64,
+    /// In the order of `BillingRules::taxes`.
+    pub taxes: Vec<i64>,
+    pub tip: i64,
+    /// The subtotal with all the charges.
+    pub total: i64,
+}
+
+impl BillingRules {
+    /// Returns the charges of a bill with the specified subtotal.
+    ///
+    /// # Arguments
+    ///
+    /// * `subtotal` - the sum of the item prices in minor units;
+    /// * `tip` - the tip the guests leave.
+    pub fn charges(&self, subtotal: i64, tip: Tip) -> Charges {
+        let service_charge = apply_rate(subtotal, self.service_charge_rate);
+        let taxes: Vec<i64> = self.taxes
+            .iter()
+            .map(|tax| {
+                let taxed = if tax.on_service_charge { subtotal + service_charge } else { subtotal };
+                apply_rate(taxed, tax.rate)
+            })
+            .collect();
+        let tip = match tip {
+            Tip::Amount(amount) => amount,
+            Tip::Rate(rate) => apply_rate(subtotal, rate),
+        };
+
+        Charges {
+            service_charge,
+            total: subtotal + service_charge + taxes.iter().sum::<i64>() + tip,
+            taxes,
+            tip,
+        }
+    }
+}
+
+/// Returns the rate of the amount, rounded half up to a whole minor unit.
+pub fn apply_rate(amount: i64, rate: i32) -> i64 {
+    let scaled = amount * i64::from(rate);
+    let full = i64::from(FULL_RATE);
+    (scaled + full / 2).div_euclid(full)
+}
+
+/// Serializes basis points as a percentage, e.g. `825` as `8.25`, for `#[serde(with = "percent")]`.
+pub mod percent {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(rate: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(super::rate_to_percent(*rate))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        let percent = f64::deserialize(deserializer)?;
+        super::rate_from_percent(percent)
+            .ok_or_else(|| de::Error::custom("expected a percentage between 0 and 100 with at most two decimal places"))
+    }
+}
+
+/// Divides an amount in proportion to the weights, so that the parts add up to it exactly.
+///
+/// The minor units left after rounding the shares down go to the largest remainders,
+/// the earlier part first if they are equal. If all the weights are zero, the amount is divided equally.
+///
+/// # Arguments
+///
+/// * `amount` - the amount in minor units, not negative;
+/// * `weights` - the weight of each part, not negative, e.g. the subtotals of the split bills.
+pub fn allocate(amount: i64, weights: &[i64]) -> Vec<i64> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+    if weights.iter().all(|weight| *weight == 0) {
+        return allocate(amount, &vec![1; weights.len()]);
+    }
+
+    let total = i128::from(weights.iter().sum::<i64>());
+    let scaled: Vec<i128> = weights.iter().map(|weight| i128::from(amount) * i128::from(*weight)).collect();
+    let mut parts: Vec<i64> = scaled.iter().map(|share| (share / total) as i64).collect();
+
+    let mut left = amount - parts.iter().sum::<i64>();
+    let mut order: Vec<usize> = (0..parts.len()).collect();
+    // A stable sort keeps the earlier part first among the equal remainders
+    order.sort_by_key(|index| std::cmp::Reverse(scaled[*index] % total));
+    for index in order {
+        if left == 0 {
+            break;
+        }
+        parts[index] += 1;
+        left -= 1;
+    }
+
+    parts
+}
+
+/// Divides an amount into equal parts.
+///
+/// The minor units left go to the parts one by one, starting with `next`, which is then moved past them,
+/// so that dividing several amounts in turn keeps the parts within one minor unit of each other.
+///
+/// # Arguments
+///
+/// * `amount` - the amount in minor units, not negative;
+/// * `parts` - the number of parts, at least one;
+/// * `next` - the part that gets the next minor unit left.
+pub fn split_evenly(amount: i64, parts: usize, next: &mut usize) -> Vec<i64> {
+    let count = parts as i64;
+    let mut shares = vec![amount / count; parts];
+    for _ in 0..amount % count {
+        shares[*next] += 1;
+        *next = (*next + 1) % parts;
+    }
+
+    shares
+}
+
+/// Converts a percentage, e.g. `8.25`, to basis points.
+///
+/// Returns `None` if it's not between 0 and 100 or has more than two decimal places.
+pub fn rate_from_percent(percent: f64) -> Option<i32> {
+    let rate = percent * 100.0;
+    if !(0.0..=f64::from(FULL_RATE)).contains(&rate) || (rate - rate.round()).abs() > 1e-6 {
+        return None;
+    }
+    Some(rate.round() as i32)
+}
+
+/// Converts basis points to a percentage, e.g. `825` to `8.25`.
+pub fn rate_to_percent(rate: i32) -> f64 {
+    f64::from(rate) / 100.0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> BillingRules {
+        BillingRules {
+            service_charge_rate: 1000,
+            taxes: vec![
+                TaxRule { name: "State tax".to_string(), rate: 625, on_service_charge: true },
+                TaxRule { name: "City tax".to_string(), rate: 200, on_service_charge: false },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_charges() {
+        let charges = rules().charges(4550, Tip::Amount(500));
+
+        assert_eq!(charges.service_charge, 455);
+        // 6.25% of 50.05 is 3.128, 2% of 45.50 is 0.91
+        assert_eq!(charges.taxes, vec![313, 91]);
+        assert_eq!(charges.tip, 500);
+        assert_eq!(charges.total, 4550 + 455 + 313 + 91 + 500);
+    }
+
+    #[test]
+    fn test_tip_rate() {
+        let charges = BillingRules::default().charges(1999, Tip::Rate(1500));
+
+        assert_eq!((charges.service_charge, charges.taxes.len()), (0, 0));
+        assert_eq!(charges.tip, 300);
+        assert_eq!(charges.total, 2299);
+    }
+
+    #[test]
+    fn test_apply_rate() {
+        assert_eq!(apply_rate(100, 50), 1);
+        assert_eq!(apply_rate(99, 50), 0);
+        assert_eq!(apply_rate(0, 2500), 0);
+        assert_eq!(apply_rate(1234, FULL_RATE), 1234);
+    }
+
+    #[test]
+    fn test_allocate() {
+        // 100 in proportion to 1:1:1 is 33.33 each, the first part gets the unit left
+        assert_eq!(allocate(100, &[1, 1, 1]), vec![34, 33, 33]);
+        // 10 in proportion to 1200:850 is 5.85 and 4.15
+        assert_eq!(allocate(10, &[1200, 850]), vec![6, 4]);
+        assert_eq!(allocate(7, &[0, 5, 0]), vec![0, 7, 0]);
+        assert_eq!(allocate(5, &[0, 0]), vec![3, 2]);
+        assert_eq!(allocate(0, &[3, 4]), vec![0, 0]);
+        assert!(allocate(5, &[]).is_empty());
+
+        let parts = allocate(1_000_003, &[7, 11, 13, 17]);
+        assert_eq!(parts.iter().sum::<i64>(), 1_000_003);
+    }
+
+    #[test]
+    fn test_split_evenly() {
+        let mut next = 0;
+        assert_eq!(split_evenly(100, 3, &mut next), vec![34, 33, 33]);
+        assert_eq!(next, 1);
+        // The next units left go to the other parts
+        assert_eq!(split_evenly(101, 3, &mut next), vec![33, 34, 34]);
+        assert_eq!(next, 0);
+        assert_eq!(split_evenly(90, 3, &mut next), vec![30, 30, 30]);
+        assert_eq!(next, 0);
+    }
+
+    #[test]
+    fn test_rate_from_percent() {
+        assert_eq!(rate_from_percent(8.25), Some(825));
+        assert_eq!(rate_from_percent(0.0), Some(0));
+        assert_eq!(rate_from_percent(100.0), Some(FULL_RATE));
+        assert_eq!(rate_from_percent(8.875), None);
+        assert_eq!(rate_from_percent(-1.0), None);
+        assert_eq!(rate_from_percent(100.01), None);
+        assert_eq!(rate_to_percent(825), 8.25);
+    }
+}
@@ -0,0 +1,565 @@
+use std::{env, fmt::Display, fs, str::FromStr};
+
+use crate::{
+    billing::{self, percent, BillingRules, TaxRule},
+    scheduler::{Scheduler, SchedulingPolicy},
+};
+
+/// Environment variable with the path to the configuration file.
+const CONFIG_PATH_ENV: &str = "APP_CONFIG";
+
+pub const USAGE: &str = "Restaurant API
+
+Usage: restaurant_api [OPTIONS]
+       restaurant_api migrate [up | down <VERSION> | status] [OPTIONS]
+
+Commands:
+    migrate [up]                        apply the pending migrations and exit
+    migrate down <VERSION>              revert the migrations newer than VERSION, 0 reverts all
+    migrate status                      list the applied and pending migrations
+
+Options:
+    --config <PATH>                     TOML configuration file (env: APP_CONFIG)
+    --host <HOST>                       address to listen on (env: SERVER_URI)
+    --port <PORT>                       port to listen on (env: SERVER_PORT)
+    --threads <N>                       number of worker threads (env: THREADS_NUMBER)
+    --queue-capacity <N>                connections waiting for a worker (env: QUEUE_CAPACITY)
+    --keep-alive-timeout <SECS>         idle connection timeout (env: KEEP_ALIVE_TIMEOUT)
+    --header-timeout <SECS>             time to receive the headers of a request (env: HEADER_TIMEOUT)
+    --keep-alive-max-requests <N>       requests per connection (env: KEEP_ALIVE_MAX_REQUESTS)
+    --shutdown-timeout <SECS>           time to finish requests on shutdown (env: SHUTDOWN_TIMEOUT)
+    --storage <BACKEND>                 where to keep the data: postgres or memory (env: STORAGE_BACKEND)
+    --kitchen-slots <N>                 items cooked at the same time outside stations (env: KITCHEN_SLOTS)
+    --kitchen-policy <POLICY>           order of the waiting items: fifo or priority (env: KITCHEN_POLICY)
+    --service-charge <PERCENT>          service charge added to the bills (env: SERVICE_CHARGE)
+    --db-host <HOST>                    database host (env: DB_HOST)
+    --db-port <PORT>                    database port (env: DB_PORT)
+    --db-user <USER>                    database user (env: DB_USER)
+    --db-password <PASSWORD>            database password (env: DB_PASS)
+    --db-name <NAME>                    database name (env: DB_NAME)
+    --db-pool-min-size <N>              connections opened at startup (env: DB_POOL_MIN_SIZE)
+    --db-pool-max-size <N>              maximum open connections (env: DB_POOL_MAX_SIZE)
+    --db-pool-checkout-timeout <SECS>   time to wait for a free connection (env: DB_POOL_CHECKOUT_TIMEOUT)
+    -h, --help                          print this help
+
+Settings are applied in the following order, later ones overriding earlier ones:
+defaults, the configuration file, environment variables, command line options.";
+
+/// A setting that can be overridden with an environment variable or a command line option.
+struct Setting {
+    /// The key in the configuration file, `section.name`.
+    key: &'static str,
+    env: &'static str,
+    flag: &'static str,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting { key: "server.host", env: "SERVER_URI", flag: "--host" },
+    Setting { key: "server.port", env: "SERVER_PORT", flag: "--port" },
+    Setting { key: "server.threads", env: "THREADS_NUMBER", flag: "--threads" },
+    Setting { key: "server.queue_capacity", env: "QUEUE_CAPACITY", flag: "--queue-capacity" },
+    Setting { key: "server.keep_alive_timeout", env: "KEEP_ALIVE_TIMEOUT", flag: "--keep-alive-timeout" },
+    Setting { key: "server.header_timeout", env: "HEADER_TIMEOUT", flag: "--header-timeout" },
+    Setting { key: "server.keep_alive_max_requests", env: "KEEP_ALIVE_MAX_REQUESTS", flag: "--keep-alive-max-requests" },
+    Setting { key: "server.shutdown_timeout", env: "SHUTDOWN_TIMEOUT", flag: "--shutdown-timeout" },
+    Setting { key: "storage.backend", env: "STORAGE_BACKEND", flag: "--storage" },
+    Setting { key: "kitchen.slots", env: "KITCHEN_SLOTS", flag: "--kitchen-slots" },
+    Setting { key: "kitchen.policy", env: "KITCHEN_POLICY", flag: "--kitchen-policy" },
+    Setting { key: "billing.service_charge", env: "SERVICE_CHARGE", flag: "--service-charge" },
+    Setting { key: "database.host", env: "DB_HOST", flag: "--db-host" },
+    Setting { key: "database.port", env: "DB_PORT", flag: "--db-port" },
+    Setting { key: "database.user", env: "DB_USER", flag: "--db-user" },
+    Setting { key: "database.password", env: "DB_PASS", flag: "--db-password" },
+    Setting { key: "database.name", env: "DB_NAME", flag: "--db-name" },
+    Setting { key: "database.pool_min_size", env: "DB_POOL_MIN_SIZE", flag: "--db-pool-min-size" },
+    Setting { key: "database.pool_max_size", env: "DB_POOL_MAX_SIZE", flag: "--db-pool-max-size" },
+    Setting { key: "database.pool_checkout_timeout", env: "DB_POOL_CHECKOUT_TIMEOUT", flag: "--db-pool-checkout-timeout" },
+];
+
+
+/// The application configuration.
+///
+/// Loaded at startup by `Config::load`. Every setting has a default,
+/// so a configuration file is only needed to change some of them.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub kitchen: KitchenConfig,
+    pub billing: BillingConfig,
+    pub database: DatabaseConfig,
+}
+
+/// The `[server]` section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Number of worker threads, i.e. how many connections are served at the same time.
+    pub threads: usize,
+    /// How many connections may wait for a free worker.
+    pub queue_capacity: usize,
+    /// Seconds an idle persistent connection is kept open, and the longest pause in a request body.
+    pub keep_alive_timeout: u64,
+    /// Seconds the request line and the headers of a request may take to arrive.
+    pub header_timeout: u64,
+    /// Requests served over a single connection.
+    pub keep_alive_max_requests: usize,
+    /// Seconds given to requests in progress on shutdown.
+    pub shutdown_timeout: u64,
+}
+
+/// The `[storage]` section.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+}
+
+/// Where the application keeps its data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// PostgreSQL, configured in the `[database]` section.
+    #[default]
+    Postgres,
+    /// The process memory. Nothing is kept between runs, and no database is needed.
+    Memory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "postgres" => Ok(StorageBackend::Postgres),
+            "memory" => Ok(StorageBackend::Memory),
+            _ => Err("expected postgres or memory".to_string()),
+        }
+    }
+}
+
+/// The `[kitchen]` section: how the cooking times are estimated.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KitchenConfig {
+    /// Number of items cooked at the same time.
+    pub slots: usize,
+    pub policy: SchedulingPolicy,
+}
+
+/// The `[billing]` section: the charges added to the bills.
+///
+/// The taxes can only be set in the configuration file, as `[[billing.taxes]]` entries.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BillingConfig {
+    /// Basis points of the subtotal, a percentage in the configuration file.
+    #[serde(with = "percent")]
+    pub service_charge: i32,
+    pub taxes: Vec<TaxRule>,
+}
+
+/// The `[database]` section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub name: String,
+    /// Connections opened at startup.
+    pub pool_min_size: usize,
+    /// Maximum number of open connections.
+    pub pool_max_size: usize,
+    /// Seconds to wait for a free connection.
+    pub pool_checkout_timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 7878,
+            threads: 20,
+            queue_capacity: 100,
+            keep_alive_timeout: 5,
+            header_timeout: 10,
+            keep_alive_max_requests: 100,
+            shutdown_timeout: 10,
+        }
+    }
+}
+
+impl Default for KitchenConfig {
+    fn default() -> Self {
+        let Scheduler { slots, policy } = Scheduler::default();
+        Self { slots, policy }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 5433,
+            user: "postgres".to_string(),
+            password: "postgres".to_string(),
+            name: "postgres".to_string(),
+            pool_min_size: 2,
+            pool_max_size: 20,
+            pool_checkout_timeout: 5,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Returns the address to listen on, e.g. `127.0.0.1:7878`.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl KitchenConfig {
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler { slots: self.slots, policy: self.policy }
+    }
+}
+
+impl BillingConfig {
+    pub fn rules(&self) -> BillingRules {
+        BillingRules { service_charge_rate: self.service_charge, taxes: self.taxes.clone() }
+    }
+}
+
+impl DatabaseConfig {
+    /// Returns the connection settings for the `postgres` client.
+    pub fn connection_config(&self) -> postgres::Config {
+        let mut config = postgres::Config::new();
+        config
+            .host(&self.host)
+            .port(self.port)
+            .user(&self.user)
+            .password(&self.password)
+            .dbname(&self.name);
+        config
+    }
+}
+
+
+impl Config {
+    /// Loads the configuration from the configuration file, the environment variables and the command line options.
+    ///
+    /// The configuration file is given with `--config` or `APP_CONFIG`, none is read otherwise.
+    ///
+    /// Returns a description of every problem found if the configuration is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - command line arguments without the program name.
+    pub fn load(args: &[String]) -> Result<Config, String> {
+        Self::load_with(args, |name| env::var(name).ok())
+    }
+
+    fn load_with(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+        let Arguments { config_path, options } = parse_args(args)?;
+
+        let mut config = match config_path.or_else(|| env(CONFIG_PATH_ENV)) {
+            Some(path) => Self::from_file(&path)?,
+            None => Config::default(),
+        };
+
+        let mut errors = Vec::new();
+
+        for setting in SETTINGS {
+            if let Some(value) = env(setting.env) {
+                if let Err(error) = config.set(setting.key, &value) {
+                    errors.push(format!("Environment variable {}: {}", setting.env, error));
+                }
+            }
+        }
+
+        for (setting, value) in options {
+            if let Err(error) = config.set(setting.key, &value) {
+                errors.push(format!("Option {}: {}", setting.flag, error));
+            }
+        }
+
+        if errors.is_empty() {
+            errors = config.validate();
+        }
+
+        if !errors.is_empty() {
+            return Err(format!("Invalid configuration:\n  {}", errors.join("\n  ")));
+        }
+
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Config, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("Can not read the configuration file {}: {}", path, error))?;
+
+        toml::from_str(&content)
+            .map_err(|error| format!("Invalid configuration file {}: {}", path, error))
+    }
+
+    /// Sets a setting by its key from a string value.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let server = &mut self.server;
+        let database = &mut self.database;
+
+        match key {
+            "server.host" => server.host = value.to_string(),
+            "server.port" => server.port = parse(value)?,
+            "server.threads" => server.threads = parse(value)?,
+            "server.queue_capacity" => server.queue_capacity = parse(value)?,
+            "server.keep_alive_timeout" => server.keep_alive_timeout = parse(value)?,
+            "server.header_timeout" => server.header_timeout = parse(value)?,
+            "server.keep_alive_max_requests" => server.keep_alive_max_requests = parse(value)?,
+            "server.shutdown_timeout" => server.shutdown_timeout = parse(value)?,
+            "storage.backend" => self.storage.backend = parse(value)?,
+            "kitchen.slots" => self.kitchen.slots = parse(value)?,
+            "kitchen.policy" => self.kitchen.policy = parse(value)?,
+            "billing.service_charge" => self.billing.service_charge = parse_percent(value)?,
+            "database.host" => database.host = value.to_string(),
+            "database.port" => database.port = parse(value)?,
+            "database.user" => database.user = value.to_string(),
+            "database.password" => database.password = value.to_string(),
+            "database.name" => database.name = value.to_string(),
+            "database.pool_min_size" => database.pool_min_size = parse(value)?,
+            "database.pool_max_size" => database.pool_max_size = parse(value)?,
+            "database.pool_checkout_timeout" => database.pool_checkout_timeout = parse(value)?,
+            _ => return Err(format!("unknown setting {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the list of the invalid settings.
+    fn validate(&self) -> Vec<String> {
+        let server = &self.server;
+        let database = &self.database;
+        let unnamed_tax = self.billing.taxes.iter().any(|tax| tax.name.trim().is_empty());
+
+        [
+            (server.host.is_empty(), "server.host must not be empty"),
+            (server.port == 0, "server.port must not be 0"),
+            (server.threads == 0, "server.threads must be at least 1"),
+            (server.keep_alive_timeout == 0, "server.keep_alive_timeout must be at least 1 second"),
+            (server.header_timeout == 0, "server.header_timeout must be at least 1 second"),
+            (server.keep_alive_max_requests == 0, "server.keep_alive_max_requests must be at least 1"),
+            (self.kitchen.slots == 0, "kitchen.slots must be at least 1"),
+            (unnamed_tax, "billing.taxes must have names"),
+            (database.host.is_empty(), "database.host must not be empty"),
+            (database.port == 0, "database.port must not be 0"),
+            (database.user.is_empty(), "database.user must not be empty"),
+            (database.name.is_empty(), "database.name must not be empty"),
+            (database.pool_max_size == 0, "database.pool_max_size must be at least 1"),
+            (database.pool_min_size > database.pool_max_size, "database.pool_min_size must not exceed database.pool_max_size"),
+            (database.pool_checkout_timeout == 0, "database.pool_checkout_timeout must be at least 1 second"),
+        ]
+        .into_iter()
+        .filter(|(invalid, _)| *invalid)
+        .map(|(_, message)| message.to_string())
+        .collect()
+    }
+}
+
+
+/// Command line arguments split into the configuration file path and the setting overrides.
+struct Arguments {
+    config_path: Option<String>,
+    options: Vec<(&'static Setting, String)>,
+}
+
+/// Parses the command line arguments.
+///
+/// Options may be given as `--name value` or `--name=value`.
+fn parse_args(args: &[String]) -> Result<Arguments, String> {
+    let mut config_path = None;
+    let mut options = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(format!("Unexpected argument {}", arg));
+        }
+
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, value.to_string()),
+            None => (arg.as_str(), args.next().ok_or_else(|| format!("Option {} requires a value", arg))?.clone()),
+        };
+
+        if flag == "--config" {
+            config_path = Some(value);
+            continue;
+        }
+
+        match SETTINGS.iter().find(|setting| setting.flag == flag) {
+            Some(setting) => options.push((setting, value)),
+            None => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+
+    Ok(Arguments { config_path, options })
+}
+
+fn parse<T>(value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|error| format!("invalid value \"{}\": {}", value, error))
+}
+
+/// Parses a percentage, e.g. `8.25`, to basis points.
+fn parse_percent(value: &str) -> Result<i32, String> {
+    billing::rate_from_percent(parse(value)?)
+        .ok_or_else(|| format!("invalid value \"{}\": expected a percentage between 0 and 100 with at most two decimal places", value))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(args: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let env: HashMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+        Config::load_with(&args, |name| env.get(name).cloned())
+    }
+
+    fn write_file(name: &str, content: &str) -> String {
+        let path = env::temp_dir().join(format!("restaurant_api_{}_{}.toml", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = load(&[], &[]).unwrap();
+
+        assert_eq!(config.server.address(), "127.0.0.1:7878");
+        assert_eq!(config.server.threads, 20);
+        assert_eq!(config.database.port, 5433);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let path = write_file("precedence", "
+            [server]
+            port = 8000
+            threads = 4
+
+            [database]
+            host = \"db\"
+        ");
+
+        let config = load(
+            &["--config", &path, "--port=9000"],
+            &[("SERVER_PORT", "8500"), ("THREADS_NUMBER", "8")],
+        ).unwrap();
+
+        // CLI over environment over file over defaults
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.threads, 8);
+        assert_eq!(config.database.host, "db");
+        assert_eq!(config.database.user, "postgres");
+
+        // The file may be given with an environment variable, too
+        let config = load(&[], &[("APP_CONFIG", &path)]).unwrap();
+        assert_eq!(config.server.port, 8000);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let error = load(&["--threads", "0", "--db-pool-min-size", "30"], &[("SERVER_PORT", "abc")]).unwrap_err();
+        assert!(error.contains("Environment variable SERVER_PORT: invalid value \"abc\""), "{}", error);
+
+        let error = load(&["--threads", "0", "--db-pool-min-size", "30"], &[]).unwrap_err();
+        assert!(error.contains("server.threads must be at least 1"), "{}", error);
+        assert!(error.contains("database.pool_min_size must not exceed database.pool_max_size"), "{}", error);
+    }
+
+    #[test]
+    fn test_storage_backend() {
+        assert_eq!(load(&[], &[]).unwrap().storage.backend, StorageBackend::Postgres);
+        assert_eq!(load(&["--storage", "memory"], &[]).unwrap().storage.backend, StorageBackend::Memory);
+
+        let path = write_file("storage", "[storage]\nbackend = \"memory\"\n");
+        assert_eq!(load(&["--config", &path], &[]).unwrap().storage.backend, StorageBackend::Memory);
+
+        let error = load(&[], &[("STORAGE_BACKEND", "redis")]).unwrap_err();
+        assert!(error.contains("expected postgres or memory"), "{}", error);
+    }
+
+    #[test]
+    fn test_kitchen() {
+        let config = load(&[], &[]).unwrap();
+        assert_eq!((config.kitchen.slots, config.kitchen.policy), (4, SchedulingPolicy::Fifo));
+
+        let config = load(&["--kitchen-policy", "priority"], &[("KITCHEN_SLOTS", "2")]).unwrap();
+        assert_eq!((config.kitchen.slots, config.kitchen.policy), (2, SchedulingPolicy::Priority));
+
+        let error = load(&["--kitchen-slots", "0"], &[]).unwrap_err();
+        assert!(error.contains("kitchen.slots must be at least 1"), "{}", error);
+    }
+
+    #[test]
+    fn test_billing() {
+        let config = load(&[], &[]).unwrap();
+        assert_eq!(config.billing.rules(), BillingRules::default());
+
+        let path = write_file("billing", "
+            [billing]
+            service_charge = 12.5
+
+            [[billing.taxes]]
+            name = \"Sales tax\"
+            rate = 8.25
+            on_service_charge = true
+        ");
+        let rules = load(&["--config", &path], &[]).unwrap().billing.rules();
+        assert_eq!(rules.service_charge_rate, 1250);
+        assert_eq!(rules.taxes, vec![TaxRule { name: "Sales tax".to_string(), rate: 825, on_service_charge: true }]);
+
+        let config = load(&["--config", &path, "--service-charge", "0"], &[]).unwrap();
+        assert_eq!(config.billing.service_charge, 0);
+
+        let error = load(&[], &[("SERVICE_CHARGE", "8.125")]).unwrap_err();
+        assert!(error.contains("Environment variable SERVICE_CHARGE: invalid value \"8.125\""), "{}", error);
+
+        let path = write_file("billing_rate", "[[billing.taxes]]\nname = \"VAT\"\nrate = 120\n");
+        assert!(load(&["--config", &path], &[]).unwrap_err().starts_with("Invalid configuration file"));
+
+        let path = write_file("billing_name", "[[billing.taxes]]\nname = \"\"\nrate = 5\n");
+        assert!(load(&["--config", &path], &[]).unwrap_err().contains("billing.taxes must have names"));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert_eq!(load(&["--unknown", "1"], &[]).unwrap_err(), "Unknown option --unknown");
+        assert_eq!(load(&["--port"], &[]).unwrap_err(), "Option --port requires a value");
+        assert_eq!(load(&["serve"], &[]).unwrap_err(), "Unexpected argument serve");
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let path = write_file("unknown_key", "[server]\nprot = 8000\n");
+        let error = load(&["--config", &path], &[]).unwrap_err();
+        assert!(error.starts_with("Invalid configuration file"), "{}", error);
+        assert!(error.contains("prot"), "{}", error);
+
+        let error = load(&["--config", "/nonexistent/config.toml"], &[]).unwrap_err();
+        assert!(error.starts_with("Can not read the configuration file"), "{}", error);
+    }
+}
@@ -0,0 +1,257 @@
+use postgres::{Client, GenericClient};
+use sha2::{Digest, Sha256};
+
+/// Key of the advisory lock that keeps two servers from migrating the same database at once.
+const LOCK_KEY: i64 = 7_878_001;
+
+/// A schema change, stored in the `migrations` directory as `<name>.up.sql` and `<name>.down.sql`.
+///
+/// The files are embedded in the binary, so a build always carries the migrations it was written for.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i32,
+    /// File name without the extension, e.g. `0001_create_tables`.
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+/// A row of the `schema_migrations` table.
+#[derive(Debug)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub name: String,
+    pub checksum: String,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../migrations/", $name, ".up.sql")),
+            down: include_str!(concat!("../../migrations/", $name, ".down.sql")),
+        }
+    };
+}
+
+/// All migrations in the order they are applied.
+///
+/// A migration must never be edited once it has been applied somewhere: add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_create_tables"),
+    migration!(2, "0002_seed_tables_and_menu"),
+    migration!(3, "0003_menu_soft_delete"),
+    migration!(4, "0004_table_attributes"),
+    migration!(5, "0005_order_status"),
+    migration!(6, "0006_order_scheduling"),
+    migration!(7, "0007_stations"),
+    migration!(8, "0008_prices"),
+    migration!(9, "0009_bills"),
+    migration!(10, "0010_split_bills"),
+    migration!(11, "0011_payments"),
+    migration!(12, "0012_order_lines"),
+    migration!(13, "0013_menu_categories"),
+    migration!(14, "0014_allergens"),
+    migration!(15, "0015_bill_line_prices"),
+    migration!(16, "0016_legacy_order_status"),
+];
+
+impl Migration {
+    /// Returns the SHA-256 of the up script as a hex string.
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.up.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+
+/// Applies all pending migrations.
+///
+/// Everything runs in one transaction, so either all pending migrations are applied or none.
+/// Refuses to run if an applied migration has been changed or is missing from this build.
+///
+/// Returns the applied migrations.
+pub fn migrate(client: &mut Client) -> Result<Vec<&'static Migration>, String> {
+    let mut transaction = client.transaction().map_err(|error| error.to_string())?;
+    let applied = prepare(&mut transaction)?;
+    let pending = pending(MIGRATIONS, &applied)?;
+
+    for migration in &pending {
+        transaction
+            .batch_execute(migration.up)
+            .map_err(|error| format!("Migration {} failed: {}", migration.name, error))?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations (version, name, checksum) VALUES ($1, $2, $3)",
+                &[&migration.version, &migration.name, &migration.checksum()],
+            )
+            .map_err(|error| error.to_string())?;
+    }
+
+    transaction.commit().map_err(|error| error.to_string())?;
+    Ok(pending)
+}
+
+/// Reverts the applied migrations newer than `version`, the newest first.
+///
+/// `0` reverts all of them.
+///
+/// Returns the reverted migrations.
+pub fn rollback(client: &mut Client, version: i32) -> Result<Vec<&'static Migration>, String> {
+    let mut transaction = client.transaction().map_err(|error| error.to_string())?;
+    let applied = prepare(&mut transaction)?;
+    pending(MIGRATIONS, &applied)?;
+
+    let reverted: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .rev()
+        .filter(|migration| migration.version > version)
+        .filter(|migration| applied.iter().any(|row| row.version == migration.version))
+        .collect();
+
+    for migration in &reverted {
+        transaction
+            .batch_execute(migration.down)
+            .map_err(|error| format!("Reverting migration {} failed: {}", migration.name, error))?;
+        transaction
+            .execute("DELETE FROM schema_migrations WHERE version = $1", &[&migration.version])
+            .map_err(|error| error.to_string())?;
+    }
+
+    transaction.commit().map_err(|error| error.to_string())?;
+    Ok(reverted)
+}
+
+/// Returns the migrations recorded in the database.
+pub fn applied(client: &mut Client) -> Result<Vec<AppliedMigration>, String> {
+    let mut transaction = client.transaction().map_err(|error| error.to_string())?;
+    let applied = prepare(&mut transaction)?;
+    transaction.commit().map_err(|error| error.to_string())?;
+
+    Ok(applied)
+}
+
+/// Takes the migration lock for the rest of the transaction,
+/// creates the tracking table if needed and reads the applied migrations.
+fn prepare(client: &mut impl GenericClient) -> Result<Vec<AppliedMigration>, String> {
+    client
+        .execute("SELECT pg_advisory_xact_lock($1)", &[&LOCK_KEY])
+        .map_err(|error| error.to_string())?;
+
+    client
+        .batch_execute("
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMP WITHOUT TIME ZONE NOT NULL DEFAULT NOW()
+        );
+        ")
+        .map_err(|error| error.to_string())?;
+
+    let rows = client
+        .query("SELECT version, name, checksum FROM schema_migrations ORDER BY version", &[])
+        .map_err(|error| error.to_string())?;
+
+    Ok(rows
+        .iter()
+        .map(|row| AppliedMigration {
+            version: row.get("version"),
+            name: row.get("name"),
+            checksum: row.get("checksum"),
+        })
+        .collect())
+}
+
+/// Verifies the applied migrations against the known ones and returns those that haven't been applied yet.
+///
+/// # Arguments
+///
+/// * `migrations` - the known migrations, in order;
+/// * `applied` - the migrations recorded in the database.
+pub fn pending<'a>(migrations: &'a [Migration], applied: &[AppliedMigration]) -> Result<Vec<&'a Migration>, String> {
+    for row in applied {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.version == row.version)
+            .ok_or_else(|| format!(
+                "Migration {} has been applied to the database, but this build doesn't know it",
+                row.name,
+            ))?;
+
+        if migration.checksum() != row.checksum {
+            return Err(format!(
+                "Migration {} has been changed after it was applied. Restore it and add a new migration instead",
+                migration.name,
+            ));
+        }
+    }
+
+    Ok(migrations
+        .iter()
+        .filter(|migration| !applied.iter().any(|row| row.version == migration.version))
+        .collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(migration: &Migration) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            name: migration.name.to_string(),
+            checksum: migration.checksum(),
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i32 + 1);
+            assert!(migration.name.starts_with(&format!("{:04}_", migration.version)), "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending(MIGRATIONS, &[]).unwrap().len(), MIGRATIONS.len());
+
+        let pending = pending(MIGRATIONS, &[applied(&MIGRATIONS[0])]).unwrap();
+        assert_eq!(pending.len(), MIGRATIONS.len() - 1);
+        assert_eq!(pending[0].version, 2);
+    }
+
+    #[test]
+    fn test_changed_migration() {
+        let mut row = applied(&MIGRATIONS[0]);
+        row.checksum = Migration { up: "DROP TABLE menu;", ..migration!(1, "0001_create_tables") }.checksum();
+
+        let error = pending(MIGRATIONS, &[row]).unwrap_err();
+        assert!(error.starts_with("Migration 0001_create_tables has been changed"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_migration() {
+        let row = AppliedMigration {
+            version: 999,
+            name: "0999_from_the_future".to_string(),
+            checksum: String::new(),
+        };
+
+        let error = pending(MIGRATIONS, &[row]).unwrap_err();
+        assert!(error.contains("0999_from_the_future"), "{}", error);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let checksum = MIGRATIONS[0].checksum();
+        assert_eq!(checksum.len(), 64);
+        assert_eq!(checksum, MIGRATIONS[0].checksum());
+        assert_ne!(checksum, MIGRATIONS[1].checksum());
+    }
+}
@@ -1,25 +1,25 @@
 use postgres::{Row, types::ToSql};
 
-use crate::database;
+use crate::{database, error::AppError};
 
 /// Model trait. All models should implement it.
 /// Share a default implementation for a database queries.
 pub trait Model {
     const TABLE_NAME: &'static str;
 
-    fn query_all_rows() -> Result<Vec<Row>, String> {
+    fn query_all_rows() -> Result<Vec<Row>, AppError> {
         database::client()?
             .query(&format!("SELECT * FROM \"{}\"", Self::TABLE_NAME), &[])
-            .map_err(|error| error.to_string())
+            .map_err(AppError::from)
     }
 
     /// Executes a statement, returning the resulting rows.
     /// 
     /// A statement may contain parameters, specified by $n, where n is the index of the parameter of the list provided, 1-indexed.
-    fn query(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, String> {
+    fn query(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, AppError> {
         database::client()?
             .query(query, params)
-            .map_err(|error| error.to_string())
+            .map_err(AppError::from)
     }
     
     /// Executes a statement which returns a single row, returning it.
@@ -27,10 +27,21 @@ pub trait Model {
     /// Returns an error if the query does not return exactly one row.
     /// 
     /// A statement may contain parameters, specified by $n, where n is the index of the parameter of the list provided, 1-indexed.
-    fn query_one(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Row, String> {
+    fn query_one(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Row, AppError> {
         database::client()?
             .query_one(query, params)
-            .map_err(|error| error.to_string())
+            .map_err(AppError::from)
+    }
+
+    /// Executes a statement which returns zero or one rows, returning it.
+    ///
+    /// Returns an error if the query returns more than one row.
+    /// 
+    /// A statement may contain parameters, specified by $n, where n is the index of the parameter of the list provided, 1-indexed.
+    fn query_opt(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Option<Row>, AppError> {
+        database::client()?
+            .query_opt(query, params)
+            .map_err(AppError::from)
     }
 
     /// Executes a statement, returning the number of rows modified.
@@ -38,24 +49,24 @@ pub trait Model {
     /// A statement may contain parameters, specified by $n, where n is the index of the parameter of the list provided, 1-indexed.
     /// 
     /// If the statement does not modify any rows (e.g. SELECT), 0 is returned.
-    fn execute(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<u64, String> {
+    fn execute(query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<u64, AppError> {
         database::client()?
             .execute(query, params)
-            .map_err(|error| error.to_string())
-        }
+            .map_err(AppError::from)
+    }
 
     /// Executes a statement, returning the number of rows modified.
     /// 
     /// A statement may contain parameters, specified by $n, where n is the index of the parameter of the list provided, 1-indexed.
     /// 
     /// If the statement does not modify any rows (e.g. SELECT), 0 is returned.
-    fn delete_by_id(id: i32) -> Result<u64, String> {
+    fn delete_by_id(id: i32) -> Result<u64, AppError> {
         database::client()?
             .execute(
                 &format!("DELETE FROM \"{}\" WHERE id = $1", Self::TABLE_NAME),
                 &[&id]
             )
-            .map_err(|error| error.to_string())
+            .map_err(AppError::from)
     }
 
 }
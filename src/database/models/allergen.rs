@@ -0,0 +1,218 @@
+use std::str::FromStr;
+
+use postgres::Row;
+
+/// One of the 14 allergens that EU food businesses must declare.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Allergen {
+    Celery,
+    Crustaceans,
+    Eggs,
+    Fish,
+    /// Cereals containing gluten: wheat, rye, barley and oats.
+    Gluten,
+    Lupin,
+    Milk,
+    Molluscs,
+    Mustard,
+    /// Tree nuts: almonds, hazelnuts, walnuts and the like.
+    Nuts,
+    Peanuts,
+    Sesame,
+    Soya,
+    /// Sulphur dioxide and sulphites.
+    Sulphites,
+}
+
+impl Allergen {
+    pub const ALL: [Allergen; 14] = [
+        Allergen::Celery,
+        Allergen::Crustaceans,
+        Allergen::Eggs,
+        Allergen::Fish,
+        Allergen::Gluten,
+        Allergen::Lupin,
+        Allergen::Milk,
+        Allergen::Molluscs,
+        Allergen::Mustard,
+        Allergen::Nuts,
+        Allergen::Peanuts,
+        Allergen::Sesame,
+        Allergen::Soya,
+        Allergen::Sulphites,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Allergen::Celery => "celery",
+            Allergen::Crustaceans => "crustaceans",
+            Allergen::Eggs => "eggs",
+            Allergen::Fish => "fish",
+            Allergen::Gluten => "gluten",
+            Allergen::Lupin => "lupin",
+            Allergen::Milk => "milk",
+            Allergen::Molluscs => "molluscs",
+            Allergen::Mustard => "mustard",
+            Allergen::Nuts => "nuts",
+            Allergen::Peanuts => "peanuts",
+            Allergen::Sesame => "sesame",
+            Allergen::Soya => "soya",
+            Allergen::Sulphites => "sulphites",
+        }
+    }
+}
+
+impl FromStr for Allergen {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Allergen::ALL
+            .into_iter()
+            .find(|allergen| allergen.as_str() == value)
+            .ok_or_else(|| format!("Undefined allergen {}", value))
+    }
+}
+
+
+/// A diet a menu item is suitable for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Diet {
+    Vegan,
+    Vegetarian,
+    Halal,
+}
+
+impl Diet {
+    pub const ALL: [Diet; 3] = [Diet::Vegan, Diet::Vegetarian, Diet::Halal];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Diet::Vegan => "vegan",
+            Diet::Vegetarian => "vegetarian",
+            Diet::Halal => "halal",
+        }
+    }
+
+    /// Returns the allergens that come from animals the diet excludes, so an item of this diet can't contain them.
+    pub fn excluded_allergens(&self) -> &'static [Allergen] {
+        match self {
+            Diet::Vegan => &[Allergen::Crustaceans, Allergen::Eggs, Allergen::Fish, Allergen::Milk, Allergen::Molluscs],
+            Diet::Vegetarian => &[Allergen::Crustaceans, Allergen::Fish, Allergen::Molluscs],
+            Diet::Halal => &[],
+        }
+    }
+}
+
+impl FromStr for Diet {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Diet::ALL
+            .into_iter()
+            .find(|diet| diet.as_str() == value)
+            .ok_or_else(|| format!("Undefined diet {}", value))
+    }
+}
+
+
+/// Returns the values sorted and without repeats, the way they are stored.
+///
+/// The allergens are sorted by their names, the diets from the strictest one.
+pub fn normalize<T: Copy + Ord>(values: &[T]) -> Vec<T> {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// Reads a `TEXT[]` column of allergens or diets.
+pub(super) fn from_column<T: FromStr>(row: &Row, column: &str) -> Vec<T> {
+    row.get::<_, Vec<String>>(column)
+        .iter()
+        // The columns only take the known values
+        .map(|value| value.parse().unwrap_or_else(|_| panic!("Unknown value {} stored in {}", value, column)))
+        .collect()
+}
+
+/// Parses a comma-separated list like `gluten,nuts`, the empty parts are skipped.
+pub fn parse_list<T: FromStr>(list: &str) -> Result<Vec<T>, T::Err> {
+    list
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+
+/// Warns the waiter that an ordered item contains allergens the table has declared allergies to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllergenWarning {
+    pub allergens: Vec<Allergen>,
+    pub message: String,
+}
+
+impl AllergenWarning {
+    /// Returns the warning for an item, `None` if it contains none of the allergies.
+    ///
+    /// # Arguments
+    ///
+    /// * `menu_name` - the name of the item;
+    /// * `allergens` - the allergens the item contains;
+    /// * `allergies` - the allergies declared for the table.
+    pub fn new(menu_name: &str, allergens: &[Allergen], allergies: &[Allergen]) -> Option<Self> {
+        let conflicts: Vec<Allergen> = normalize(allergens)
+            .into_iter()
+            .filter(|allergen| allergies.contains(allergen))
+            .collect();
+        if conflicts.is_empty() {
+            return None;
+        }
+
+        let names: Vec<&str> = conflicts.iter().map(Allergen::as_str).collect();
+        Some(AllergenWarning {
+            message: format!("{} contains {}, which the table is allergic to", menu_name, names.join(", ")),
+            allergens: conflicts,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for allergen in Allergen::ALL {
+            assert_eq!(allergen.as_str().parse::<Allergen>(), Ok(allergen));
+            assert_eq!(serde_json::to_value(allergen).unwrap(), allergen.as_str());
+        }
+        for diet in Diet::ALL {
+            assert_eq!(diet.as_str().parse::<Diet>(), Ok(diet));
+            assert_eq!(serde_json::to_value(diet).unwrap(), diet.as_str());
+        }
+        assert!("shellfish".parse::<Allergen>().is_err());
+        assert!("keto".parse::<Diet>().is_err());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list::<Allergen>("gluten, nuts,,"), Ok(vec![Allergen::Gluten, Allergen::Nuts]));
+        assert_eq!(parse_list::<Diet>(""), Ok(Vec::new()));
+        assert!(parse_list::<Allergen>("gluten,shellfish").is_err());
+        assert_eq!(normalize(&[Allergen::Nuts, Allergen::Gluten, Allergen::Nuts]), vec![Allergen::Gluten, Allergen::Nuts]);
+    }
+
+    #[test]
+    fn test_warning() {
+        let carbonara = [Allergen::Milk, Allergen::Gluten, Allergen::Eggs];
+        assert_eq!(AllergenWarning::new("Spaghetti Carbonara", &carbonara, &[Allergen::Nuts]), None);
+
+        let warning = AllergenWarning::new("Spaghetti Carbonara", &carbonara, &[Allergen::Milk, Allergen::Gluten]).unwrap();
+        assert_eq!(warning.allergens, vec![Allergen::Gluten, Allergen::Milk]);
+        assert_eq!(warning.message, "Spaghetti Carbonara contains gluten, milk, which the table is allergic to");
+    }
+}
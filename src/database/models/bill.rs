@@ -0,0 +1,926 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use postgres::{Row, Transaction};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    billing::{self, percent, BillingRules, Tip},
+    database::{self, model::Model},
+    error::AppError,
+};
+use std::fmt;
+use super::{order::{self, Order, OrderOutput}, payment, table::{self, Table}};
+
+/// A model for the `bill` DB table, with its `bill_line` and `bill_tax` rows.
+///
+/// DB table: `bill`
+///
+/// ## Fields:
+///
+/// - `id` - i32 - bill id
+/// - `table_id` - i32 - the table that has paid the bill
+/// - `seat` - i32 - the seat the bill has been split for, `None` if it isn't split by seat
+/// - `currency` - String - the currency of all the amounts
+/// - `lines` - Vec<BillLine> - the order lines paid for, with their names, quantities and prices at the checkout time
+/// - `subtotal` - i64 - the sum of the amounts paid for the items in minor units
+/// - `service_charge` - ServiceCharge - the service charge and its rate
+/// - `taxes` - Vec<BillTax> - the taxes, in the order of the billing rules
+/// - `tip` - i64 - the tip in minor units
+/// - `total` - i64 - the subtotal with all the charges
+/// - `created_at` - SystemTime - checkout time.
+///
+/// A bill is never changed once created, whatever happens to the menu or the billing rules.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bill {
+    pub id: Option<i32>,
+    pub table_id: i32,
+    pub seat: Option<i32>,
+    pub currency: String,
+    pub lines: Vec<BillLine>,
+    pub subtotal: i64,
+    pub service_charge: ServiceCharge,
+    pub taxes: Vec<BillTax>,
+    pub tip: i64,
+    pub total: i64,
+    pub created_at: SystemTime,
+}
+
+impl Model for Bill {
+    const TABLE_NAME: &'static str = "bill";
+}
+
+/// The most bills a table can be split into.
+pub const MAX_SPLIT_BILLS: usize = 20;
+
+impl Bill {
+    /// Bills everything the table has ordered since its previous bill and returns the bill.
+    ///
+    /// The billed orders get the bill ID, so the table starts a new session with its next order.
+    ///
+    /// ##The table can not be checked out in the cases:
+    ///
+    /// - if there is no table with the specified ID (`AppError::NotFound`)
+    /// - if the tip is invalid (`AppError::Validation`)
+    /// - if there is nothing to pay for or the items are priced in several currencies, see `Bill::prepare` (`AppError::Conflict`).
+    ///
+    /// # Arguments
+    ///
+    /// * `table_id` - the table to check out;
+    /// * `params` - the tip;
+    /// * `rules` - the service charge and the taxes;
+    /// * `now` - the checkout time.
+    pub fn checkout(table_id: i32, params: CheckoutParams, rules: &BillingRules, now: SystemTime) -> Result<BillOutput, AppError> {
+        let tip = params.tip()?;
+
+        let mut bills = check_out(table_id, now, |orders| Ok(vec![Bill::prepare(table_id, orders, tip, rules, now)?]))?;
+        Ok(bills.remove(0))
+    }
+
+    /// Bills everything the table has ordered since its previous bill in several bills and returns them.
+    ///
+    /// The bills add up exactly to the bill the table would get at once, see `Bill::prepare_split`.
+    ///
+    /// ##The table can not be split in the cases:
+    ///
+    /// - if there is no table with the specified ID (`AppError::NotFound`)
+    /// - if the split or the tip is invalid, or the items of the split aren't the ones to pay for (`AppError::Validation`)
+    /// - if the table can't be checked out, or it's split by seat and no item has a seat (`AppError::Conflict`).
+    ///
+    /// # Arguments
+    ///
+    /// * `table_id` - the table to check out;
+    /// * `params` - the way to split and the tip;
+    /// * `rules` - the service charge and the taxes;
+    /// * `now` - the checkout time.
+    pub fn split(table_id: i32, params: SplitParams, rules: &BillingRules, now: SystemTime) -> Result<Vec<BillOutput>, AppError> {
+        let tip = params.validate()?;
+
+        check_out(table_id, now, |orders| Bill::prepare_split(table_id, orders, &params.split, tip, rules, now))
+    }
+
+    /// Returns a bill with specified ID.
+    ///
+    /// If there is no such bill, `AppError::NotFound` is returned.
+    pub fn get_one(bill_id: i32) -> Result<BillOutput, AppError> {
+        database::transaction(|transaction| {
+            let row = transaction.query_opt(
+                &format!("SELECT b.*, {} FROM \"{}\" AS b WHERE b.bill_id = $1", payment::PAID_COLUMN, Self::TABLE_NAME),
+                &[&bill_id],
+            )?.ok_or_else(|| not_found(bill_id))?;
+
+            let lines = transaction
+                .query("SELECT * FROM bill_line WHERE bill_id = $1 ORDER BY position", &[&bill_id])?
+                .iter()
+                .map(BillLine::from_row)
+                .collect();
+            let taxes = transaction
+                .query("SELECT * FROM bill_tax WHERE bill_id = $1 ORDER BY position", &[&bill_id])?
+                .iter()
+                .map(BillTax::from_row)
+                .collect();
+
+            Ok(BillOutput::from_row(&row, lines, taxes))
+        })
+    }
+
+    /// Computes the bill of a table session, without storing it.
+    ///
+    /// The cancelled and voided items are left out, the ones that haven't been served yet are billed as well.
+    /// `AppError::Conflict` is returned if there is nothing to pay for or the items are priced in several currencies.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_id` - the table to check out;
+    /// * `orders` - the orders of the table that haven't been billed yet;
+    /// * `tip` - the tip the guests leave;
+    /// * `rules` - the service charge and the taxes;
+    /// * `now` - the checkout time.
+    pub fn prepare(table_id: i32, orders: &[OrderOutput], tip: Tip, rules: &BillingRules, now: SystemTime) -> Result<Bill, AppError> {
+        let lines: Vec<&OrderOutput> = orders.iter().filter(|order| !order.status.is_deleted()).collect();
+        let mut currencies: Vec<&str> = lines.iter().map(|order| order.currency.as_str()).collect();
+        currencies.sort_unstable();
+        currencies.dedup();
+        let currency = match currencies.as_slice() {
+            [] => return Err(AppError::Conflict(format!("Table {} has nothing to pay for", table_id))),
+            [currency] => currency.to_string(),
+            _ => return Err(AppError::Conflict(format!(
+                "Table {} has items in several currencies and can't be billed at once: {}",
+                table_id,
+                currencies.join(", "),
+            ))),
+        };
+
+        let lines: Vec<BillLine> = lines
+            .into_iter()
+            .map(|order| BillLine {
+                order_id: order.id,
+                menu_id: order.menu_id,
+                menu_name: order.menu_name.clone(),
+                quantity: order.quantity,
+                price: order.unit_price(),
+                amount: order.amount,
+            })
+            .collect();
+        let subtotal = lines.iter().map(|line| line.amount).sum();
+        let charges = rules.charges(subtotal, tip);
+
+        Ok(Bill {
+            id: None,
+            table_id,
+            seat: None,
+            currency,
+            lines,
+            subtotal,
+            service_charge: ServiceCharge { rate: rules.service_charge_rate, amount: charges.service_charge },
+            taxes: rules.taxes
+                .iter()
+                .zip(charges.taxes)
+                .map(|(tax, amount)| BillTax { name: tax.name.clone(), rate: tax.rate, amount })
+                .collect(),
+            tip: charges.tip,
+            total: charges.total,
+            created_at: now,
+        })
+    }
+
+    /// Computes the bills of a table session split in several parts, without storing them.
+    ///
+    /// The bill of the whole session is prepared first, see `Bill::prepare`, and then divided:
+    ///
+    /// - `Split::Even` shares every item between all the bills;
+    /// - `Split::Seat` makes a bill for every seat, the items without a seat are shared between all of them;
+    /// - `Split::Items` makes a bill for every list of order IDs, which must list every item to pay for once.
+    ///
+    /// A shared item is divided evenly, the minor units left going to the bills in turn.
+    /// The charges are divided in proportion to the subtotals of the bills, see `billing::allocate`,
+    /// so every amount of the bills adds up exactly to the one of the whole bill.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_id` - the table to check out;
+    /// * `orders` - the orders of the table that haven't been billed yet;
+    /// * `split` - how to split the bill, checked with `SplitParams::validate`;
+    /// * `tip` - the tip the guests leave altogether;
+    /// * `rules` - the service charge and the taxes;
+    /// * `now` - the checkout time.
+    pub fn prepare_split(
+        table_id: i32,
+        orders: &[OrderOutput],
+        split: &Split,
+        tip: Tip,
+        rules: &BillingRules,
+        now: SystemTime,
+    ) -> Result<Vec<Bill>, AppError> {
+        let bill = Bill::prepare(table_id, orders, tip, rules, now)?;
+        let seats: HashMap<i32, Option<i32>> = orders.iter().map(|order| (order.id, order.seat)).collect();
+        let mut next = 0;
+
+        let parts: Vec<Part> = match split {
+            Split::Even { ways } => {
+                let ways = *ways as usize;
+                let mut parts: Vec<Part> = (0..ways).map(|_| Part::default()).collect();
+                for (index, line) in bill.lines.iter().enumerate() {
+                    for (part, amount) in parts.iter_mut().zip(billing::split_evenly(line.amount, ways, &mut next)) {
+                        part.lines.push((index, amount));
+                    }
+                }
+                parts
+            }
+            Split::Seat => {
+                let mut seat_list: Vec<i32> = bill.lines.iter().filter_map(|line| seats[&line.order_id]).collect();
+                seat_list.sort_unstable();
+                seat_list.dedup();
+                if seat_list.is_empty() {
+                    return Err(AppError::Conflict(format!(
+                        "Table {} has no items with a seat, split it evenly or by items instead",
+                        table_id,
+                    )));
+                }
+
+                let mut parts: Vec<Part> = seat_list.iter().map(|seat| Part { seat: Some(*seat), lines: Vec::new() }).collect();
+                for (index, line) in bill.lines.iter().enumerate() {
+                    match seats[&line.order_id] {
+                        Some(seat) => {
+                            let part = seat_list.binary_search(&seat).expect("Every seat has a bill");
+                            parts[part].lines.push((index, line.amount));
+                        }
+                        None => {
+                            let shares = billing::split_evenly(line.amount, parts.len(), &mut next);
+                            for (part, amount) in parts.iter_mut().zip(shares) {
+                                part.lines.push((index, amount));
+                            }
+                        }
+                    }
+                }
+                parts
+            }
+            Split::Items { bills } => {
+                let lines: HashMap<i32, usize> = bill.lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, line)| (line.order_id, index))
+                    .collect();
+
+                let listed: Vec<i32> = bills.iter().flatten().copied().collect();
+                let invalid_order_ids: Vec<i32> = listed.iter().copied().filter(|id| !lines.contains_key(id)).collect();
+                let missing_order_ids: Vec<i32> = bill.lines
+                    .iter()
+                    .map(|line| line.order_id)
+                    .filter(|id| !listed.contains(id))
+                    .collect();
+                if !invalid_order_ids.is_empty() || !missing_order_ids.is_empty() {
+                    let mut error = AppError::validation("The split must list every item the table has to pay for once");
+                    if !invalid_order_ids.is_empty() {
+                        error = error.with("invalid_order_ids", invalid_order_ids);
+                    }
+                    if !missing_order_ids.is_empty() {
+                        error = error.with("missing_order_ids", missing_order_ids);
+                    }
+                    return Err(error);
+                }
+
+                bills
+                    .iter()
+                    .map(|order_ids| Part {
+                        seat: None,
+                        lines: order_ids.iter().map(|id| (lines[id], bill.lines[lines[id]].amount)).collect(),
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(bill.divide(parts))
+    }
+
+    /// Divides the bill into the parts, the charges in proportion to their subtotals.
+    fn divide(self, parts: Vec<Part>) -> Vec<Bill> {
+        let subtotals: Vec<i64> = parts
+            .iter()
+            .map(|part| part.lines.iter().map(|(_, amount)| amount).sum())
+            .collect();
+        let service_charges = billing::allocate(self.service_charge.amount, &subtotals);
+        let taxes: Vec<Vec<i64>> = self.taxes.iter().map(|tax| billing::allocate(tax.amount, &subtotals)).collect();
+        let tips = billing::allocate(self.tip, &subtotals);
+
+        parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let taxes: Vec<BillTax> = self.taxes
+                    .iter()
+                    .zip(&taxes)
+                    .map(|(tax, amounts)| BillTax { amount: amounts[index], ..tax.clone() })
+                    .collect();
+
+                Bill {
+                    id: None,
+                    table_id: self.table_id,
+                    seat: part.seat,
+                    currency: self.currency.clone(),
+                    lines: part.lines
+                        .into_iter()
+                        .map(|(line, amount)| BillLine { amount, ..self.lines[line].clone() })
+                        .collect(),
+                    subtotal: subtotals[index],
+                    service_charge: ServiceCharge { amount: service_charges[index], ..self.service_charge.clone() },
+                    total: subtotals[index]
+                        + service_charges[index]
+                        + taxes.iter().map(|tax| tax.amount).sum::<i64>()
+                        + tips[index],
+                    taxes,
+                    tip: tips[index],
+                    created_at: self.created_at,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A bill of a split: the seat it's for and the amount it pays for each line of the whole bill, by the line index.
+#[derive(Default)]
+struct Part {
+    seat: Option<i32>,
+    lines: Vec<(usize, i64)>,
+}
+
+/// Stores the bills the `prepare` closure makes of the orders that haven't been billed yet and returns them.
+///
+/// The orders are locked till the bills are stored, so they can't change or be billed twice,
+/// and then closed with the bills, see `closing_bills`.
+fn check_out(
+    table_id: i32,
+    now: SystemTime,
+    prepare: impl FnOnce(&[OrderOutput]) -> Result<Vec<Bill>, AppError>,
+) -> Result<Vec<BillOutput>, AppError> {
+    database::transaction(|transaction| {
+        transaction.query_opt(
+            &format!("SELECT 1 FROM \"{}\" WHERE table_id = $1 FOR KEY SHARE", Table::TABLE_NAME),
+            &[&table_id],
+        )?.ok_or_else(|| table::not_found(table_id))?;
+
+        let rows = transaction.query(
+            &order::select_query("WHERE o.table_id = $1 AND o.bill_id IS NULL ORDER BY o.order_id FOR UPDATE OF o"),
+            &[&table_id],
+        )?;
+        let orders: Vec<OrderOutput> = rows.iter().map(|row| OrderOutput::from_row(row, now)).collect();
+
+        let mut bills = Vec::new();
+        for mut bill in prepare(&orders)? {
+            let bill_id = insert(transaction, &mut bill)?;
+            bills.push(BillOutput::new(bill, bill_id));
+        }
+
+        let (order_ids, bill_ids): (Vec<i32>, Vec<i32>) = closing_bills(&orders, &bills).into_iter().unzip();
+        transaction.execute(
+            &format!("
+                UPDATE \"{}\" AS o SET bill_id = closed.bill_id, updated_at = $3
+                FROM UNNEST($1::INTEGER[], $2::INTEGER[]) AS closed(order_id, bill_id)
+                WHERE o.order_id = closed.order_id", Order::TABLE_NAME),
+            &[&order_ids, &bill_ids, &now],
+        )?;
+
+        Ok(bills)
+    })
+}
+
+/// Returns the bill every order of a table session is closed with, by the order ID:
+/// the first bill that pays for it, or the first bill of all if it isn't paid for, e.g. cancelled.
+///
+/// # Arguments
+///
+/// * `orders` - the orders of the session;
+/// * `bills` - the stored bills of the session, at least one.
+pub fn closing_bills(orders: &[OrderOutput], bills: &[BillOutput]) -> Vec<(i32, i32)> {
+    orders
+        .iter()
+        .map(|order| {
+            let bill = bills
+                .iter()
+                .find(|bill| bill.lines.iter().any(|line| line.order_id == order.id))
+                .unwrap_or(&bills[0]);
+            (order.id, bill.id)
+        })
+        .collect()
+}
+
+/// Stores the bill with its lines and taxes and returns its ID.
+///
+/// The creation time is updated to the stored one, which has a lower precision.
+fn insert(transaction: &mut Transaction, bill: &mut Bill) -> Result<i32, AppError> {
+    let row = transaction.query_one(
+        &format!("
+            INSERT INTO \"{}\" (table_id, seat, currency, subtotal, service_charge_rate, service_charge, tip, total, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING bill_id, created_at", Bill::TABLE_NAME),
+        &[
+            &bill.table_id,
+            &bill.seat,
+            &bill.currency,
+            &bill.subtotal,
+            &bill.service_charge.rate,
+            &bill.service_charge.amount,
+            &bill.tip,
+            &bill.total,
+            &bill.created_at,
+        ],
+    )?;
+    let bill_id: i32 = row.get("bill_id");
+    bill.created_at = row.get("created_at");
+
+    let order_ids: Vec<i32> = bill.lines.iter().map(|line| line.order_id).collect();
+    let menu_ids: Vec<i32> = bill.lines.iter().map(|line| line.menu_id).collect();
+    let menu_names: Vec<&str> = bill.lines.iter().map(|line| line.menu_name.as_str()).collect();
+    let quantities: Vec<i32> = bill.lines.iter().map(|line| line.quantity).collect();
+    let prices: Vec<i64> = bill.lines.iter().map(|line| line.price).collect();
+    let line_amounts: Vec<i64> = bill.lines.iter().map(|line| line.amount).collect();
+    transaction.execute("
+        INSERT INTO bill_line (bill_id, position, order_id, menu_id, menu_name, quantity, price, amount)
+        SELECT $1, line.position, line.order_id, line.menu_id, line.menu_name, line.quantity, line.price, line.amount
+        FROM UNNEST($2::INTEGER[], $3::INTEGER[], $4::TEXT[], $5::INTEGER[], $6::BIGINT[], $7::BIGINT[])
+            WITH ORDINALITY AS line(order_id, menu_id, menu_name, quantity, price, amount, position)",
+        &[&bill_id, &order_ids, &menu_ids, &menu_names, &quantities, &prices, &line_amounts],
+    )?;
+
+    let names: Vec<&str> = bill.taxes.iter().map(|tax| tax.name.as_str()).collect();
+    let rates: Vec<i32> = bill.taxes.iter().map(|tax| tax.rate).collect();
+    let amounts: Vec<i64> = bill.taxes.iter().map(|tax| tax.amount).collect();
+    transaction.execute("
+        INSERT INTO bill_tax (bill_id, position, tax_name, rate, amount)
+        SELECT $1, tax.position, tax.name, tax.rate, tax.amount
+        FROM UNNEST($2::TEXT[], $3::INTEGER[], $4::BIGINT[]) WITH ORDINALITY AS tax(name, rate, amount, position)",
+        &[&bill_id, &names, &rates, &amounts],
+    )?;
+
+    Ok(bill_id)
+}
+
+pub fn not_found(bill_id: i32) -> AppError {
+    AppError::NotFound(format!("Bill {} not found", bill_id))
+}
+
+
+/// The body of a checkout request: the tip, as an amount or as a percentage of the subtotal.
+#[derive(Default, Serialize, Deserialize)]
+pub struct CheckoutParams {
+    /// In minor units.
+    pub tip: Option<i64>,
+    pub tip_percent: Option<f64>,
+}
+
+impl CheckoutParams {
+    /// Returns the tip, no tip if none is specified.
+    ///
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors` if the tip is invalid.
+    pub fn tip(&self) -> Result<Tip, AppError> {
+        let mut errors = Vec::new();
+        let tip = self.check_tip(&mut errors);
+
+        if !errors.is_empty() {
+            return Err(AppError::validation("The checkout data is invalid").with("errors", errors));
+        }
+        Ok(tip)
+    }
+
+    /// Returns the tip, adding the problems with it to `errors`.
+    fn check_tip(&self, errors: &mut Vec<Value>) -> Tip {
+        if self.tip.is_some() && self.tip_percent.is_some() {
+            errors.push(json!({ "field": "tip", "message": "must not be specified together with tip_percent" }));
+        }
+        if self.tip.is_some_and(|tip| tip < 0) {
+            errors.push(json!({ "field": "tip", "message": "must not be negative" }));
+        }
+        let rate = self.tip_percent.map(billing::rate_from_percent);
+        if rate == Some(None) {
+            errors.push(json!({ "field": "tip_percent", "message": "must be between 0 and 100 with at most two decimal places" }));
+        }
+
+        match (self.tip, rate.flatten()) {
+            (_, Some(rate)) => Tip::Rate(rate),
+            (tip, None) => Tip::Amount(tip.unwrap_or_default()),
+        }
+    }
+}
+
+
+/// The body of a split request: how to split and the tip of the whole table, e.g.
+/// `{"mode": "even", "ways": 3, "tip_percent": 15}`.
+#[derive(Serialize, Deserialize)]
+pub struct SplitParams {
+    #[serde(flatten)]
+    pub split: Split,
+    #[serde(flatten)]
+    pub tip: CheckoutParams,
+}
+
+/// How to split a bill, see `Bill::prepare_split`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum Split {
+    /// In the number of equal bills.
+    Even { ways: i32 },
+    /// A bill for every seat.
+    Seat,
+    /// A bill for every list of order IDs.
+    Items { bills: Vec<Vec<i32>> },
+}
+
+impl SplitParams {
+    /// Checks the parameters that don't depend on the stored data and returns the tip.
+    ///
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors`.
+    pub fn validate(&self) -> Result<Tip, AppError> {
+        let mut errors = Vec::new();
+        let bills_message = format!("must be between 2 and {}", MAX_SPLIT_BILLS);
+
+        match &self.split {
+            Split::Even { ways } => {
+                if !(2..=MAX_SPLIT_BILLS as i32).contains(ways) {
+                    errors.push(json!({ "field": "ways", "message": bills_message }));
+                }
+            }
+            Split::Seat => {}
+            Split::Items { bills } => {
+                if !(2..=MAX_SPLIT_BILLS).contains(&bills.len()) {
+                    errors.push(json!({ "field": "bills", "message": format!("{} bills", bills_message) }));
+                }
+                if bills.iter().any(|order_ids| order_ids.is_empty()) {
+                    errors.push(json!({ "field": "bills", "message": "must not be empty" }));
+                }
+                let mut order_ids: Vec<i32> = bills.iter().flatten().copied().collect();
+                order_ids.sort_unstable();
+                if order_ids.windows(2).any(|pair| pair[0] == pair[1]) {
+                    errors.push(json!({ "field": "bills", "message": "must list every item once" }));
+                }
+            }
+        }
+        let tip = self.tip.check_tip(&mut errors);
+
+        if !errors.is_empty() {
+            return Err(AppError::validation("The split data is invalid").with("errors", errors));
+        }
+        Ok(tip)
+    }
+}
+
+
+/// A billed item.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BillLine {
+    pub order_id: i32,
+    pub menu_id: i32,
+    pub menu_name: String,
+    pub quantity: i32,
+    /// The price of one item with the modifiers, in minor units.
+    pub price: i64,
+    /// The part of the line paid with this bill, the price times the quantity unless the line is shared with other bills.
+    pub amount: i64,
+}
+
+impl BillLine {
+    fn from_row(row: &Row) -> Self {
+        BillLine {
+            order_id: row.get("order_id"),
+            menu_id: row.get("menu_id"),
+            menu_name: row.get("menu_name"),
+            quantity: row.get("quantity"),
+            price: row.get("price"),
+            amount: row.get("amount"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceCharge {
+    /// Basis points of the subtotal, a percentage in JSON.
+    #[serde(with = "percent")]
+    pub rate: i32,
+    pub amount: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BillTax {
+    pub name: String,
+    /// Basis points of the taxed amount, a percentage in JSON.
+    #[serde(with = "percent")]
+    pub rate: i32,
+    pub amount: i64,
+}
+
+impl BillTax {
+    fn from_row(row: &Row) -> Self {
+        BillTax {
+            name: row.get("tax_name"),
+            rate: row.get("rate"),
+            amount: row.get("amount"),
+        }
+    }
+}
+
+
+/// A bill as the API returns it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BillOutput {
+    pub id: i32,
+    pub table_id: i32,
+    /// The seat the bill has been split for.
+    pub seat: Option<i32>,
+    pub currency: String,
+    pub lines: Vec<BillLine>,
+    pub subtotal: i64,
+    pub service_charge: ServiceCharge,
+    pub taxes: Vec<BillTax>,
+    pub tip: i64,
+    pub total: i64,
+    /// The approved payments less their refunds, in minor units.
+    pub paid: i64,
+    pub status: BillStatus,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl BillOutput {
+    /// Builds the output of a bill stored with the specified ID.
+    pub fn new(bill: Bill, bill_id: i32) -> Self {
+        BillOutput {
+            id: bill_id,
+            table_id: bill.table_id,
+            seat: bill.seat,
+            currency: bill.currency,
+            lines: bill.lines,
+            subtotal: bill.subtotal,
+            service_charge: bill.service_charge,
+            taxes: bill.taxes,
+            tip: bill.tip,
+            total: bill.total,
+            paid: 0,
+            status: BillStatus::new(bill.total, 0),
+            created_at: OffsetDateTime::from(bill.created_at),
+        }
+    }
+
+    /// Sets the amount paid, and the status with it.
+    pub fn set_paid(&mut self, paid: i64) {
+        self.paid = paid;
+        self.status = BillStatus::new(self.total, paid);
+    }
+
+    /// Returns the amount left to pay.
+    pub fn due(&self) -> i64 {
+        (self.total - self.paid).max(0)
+    }
+
+    /// Builds the output from a row with the `payment::PAID_COLUMN`.
+    fn from_row(row: &Row, lines: Vec<BillLine>, taxes: Vec<BillTax>) -> Self {
+        let mut bill = BillOutput {
+            id: row.get("bill_id"),
+            table_id: row.get("table_id"),
+            seat: row.get("seat"),
+            currency: row.get("currency"),
+            lines,
+            subtotal: row.get("subtotal"),
+            service_charge: ServiceCharge { rate: row.get("service_charge_rate"), amount: row.get("service_charge") },
+            taxes,
+            tip: row.get("tip"),
+            total: row.get("total"),
+            paid: 0,
+            status: BillStatus::Open,
+            created_at: OffsetDateTime::from(row.get::<_, SystemTime>("created_at")),
+        };
+        bill.set_paid(row.get("paid"));
+
+        bill
+    }
+}
+
+/// Whether a bill has been paid, see `BillOutput::paid`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BillStatus {
+    Open,
+    PartiallyPaid,
+    /// Only when the payments cover the total. A refund may open the bill again.
+    Paid,
+}
+
+impl BillStatus {
+    pub fn new(total: i64, paid: i64) -> Self {
+        if paid >= total {
+            BillStatus::Paid
+        } else if paid > 0 {
+            BillStatus::PartiallyPaid
+        } else {
+            BillStatus::Open
+        }
+    }
+}
+
+impl fmt::Debug for BillOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bill {} for table {}: {} items, total {} {}, paid {}",
+            self.id,
+            self.table_id,
+            self.lines.len(),
+            self.total,
+            self.currency,
+            self.paid,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        billing::TaxRule,
+        database::models::{menu::{Modifier, ModifierKind}, order::{OrderStatus, OrderTimestamps}},
+    };
+
+    fn order(id: i32, price: i32, currency: &str, status: OrderStatus) -> OrderOutput {
+        OrderOutput {
+            id,
+            table_id: 1,
+            menu_id: id,
+            menu_name: format!("Dish {}", id),
+            menu_description: String::new(),
+            quantity: 1,
+            notes: None,
+            modifiers: Vec::new(),
+            station_id: None,
+            price,
+            amount: i64::from(price),
+            currency: currency.to_string(),
+            seat: None,
+            bill_id: None,
+            status,
+            priority: 0,
+            seconds_left: 0,
+            is_prepared: status.is_prepared(),
+            is_deleted: status.is_deleted(),
+            timestamps: OrderTimestamps::default(),
+            allergen_warning: None,
+        }
+    }
+
+    fn conflict(result: Result<Bill, AppError>) -> String {
+        match result {
+            Err(AppError::Conflict(detail)) => detail,
+            Err(error) => panic!("unexpected error {:?}", error),
+            Ok(_) => panic!("unexpected bill"),
+        }
+    }
+
+    #[test]
+    fn test_prepare() {
+        let orders = [
+            order(1, 1200, "USD", OrderStatus::Served),
+            order(2, 850, "USD", OrderStatus::Cancelled),
+            order(3, 1800, "USD", OrderStatus::Served),
+        ];
+
+        let bill = Bill::prepare(1, &orders, Tip::Amount(300), &rules(), SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(bill.lines.iter().map(|line| line.order_id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!((bill.subtotal, bill.service_charge.amount, bill.taxes[0].amount), (3000, 300, 660));
+        assert_eq!((bill.tip, bill.total), (300, 4260));
+
+        let json = serde_json::to_value(BillOutput::new(bill, 5)).unwrap();
+        assert_eq!(json["service_charge"], json!({ "rate": 10.0, "amount": 300 }));
+        assert_eq!(json["created_at"], "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_prepare_lines() {
+        // Two carbonaras with extra parmesan
+        let parmesan = Modifier { id: 4, name: "Extra parmesan".to_string(), kind: ModifierKind::Add, price_delta: 150, cook_time_delta: 0 };
+        let orders = [
+            OrderOutput { quantity: 2, modifiers: vec![parmesan], amount: 3200, ..order(1, 1450, "USD", OrderStatus::Served) },
+            order(2, 850, "USD", OrderStatus::Served),
+            // Still cooking
+            order(3, 700, "USD", OrderStatus::Cooking),
+            order(4, 900, "USD", OrderStatus::Cancelled),
+        ];
+
+        let bill = Bill::prepare(1, &orders, Tip::Amount(0), &BillingRules::default(), SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!((bill.lines[0].quantity, bill.lines[0].price, bill.lines[0].amount), (2, 1600, 3200));
+        assert_eq!(bill.lines.iter().map(|line| line.order_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(bill.subtotal, 4750);
+    }
+
+    #[test]
+    fn test_prepare_conflicts() {
+        let rules = BillingRules::default();
+        let prepare = |orders: &[OrderOutput]| Bill::prepare(7, orders, Tip::Amount(0), &rules, SystemTime::UNIX_EPOCH);
+
+        assert_eq!(conflict(prepare(&[order(1, 100, "USD", OrderStatus::Voided)])), "Table 7 has nothing to pay for");
+        assert_eq!(
+            conflict(prepare(&[order(1, 100, "USD", OrderStatus::Served), order(2, 100, "EUR", OrderStatus::Served)])),
+            "Table 7 has items in several currencies and can't be billed at once: EUR, USD",
+        );
+    }
+
+    fn rules() -> BillingRules {
+        BillingRules {
+            service_charge_rate: 1000,
+            taxes: vec![TaxRule { name: "VAT".to_string(), rate: 2000, on_service_charge: true }],
+        }
+    }
+
+    fn split(orders: &[OrderOutput], split: Split) -> Result<Vec<Bill>, AppError> {
+        Bill::prepare_split(1, orders, &split, Tip::Amount(100), &rules(), SystemTime::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_split_even() {
+        let orders = [order(1, 1200, "USD", OrderStatus::Served), order(2, 850, "USD", OrderStatus::Served)];
+        let whole = Bill::prepare(1, &orders, Tip::Amount(100), &rules(), SystemTime::UNIX_EPOCH).unwrap();
+
+        let bills = split(&orders, Split::Even { ways: 3 }).unwrap();
+        // 8.50 is shared as 2.84, 2.83 and 2.83, the charges in proportion to the subtotals
+        assert_eq!(bills.iter().map(|bill| bill.subtotal).collect::<Vec<_>>(), vec![684, 683, 683]);
+        assert_eq!(bills.iter().map(|bill| bill.service_charge.amount).collect::<Vec<_>>(), vec![69, 68, 68]);
+        assert_eq!(bills.iter().map(|bill| bill.taxes[0].amount).collect::<Vec<_>>(), vec![151, 150, 150]);
+        assert_eq!(bills.iter().map(|bill| bill.tip).collect::<Vec<_>>(), vec![34, 33, 33]);
+        assert_eq!(bills.iter().map(|bill| bill.total).sum::<i64>(), whole.total);
+        assert!(bills.iter().all(|bill| bill.lines.len() == 2 && bill.lines[1].price == 850));
+    }
+
+    #[test]
+    fn test_split_by_seat() {
+        let mut orders = [
+            order(1, 1200, "USD", OrderStatus::Served),
+            order(2, 850, "USD", OrderStatus::Served),
+            order(3, 1350, "USD", OrderStatus::Served),
+        ];
+        assert_eq!(
+            conflict(split(&orders, Split::Seat).map(|mut bills| bills.remove(0))),
+            "Table 1 has no items with a seat, split it evenly or by items instead",
+        );
+
+        orders[0].seat = Some(2);
+        orders[1].seat = Some(1);
+        let bills = split(&orders, Split::Seat).unwrap();
+        assert_eq!(bills.iter().map(|bill| bill.seat).collect::<Vec<_>>(), vec![Some(1), Some(2)]);
+        // The third item has no seat, so it's shared
+        let lines = |bill: &Bill| bill.lines.iter().map(|line| (line.order_id, line.amount)).collect::<Vec<_>>();
+        assert_eq!(lines(&bills[0]), vec![(2, 850), (3, 675)]);
+        assert_eq!(lines(&bills[1]), vec![(1, 1200), (3, 675)]);
+        assert_eq!(bills.iter().map(|bill| bill.total).sum::<i64>(), 3400 + 340 + 748 + 100);
+    }
+
+    #[test]
+    fn test_split_by_items() {
+        let orders = [
+            order(1, 1200, "USD", OrderStatus::Served),
+            order(2, 850, "USD", OrderStatus::Served),
+            order(3, 1350, "USD", OrderStatus::Cancelled),
+        ];
+
+        let bills = split(&orders, Split::Items { bills: vec![vec![2], vec![1]] }).unwrap();
+        assert_eq!((bills[0].subtotal, bills[1].subtotal), (850, 1200));
+        assert_eq!(bills.iter().map(|bill| bill.total).sum::<i64>(), 2050 + 205 + 451 + 100);
+
+        match split(&orders, Split::Items { bills: vec![vec![1], vec![3]] }) {
+            Err(AppError::Validation { extensions, .. }) => {
+                assert_eq!(extensions["invalid_order_ids"], json!([3]));
+                assert_eq!(extensions["missing_order_ids"], json!([2]));
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_split_params() {
+        let params: SplitParams = serde_json::from_str(r#"{"mode": "even", "ways": 2, "tip_percent": 10}"#).unwrap();
+        assert_eq!(params.split, Split::Even { ways: 2 });
+        assert_eq!(params.validate().unwrap(), Tip::Rate(1000));
+
+        let params: SplitParams = serde_json::from_str(r#"{"mode": "items", "bills": [[1, 2], [2], []]}"#).unwrap();
+        match params.validate() {
+            Err(AppError::Validation { extensions, .. }) => assert_eq!(extensions["errors"].as_array().unwrap().len(), 2),
+            _ => panic!("unexpected result"),
+        }
+
+        let invalid = |json| serde_json::from_str::<SplitParams>(json).unwrap().validate().is_err();
+        assert!(invalid(r#"{"mode": "even", "ways": 1}"#));
+        assert!(invalid(r#"{"mode": "seat", "tip": -5}"#));
+        assert!(!invalid(r#"{"mode": "seat"}"#));
+        assert!(serde_json::from_str::<SplitParams>(r#"{"mode": "even"}"#).is_err());
+        assert!(serde_json::from_str::<SplitParams>(r#"{"mode": "random"}"#).is_err());
+    }
+
+    #[test]
+    fn test_tip() {
+        let params = |tip, tip_percent| CheckoutParams { tip, tip_percent };
+
+        assert_eq!(params(None, None).tip().unwrap(), Tip::Amount(0));
+        assert_eq!(params(Some(500), None).tip().unwrap(), Tip::Amount(500));
+        assert_eq!(params(None, Some(12.5)).tip().unwrap(), Tip::Rate(1250));
+
+        assert!(params(Some(-1), None).tip().is_err());
+        assert!(params(None, Some(150.0)).tip().is_err());
+        assert!(params(Some(500), Some(10.0)).tip().is_err());
+    }
+}
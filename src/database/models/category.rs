@@ -0,0 +1,170 @@
+use postgres::Row;
+
+use crate::{database::model::Model, error::AppError};
+use std::fmt;
+use super::menu::MenuOutput;
+
+/// A model for the `category` DB table.
+///
+/// DB table: `category`
+///
+/// ## Fields:
+///
+/// - `id` - i32 - category id
+/// - `name` - String - name of the category, e.g. starters, mains, desserts or drinks
+/// - `display_order` - i32 - position of the category in the menu, the lower ones are shown first.
+#[derive(Serialize, Deserialize)]
+pub struct Category {
+    pub id: Option<i32>,
+    pub name: String,
+    pub display_order: i32,
+}
+
+impl Model for Category {
+    const TABLE_NAME: &'static str = "category";
+}
+
+impl Category {
+    /// Returns all categories in the order they are shown.
+    pub fn get_all() -> Result<Vec<CategoryOutput>, AppError> {
+        let rows = Self::query(
+            &format!("SELECT * FROM \"{}\" ORDER BY display_order, category_id", Self::TABLE_NAME),
+            &[],
+        )?;
+        Ok(rows.iter().map(CategoryOutput::from_row).collect())
+    }
+
+    /// Returns a category with specified ID.
+    ///
+    /// If there is no such category, `AppError::NotFound` is returned.
+    pub fn get_one(category_id: i32) -> Result<CategoryOutput, AppError> {
+        Self::query_opt(&format!("SELECT * FROM \"{}\" WHERE category_id = $1", Self::TABLE_NAME), &[&category_id])?
+            .map(|row| CategoryOutput::from_row(&row))
+            .ok_or_else(|| not_found(category_id))
+    }
+}
+
+pub fn not_found(category_id: i32) -> AppError {
+    AppError::NotFound(format!("Category {} not found", category_id))
+}
+
+/// Returns the error for a menu item that refers to an unknown category.
+pub fn invalid_category(category_id: i32) -> AppError {
+    AppError::validation("The category doesn't exist").with("invalid_category_id", category_id)
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CategoryOutput {
+    pub id: i32,
+    pub name: String,
+    pub display_order: i32,
+}
+
+impl CategoryOutput {
+    fn from_row(row: &Row) -> Self {
+        CategoryOutput {
+            id: row.get("category_id"),
+            name: row.get("category_name"),
+            display_order: row.get("display_order"),
+        }
+    }
+}
+
+impl fmt::Debug for CategoryOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}. {} (#{})", self.id, self.name, self.display_order)
+    }
+}
+
+
+/// A category with its menu items, in the order they are shown.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CategoryMenu {
+    #[serde(flatten)]
+    pub category: CategoryOutput,
+    pub items: Vec<MenuOutput>,
+}
+
+/// The menu grouped by categories for the `GET /menu?grouped=true` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupedMenu {
+    /// All categories, the empty ones too, in the order they are shown.
+    pub categories: Vec<CategoryMenu>,
+    /// The items without a category, shown after the categories.
+    pub uncategorized: Vec<MenuOutput>,
+}
+
+impl GroupedMenu {
+    /// Puts the menu items into their categories.
+    ///
+    /// # Arguments
+    ///
+    /// * `categories` - all categories, in the order they are shown;
+    /// * `items` - the menu items, each category keeps them in the given order.
+    pub fn new(categories: Vec<CategoryOutput>, items: Vec<MenuOutput>) -> Self {
+        let mut categories: Vec<CategoryMenu> = categories
+            .into_iter()
+            .map(|category| CategoryMenu { category, items: Vec::new() })
+            .collect();
+        let mut uncategorized = Vec::new();
+
+        for item in items {
+            let category = item.category_id.and_then(|category_id| {
+                categories.iter_mut().find(|menu| menu.category.id == category_id)
+            });
+            match category {
+                Some(category) => category.items.push(item),
+                None => uncategorized.push(item),
+            }
+        }
+
+        GroupedMenu { categories, uncategorized }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: i32, name: &str, display_order: i32) -> CategoryOutput {
+        CategoryOutput { id, name: name.to_string(), display_order }
+    }
+
+    fn item(id: i32, category_id: Option<i32>) -> MenuOutput {
+        MenuOutput {
+            id,
+            name: format!("Item {}", id),
+            description: String::new(),
+            time_to_cook_in_minutes: 5,
+            station_id: None,
+            category_id,
+            display_order: 0,
+            allergens: Vec::new(),
+            diets: Vec::new(),
+            price: 990,
+            currency: "USD".to_string(),
+            modifiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_grouped_menu() {
+        let categories = vec![category(2, "Starters", 1), category(1, "Mains", 2), category(3, "Desserts", 3)];
+        let items = vec![item(5, Some(2)), item(3, Some(1)), item(1, Some(2)), item(4, None), item(2, Some(9))];
+
+        let menu = GroupedMenu::new(categories, items);
+        let ids = |items: &[MenuOutput]| items.iter().map(|item| item.id).collect::<Vec<_>>();
+        assert_eq!(menu.categories.iter().map(|menu| menu.category.id).collect::<Vec<_>>(), vec![2, 1, 3]);
+        assert_eq!(ids(&menu.categories[0].items), vec![5, 1]);
+        assert_eq!(ids(&menu.categories[1].items), vec![3]);
+        assert!(menu.categories[2].items.is_empty());
+        assert_eq!(ids(&menu.uncategorized), vec![4, 2]);
+
+        let json = serde_json::to_value(&menu).unwrap();
+        assert_eq!(json["categories"][0]["name"], "Starters");
+        assert_eq!(json["categories"][0]["display_order"], 1);
+        assert_eq!(json["categories"][0]["items"][0]["id"], 5);
+    }
+}
@@ -1,5 +1,28 @@
-use crate::database::model::Model;
+use std::{collections::HashMap, str::FromStr};
+
+use postgres::{Row, Transaction};
+use serde_json::json;
+
+use crate::{database::{self, model::Model}, error::AppError};
 use std::fmt;
+use super::{allergen::{self, Allergen, Diet}, category::{self, Category, CategoryOutput}, order::{Order, OrderStatus}, station::{self, Station}};
+
+/// The longest allowed name of a menu item, the size of the `menu_name` column.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// The most modifiers a menu item can have.
+pub const MAX_MODIFIERS: usize = 20;
+
+/// The highest price of a menu item and the highest price delta of a modifier, in minor units.
+///
+/// With `MAX_MODIFIERS` of them the price of an item still fits the `INTEGER` columns.
+pub const MAX_PRICE: i32 = 100_000_000;
+
+/// The longest cooking time of a menu item and the biggest cooking time delta of a modifier, in minutes.
+pub const MAX_TIME_TO_COOK: i32 = 24 * 60;
+
+/// The currency of the items that don't specify one, the default of the `currency` column.
+pub const DEFAULT_CURRENCY: &str = "USD";
 
 /// A model for the `menu` DB table.
 /// 
@@ -10,13 +33,38 @@ use std::fmt;
 /// - `id` - i32 - menu item id
 /// - `name` - String - name of the dish
 /// - `description` - String - description
-/// - `time_to_cook_in_minutes` - i32 - time to prepare this item in minutes.
+/// - `time_to_cook_in_minutes` - i32 - time to prepare this item in minutes
+/// - `station_id` - i32 - the station that cooks this item, `None` for the general kitchen
+/// - `category_id` - i32 - the category the item is shown in, `None` if it isn't in any
+/// - `display_order` - i32 - position of the item in its category, the lower ones are shown first
+/// - `allergens` - Vec<Allergen> - the allergens the item contains
+/// - `diets` - Vec<Diet> - the diets the item is suitable for, e.g. vegan
+/// - `price` - i32 - price in the minor units of the currency, e.g. cents
+/// - `currency` - String - ISO 4217 currency code, `DEFAULT_CURRENCY` if it's not specified
+/// - `modifiers` - Vec<ModifierParams> - the options the item can be ordered with, stored in the `menu_modifier` table;
+///   `None` keeps the current ones, the stored modifiers get new IDs every time they are replaced.
+/// 
+/// Deleted items are kept with `is_deleted` set, so the orders that refer to them can still be shown.
 #[derive(Serialize, Deserialize)]
 pub struct Menu {
     pub id: Option<i32>,
     pub name: String,
     pub description: Option<String>,
     pub time_to_cook_in_minutes: i32,
+    #[serde(default)]
+    pub station_id: Option<i32>,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub display_order: i32,
+    #[serde(default)]
+    pub allergens: Vec<Allergen>,
+    #[serde(default)]
+    pub diets: Vec<Diet>,
+    pub price: i32,
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub modifiers: Option<Vec<ModifierParams>>,
 }
 
 impl Model for Menu {
@@ -24,40 +72,815 @@ impl Model for Menu {
 }
 
 impl Menu {
-    /// Returns the entire menu.
-    pub fn get_all_items() -> Result<Vec<MenuOutput>, String> {
-        let rows = Self::query_all_rows()?;
-        let mut menu_items = Vec::new();
-        for row in rows {
-            menu_items.push(MenuOutput {
-                id: row.get("menu_id"),
-                name: row.get("menu_name"),
-                description: row.get("menu_description"),
-                time_to_cook_in_minutes: row.get("time_to_cook_in_minutes"),
-            });
+    /// Returns the entire menu, without the deleted items, in the order it is shown, see `sort_items`.
+    pub fn get_all_items() -> Result<Vec<MenuOutput>, AppError> {
+        database::transaction(|transaction| {
+            let rows = transaction.query(
+                &format!("
+                    SELECT m.*
+                    FROM \"{}\" AS m
+                    LEFT JOIN \"{}\" AS c ON c.category_id = m.category_id
+                    WHERE m.is_deleted = FALSE
+                    ORDER BY c.display_order NULLS LAST, c.category_id, m.display_order, m.menu_id",
+                    Self::TABLE_NAME,
+                    Category::TABLE_NAME,
+                ),
+                &[],
+            )?;
+            let menu_ids: Vec<i32> = rows.iter().map(|row| row.get("menu_id")).collect();
+            let mut modifiers = select_modifiers(transaction, &menu_ids)?;
+
+            Ok(rows
+                .iter()
+                .map(|row| {
+                    let modifiers = modifiers.remove(&row.get::<_, i32>("menu_id")).unwrap_or_default();
+                    MenuOutput::from_row(row, modifiers)
+                })
+                .collect())
+        })
+    }
+
+    /// Returns a menu item with specified ID.
+    /// 
+    /// If there is no such item or it has been deleted, `AppError::NotFound` is returned.
+    pub fn get_one(menu_id: i32) -> Result<MenuOutput, AppError> {
+        database::transaction(|transaction| {
+            let row = transaction.query_opt(
+                &format!("SELECT * FROM \"{}\" WHERE menu_id = $1 AND is_deleted = FALSE", Self::TABLE_NAME),
+                &[&menu_id],
+            )?.ok_or_else(|| not_found(menu_id))?;
+
+            output(transaction, &row)
+        })
+    }
+
+    /// Adds a menu item and returns it.
+    /// 
+    /// The item is validated first, `AppError::Validation` is returned if it's invalid or its station or category doesn't exist.
+    pub fn create(item: Menu) -> Result<MenuOutput, AppError> {
+        item.validate()?;
+        item.check_station()?;
+        item.check_category()?;
+
+        database::transaction(|transaction| {
+            let row = transaction.query_one(
+                &format!("
+                    INSERT INTO \"{}\" (
+                        menu_name, menu_description, time_to_cook_in_minutes, station_id, category_id, display_order, price, currency,
+                        allergens, diets
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                    RETURNING *", Self::TABLE_NAME),
+                &[
+                    &item.name.trim(),
+                    &item.description(),
+                    &item.time_to_cook_in_minutes,
+                    &item.station_id,
+                    &item.category_id,
+                    &item.display_order,
+                    &item.price,
+                    &item.currency(),
+                    &item.allergen_names(),
+                    &item.diet_names(),
+                ],
+            )?;
+            if let Some(modifiers) = &item.modifiers {
+                replace_modifiers(transaction, row.get("menu_id"), modifiers)?;
+            }
+
+            output(transaction, &row)
+        })
+    }
+
+    /// Replaces a menu item with specified ID and returns the updated item.
+    /// 
+    /// The orders that have already been placed keep their cooking time, station, price and modifiers.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `menu_id` - the item to update, `AppError::NotFound` is returned if there is no such item or it has been deleted;
+    /// * `item` - the new data, the `id` field is ignored.
+    pub fn update(menu_id: i32, item: Menu) -> Result<MenuOutput, AppError> {
+        item.validate()?;
+        item.check_station()?;
+        item.check_category()?;
+
+        database::transaction(|transaction| {
+            let row = transaction.query_opt(
+                &format!("
+                    UPDATE \"{}\"
+                    SET
+                        menu_name = $2,
+                        menu_description = $3,
+                        time_to_cook_in_minutes = $4,
+                        station_id = $5,
+                        category_id = $6,
+                        display_order = $7,
+                        price = $8,
+                        currency = $9,
+                        allergens = $10,
+                        diets = $11
+                    WHERE menu_id = $1
+                    AND is_deleted = FALSE
+                    RETURNING *", Self::TABLE_NAME),
+                &[
+                    &menu_id,
+                    &item.name.trim(),
+                    &item.description(),
+                    &item.time_to_cook_in_minutes,
+                    &item.station_id,
+                    &item.category_id,
+                    &item.display_order,
+                    &item.price,
+                    &item.currency(),
+                    &item.allergen_names(),
+                    &item.diet_names(),
+                ],
+            )?.ok_or_else(|| not_found(menu_id))?;
+            if let Some(modifiers) = &item.modifiers {
+                replace_modifiers(transaction, menu_id, modifiers)?;
+            }
+
+            output(transaction, &row)
+        })
+    }
+
+    /// Delete a menu item by its ID.
+    /// 
+    /// A record in a table will not be deleted but marked as deleted,
+    /// so the orders that refer to it keep their menu name and description.
+    /// 
+    /// ##The item can not be deleted in the cases:
+    /// 
+    /// - if there is no item with the specified ID or it is already deleted (`AppError::NotFound`)
+    /// - if there are open orders for this item, see `OrderStatus::is_open` (`AppError::Conflict`).
+    pub fn delete(menu_id: i32) -> Result<(), AppError> {
+        database::transaction(|transaction| {
+            // The row is locked, so no order can be created for this item until it is deleted
+            transaction.query_opt(
+                &format!("SELECT 1 FROM \"{}\" WHERE menu_id = $1 AND is_deleted = FALSE FOR UPDATE", Self::TABLE_NAME),
+                &[&menu_id],
+            )?.ok_or_else(|| not_found(menu_id))?;
+
+            let open_statuses: Vec<&str> = OrderStatus::ALL
+                .iter()
+                .filter(|status| status.is_open())
+                .map(OrderStatus::as_str)
+                .collect();
+            let open_orders: i64 = transaction.query_one(
+                &format!("SELECT COUNT(*) FROM \"{}\" WHERE menu_id = $1 AND status = ANY($2)", Order::TABLE_NAME),
+                &[&menu_id, &open_statuses],
+            )?.get(0);
+            if open_orders > 0 {
+                return Err(AppError::Conflict(format!(
+                    "Menu item {} is referenced by {} open orders",
+                    menu_id,
+                    open_orders,
+                )));
+            }
+
+            transaction.execute(
+                &format!("UPDATE \"{}\" SET is_deleted = TRUE WHERE menu_id = $1", Self::TABLE_NAME),
+                &[&menu_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Checks the item before it is stored.
+    /// 
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors` otherwise.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        let name = self.name.trim();
+        if name.is_empty() {
+            errors.push(json!({ "field": "name", "message": "must not be empty" }));
+        } else if name.chars().count() > MAX_NAME_LENGTH {
+            errors.push(json!({ "field": "name", "message": format!("must be at most {} characters long", MAX_NAME_LENGTH) }));
+        }
+        if self.time_to_cook_in_minutes <= 0 {
+            errors.push(json!({ "field": "time_to_cook_in_minutes", "message": "must be positive" }));
+        } else if self.time_to_cook_in_minutes > MAX_TIME_TO_COOK {
+            errors.push(json!({ "field": "time_to_cook_in_minutes", "message": format!("must be at most {}", MAX_TIME_TO_COOK) }));
+        }
+        if self.price < 0 {
+            errors.push(json!({ "field": "price", "message": "must not be negative" }));
+        } else if self.price > MAX_PRICE {
+            errors.push(json!({ "field": "price", "message": format!("must be at most {}", MAX_PRICE) }));
+        }
+        if !is_currency_code(self.currency()) {
+            errors.push(json!({ "field": "currency", "message": "must be a three-letter ISO 4217 code, e.g. USD" }));
+        }
+        for diet in &self.diets {
+            let animal_allergens: Vec<&str> = self.allergens
+                .iter()
+                .filter(|allergen| diet.excluded_allergens().contains(allergen))
+                .map(Allergen::as_str)
+                .collect();
+            if !animal_allergens.is_empty() {
+                errors.push(json!({
+                    "field": "diets",
+                    "message": format!("a {} item can't contain {}", diet.as_str(), animal_allergens.join(", ")),
+                }));
+            }
+        }
+        if let Some(modifiers) = &self.modifiers {
+            check_modifiers(modifiers, &mut errors);
         }
-        Ok(menu_items)
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::validation("The menu item is invalid").with("errors", errors))
+    }
+
+    /// Checks that the station of the item exists, returns `AppError::Validation` otherwise.
+    fn check_station(&self) -> Result<(), AppError> {
+        let Some(station_id) = self.station_id else {
+            return Ok(());
+        };
+
+        Station::query_opt(&format!("SELECT 1 FROM \"{}\" WHERE station_id = $1", Station::TABLE_NAME), &[&station_id])?
+            .map(|_| ())
+            .ok_or_else(|| station::invalid_station(station_id))
+    }
+
+    /// Checks that the category of the item exists, returns `AppError::Validation` otherwise.
+    fn check_category(&self) -> Result<(), AppError> {
+        let Some(category_id) = self.category_id else {
+            return Ok(());
+        };
+
+        Category::query_opt(&format!("SELECT 1 FROM \"{}\" WHERE category_id = $1", Category::TABLE_NAME), &[&category_id])?
+            .map(|_| ())
+            .ok_or_else(|| category::invalid_category(category_id))
+    }
+
+    /// Returns the description, an empty one if it's not specified.
+    pub fn description(&self) -> &str {
+        self.description.as_deref().unwrap_or_default()
     }
+
+    /// Returns the currency, `DEFAULT_CURRENCY` if it's not specified.
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or(DEFAULT_CURRENCY)
+    }
+
+    /// Returns the allergens as they are stored, sorted and without repeats.
+    fn allergen_names(&self) -> Vec<&'static str> {
+        allergen::normalize(&self.allergens).iter().map(Allergen::as_str).collect()
+    }
+
+    /// Returns the diets as they are stored, sorted and without repeats.
+    fn diet_names(&self) -> Vec<&'static str> {
+        allergen::normalize(&self.diets).iter().map(Diet::as_str).collect()
+    }
+}
+
+/// Adds the problems with the modifiers of an item to `errors`.
+fn check_modifiers(modifiers: &[ModifierParams], errors: &mut Vec<serde_json::Value>) {
+    if modifiers.len() > MAX_MODIFIERS {
+        errors.push(json!({ "field": "modifiers", "message": format!("must be at most {}", MAX_MODIFIERS) }));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for (index, modifier) in modifiers.iter().enumerate() {
+        let name = modifier.name.trim();
+        if name.is_empty() {
+            errors.push(json!({ "field": format!("modifiers[{}].name", index), "message": "must not be empty" }));
+        } else if name.chars().count() > MAX_NAME_LENGTH {
+            errors.push(json!({
+                "field": format!("modifiers[{}].name", index),
+                "message": format!("must be at most {} characters long", MAX_NAME_LENGTH),
+            }));
+        } else if names.contains(&name.to_lowercase()) {
+            errors.push(json!({ "field": format!("modifiers[{}].name", index), "message": "must differ from the other modifiers" }));
+        }
+        names.push(name.to_lowercase());
+
+        if modifier.price_delta < 0 {
+            errors.push(json!({ "field": format!("modifiers[{}].price_delta", index), "message": "must not be negative" }));
+        } else if modifier.price_delta > MAX_PRICE {
+            errors.push(json!({
+                "field": format!("modifiers[{}].price_delta", index),
+                "message": format!("must be at most {}", MAX_PRICE),
+            }));
+        }
+        if !(-MAX_TIME_TO_COOK..=MAX_TIME_TO_COOK).contains(&modifier.cook_time_delta) {
+            errors.push(json!({
+                "field": format!("modifiers[{}].cook_time_delta", index),
+                "message": format!("must be between -{} and {}", MAX_TIME_TO_COOK, MAX_TIME_TO_COOK),
+            }));
+        }
+    }
+}
+
+/// Builds the output of a menu row with its modifiers.
+fn output(transaction: &mut Transaction, row: &Row) -> Result<MenuOutput, AppError> {
+    let menu_id: i32 = row.get("menu_id");
+    let modifiers = select_modifiers(transaction, &[menu_id])?.remove(&menu_id).unwrap_or_default();
+
+    Ok(MenuOutput::from_row(row, modifiers))
+}
+
+/// Returns the modifiers of the menu items by the item ID, in the order they were given.
+fn select_modifiers(transaction: &mut Transaction, menu_ids: &[i32]) -> Result<HashMap<i32, Vec<Modifier>>, AppError> {
+    let rows = transaction.query(
+        "SELECT * FROM menu_modifier WHERE menu_id = ANY($1) ORDER BY menu_id, position",
+        &[&menu_ids],
+    )?;
+
+    let mut modifiers: HashMap<i32, Vec<Modifier>> = HashMap::new();
+    for row in &rows {
+        modifiers.entry(row.get("menu_id")).or_default().push(Modifier::from_row(row));
+    }
+    Ok(modifiers)
+}
+
+/// Replaces the modifiers of a menu item.
+/// 
+/// The orders keep their own copies of the modifiers, so the old ones can be deleted.
+fn replace_modifiers(transaction: &mut Transaction, menu_id: i32, modifiers: &[ModifierParams]) -> Result<(), AppError> {
+    transaction.execute("DELETE FROM menu_modifier WHERE menu_id = $1", &[&menu_id])?;
+
+    let names: Vec<&str> = modifiers.iter().map(|modifier| modifier.name.trim()).collect();
+    let kinds: Vec<&str> = modifiers.iter().map(|modifier| modifier.kind.as_str()).collect();
+    let price_deltas: Vec<i32> = modifiers.iter().map(|modifier| modifier.price_delta).collect();
+    let cook_time_deltas: Vec<i32> = modifiers.iter().map(|modifier| modifier.cook_time_delta).collect();
+    transaction.execute("
+        INSERT INTO menu_modifier (menu_id, position, modifier_name, kind, price_delta, cook_time_delta)
+        SELECT $1, modifier.position, modifier.name, modifier.kind, modifier.price_delta, modifier.cook_time_delta
+        FROM UNNEST($2::TEXT[], $3::TEXT[], $4::INTEGER[], $5::INTEGER[])
+            WITH ORDINALITY AS modifier(name, kind, price_delta, cook_time_delta, position)
+        ORDER BY modifier.position",
+        &[&menu_id, &names, &kinds, &price_deltas, &cook_time_deltas],
+    )?;
+
+    Ok(())
+}
+
+/// Sorts the menu items in the order they are shown: by the display order of their categories,
+/// the items without a category last, then by their own display order and ID.
+///
+/// # Arguments
+///
+/// * `items` - the items to sort;
+/// * `categories` - all categories, the items of an unknown one are shown with those without a category.
+pub fn sort_items(items: &mut [MenuOutput], categories: &[CategoryOutput]) {
+    items.sort_by_key(|item| {
+        let category = item.category_id.and_then(|category_id| categories.iter().find(|category| category.id == category_id));
+        (category.is_none(), category.map(|category| (category.display_order, category.id)), item.display_order, item.id)
+    });
+}
+
+/// Checks if the value looks like an ISO 4217 currency code: three uppercase Latin letters.
+fn is_currency_code(value: &str) -> bool {
+    value.len() == 3 && value.bytes().all(|byte| byte.is_ascii_uppercase())
+}
+
+pub fn not_found(menu_id: i32) -> AppError {
+    AppError::NotFound(format!("Menu item {} not found", menu_id))
 }
 
 
+/// A partial update of a menu item: the fields that are not specified keep their values.
+/// 
+/// An item can't be moved back to the general kitchen or out of its category with a patch, replace it with `PUT` instead.
 #[derive(Serialize, Deserialize)]
+pub struct MenuPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub time_to_cook_in_minutes: Option<i32>,
+    pub station_id: Option<i32>,
+    pub category_id: Option<i32>,
+    pub display_order: Option<i32>,
+    pub allergens: Option<Vec<Allergen>>,
+    pub diets: Option<Vec<Diet>>,
+    pub price: Option<i32>,
+    pub currency: Option<String>,
+    pub modifiers: Option<Vec<ModifierParams>>,
+}
+
+impl MenuPatch {
+    /// Applies the changes to the current item and returns the updated one.
+    /// 
+    /// Returns `AppError::Validation` if there is nothing to change.
+    pub fn apply(self, item: MenuOutput) -> Result<Menu, AppError> {
+        if self.name.is_none()
+            && self.description.is_none()
+            && self.time_to_cook_in_minutes.is_none()
+            && self.station_id.is_none()
+            && self.category_id.is_none()
+            && self.display_order.is_none()
+            && self.allergens.is_none()
+            && self.diets.is_none()
+            && self.price.is_none()
+            && self.currency.is_none()
+            && self.modifiers.is_none()
+        {
+            return Err(AppError::validation("At least one field must be specified"));
+        }
+
+        Ok(Menu {
+            id: Some(item.id),
+            name: self.name.unwrap_or(item.name),
+            description: Some(self.description.unwrap_or(item.description)),
+            time_to_cook_in_minutes: self.time_to_cook_in_minutes.unwrap_or(item.time_to_cook_in_minutes),
+            station_id: self.station_id.or(item.station_id),
+            category_id: self.category_id.or(item.category_id),
+            display_order: self.display_order.unwrap_or(item.display_order),
+            allergens: self.allergens.unwrap_or(item.allergens),
+            diets: self.diets.unwrap_or(item.diets),
+            price: self.price.unwrap_or(item.price),
+            currency: Some(self.currency.unwrap_or(item.currency)),
+            modifiers: self.modifiers,
+        })
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MenuOutput {
     pub id: i32,
     pub name: String,
     pub description: String,
     pub time_to_cook_in_minutes: i32,
+    pub station_id: Option<i32>,
+    pub category_id: Option<i32>,
+    pub display_order: i32,
+    pub allergens: Vec<Allergen>,
+    pub diets: Vec<Diet>,
+    pub price: i32,
+    pub currency: String,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl MenuOutput {
+    fn from_row(row: &Row, modifiers: Vec<Modifier>) -> Self {
+        MenuOutput {
+            id: row.get("menu_id"),
+            name: row.get("menu_name"),
+            description: row.get("menu_description"),
+            time_to_cook_in_minutes: row.get("time_to_cook_in_minutes"),
+            station_id: row.get("station_id"),
+            category_id: row.get("category_id"),
+            display_order: row.get("display_order"),
+            allergens: allergen::from_column(row, "allergens"),
+            diets: allergen::from_column(row, "diets"),
+            price: row.get("price"),
+            currency: row.get("currency"),
+            modifiers,
+        }
+    }
 }
 
 impl fmt::Debug for MenuOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}. {} ({}), time: {} minutes",
+            "{}. {} ({}), time: {} minutes, price: {} {}",
             self.id,
             self.name,
             self.description,
-            self.time_to_cook_in_minutes
+            self.time_to_cook_in_minutes,
+            self.price,
+            self.currency
         )
     }
-}
\ No newline at end of file
+}
+
+
+/// The filter of the `GET /menu` endpoint.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MenuFilter {
+    /// The items that contain any of these allergens are left out.
+    pub exclude_allergens: Vec<Allergen>,
+    /// Only the items suitable for all of these diets are kept.
+    pub diets: Vec<Diet>,
+}
+
+impl MenuFilter {
+    /// Reads the filter from the query parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `exclude_allergens` - a comma-separated list of allergens, e.g. `gluten,nuts`;
+    /// * `diet` - a comma-separated list of diets, e.g. `vegan`.
+    ///
+    /// Returns `AppError::BadRequest` if any of the allergens or diets is unknown.
+    pub fn parse(exclude_allergens: Option<&str>, diet: Option<&str>) -> Result<Self, AppError> {
+        Ok(MenuFilter {
+            exclude_allergens: allergen::parse_list(exclude_allergens.unwrap_or_default())
+                .map_err(|error| AppError::BadRequest(format!("Invalid exclude_allergens: {}", error)))?,
+            diets: allergen::parse_list(diet.unwrap_or_default())
+                .map_err(|error| AppError::BadRequest(format!("Invalid diet: {}", error)))?,
+        })
+    }
+
+    /// Returns whether the item passes the filter.
+    pub fn matches(&self, item: &MenuOutput) -> bool {
+        !item.allergens.iter().any(|allergen| self.exclude_allergens.contains(allergen))
+            && self.diets.iter().all(|diet| item.diets.contains(diet))
+    }
+}
+
+
+/// An option a menu item can be ordered with, e.g. extra cheese or no onions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifier {
+    pub id: i32,
+    pub name: String,
+    pub kind: ModifierKind,
+    /// Added to the price of the item, in minor units.
+    pub price_delta: i32,
+    /// Added to the cooking time of the item, in minutes, may be negative.
+    pub cook_time_delta: i32,
+}
+
+impl Modifier {
+    /// Builds the modifier from a `menu_modifier` or `order_modifier` row.
+    pub(super) fn from_row(row: &Row) -> Self {
+        Modifier {
+            id: row.get("modifier_id"),
+            name: row.get("modifier_name"),
+            // The column only takes the known kinds
+            kind: row.get::<_, &str>("kind").parse().expect("Unknown modifier kind stored"),
+            price_delta: row.get("price_delta"),
+            cook_time_delta: row.get("cook_time_delta"),
+        }
+    }
+}
+
+/// A modifier as it is given with a menu item, see `Menu::modifiers`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModifierParams {
+    pub name: String,
+    pub kind: ModifierKind,
+    #[serde(default)]
+    pub price_delta: i32,
+    #[serde(default)]
+    pub cook_time_delta: i32,
+}
+
+/// Whether a modifier adds something to the item or leaves something out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifierKind {
+    Add,
+    Remove,
+}
+
+impl ModifierKind {
+    pub const ALL: [ModifierKind; 2] = [ModifierKind::Add, ModifierKind::Remove];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModifierKind::Add => "add",
+            ModifierKind::Remove => "remove",
+        }
+    }
+}
+
+impl FromStr for ModifierKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        ModifierKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| format!("Undefined modifier kind {}", value))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, time_to_cook_in_minutes: i32) -> Menu {
+        Menu {
+            id: None,
+            name: name.to_string(),
+            description: None,
+            time_to_cook_in_minutes,
+            station_id: None,
+            category_id: None,
+            display_order: 0,
+            allergens: Vec::new(),
+            diets: Vec::new(),
+            price: 990,
+            currency: None,
+            modifiers: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(item("Borscht", 15).validate().is_ok());
+
+        match item("  ", 0).validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                let fields: Vec<&str> = extensions["errors"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|error| error["field"].as_str().unwrap())
+                    .collect();
+                assert_eq!(fields, vec!["name", "time_to_cook_in_minutes"]);
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        assert!(item(&"x".repeat(MAX_NAME_LENGTH + 1), 5).validate().is_err());
+        assert!(Menu { price: -1, ..item("Borscht", 15) }.validate().is_err());
+        assert!(Menu { price: MAX_PRICE, ..item("Borscht", MAX_TIME_TO_COOK) }.validate().is_ok());
+        assert!(Menu { price: MAX_PRICE + 1, ..item("Borscht", 15) }.validate().is_err());
+        assert!(item("Borscht", MAX_TIME_TO_COOK + 1).validate().is_err());
+    }
+
+    #[test]
+    fn test_currency() {
+        assert_eq!(item("Borscht", 15).currency(), DEFAULT_CURRENCY);
+
+        for currency in ["EUR", "JPY"] {
+            assert!(Menu { currency: Some(currency.to_string()), ..item("Borscht", 15) }.validate().is_ok());
+        }
+        for currency in ["usd", "US", "EURO", "U$D", ""] {
+            assert!(Menu { currency: Some(currency.to_string()), ..item("Borscht", 15) }.validate().is_err(), "{}", currency);
+        }
+    }
+
+    #[test]
+    fn test_patch() {
+        let current = MenuOutput {
+            id: 3,
+            name: "Borscht".to_string(),
+            description: "Beet soup".to_string(),
+            time_to_cook_in_minutes: 15,
+            station_id: Some(1),
+            category_id: Some(2),
+            display_order: 3,
+            allergens: vec![Allergen::Celery],
+            diets: vec![Diet::Vegan],
+            price: 990,
+            currency: "EUR".to_string(),
+            modifiers: Vec::new(),
+        };
+        let patch = |time_to_cook_in_minutes, price| MenuPatch {
+            name: None,
+            description: None,
+            time_to_cook_in_minutes,
+            station_id: None,
+            category_id: None,
+            display_order: None,
+            allergens: None,
+            diets: None,
+            price,
+            currency: None,
+            modifiers: None,
+        };
+
+        let updated = patch(Some(20), None).apply(current.clone()).unwrap();
+        assert_eq!((updated.name.as_str(), updated.description(), updated.time_to_cook_in_minutes), ("Borscht", "Beet soup", 20));
+        assert_eq!((updated.station_id, updated.price, updated.currency()), (Some(1), 990, "EUR"));
+        assert_eq!((updated.category_id, updated.display_order), (Some(2), 3));
+        assert_eq!((updated.allergens, updated.diets), (vec![Allergen::Celery], vec![Diet::Vegan]));
+
+        let updated = patch(None, Some(1090)).apply(current.clone()).unwrap();
+        assert_eq!((updated.time_to_cook_in_minutes, updated.price), (15, 1090));
+
+        let empty = patch(None, None);
+        assert!(matches!(empty.apply(current), Err(AppError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_diets() {
+        let vegan = |allergens| Menu { allergens, diets: vec![Diet::Vegan, Diet::Halal], ..item("Chickpea Stew", 20) };
+        assert!(vegan(vec![Allergen::Celery, Allergen::Sesame]).validate().is_ok());
+
+        match vegan(vec![Allergen::Milk, Allergen::Celery, Allergen::Eggs]).validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => assert_eq!(extensions["errors"], json!([
+                { "field": "diets", "message": "a vegan item can't contain milk, eggs" },
+            ])),
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let item: Menu = serde_json::from_str(r#"{"name": "Borscht", "time_to_cook_in_minutes": 15, "price": 990}"#).unwrap();
+        assert!(item.allergens.is_empty() && item.diets.is_empty());
+        let item: Menu = serde_json::from_str(
+            r#"{"name": "Borscht", "time_to_cook_in_minutes": 15, "price": 990, "allergens": ["milk", "celery", "milk"]}"#,
+        ).unwrap();
+        assert_eq!(item.allergen_names(), vec!["celery", "milk"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let output = |allergens, diets| MenuOutput {
+            id: 1,
+            name: "Borscht".to_string(),
+            description: String::new(),
+            time_to_cook_in_minutes: 15,
+            station_id: None,
+            category_id: None,
+            display_order: 0,
+            allergens,
+            diets,
+            price: 990,
+            currency: DEFAULT_CURRENCY.to_string(),
+            modifiers: Vec::new(),
+        };
+        let filter = MenuFilter::parse(Some("gluten,nuts"), Some("vegan")).unwrap();
+        assert_eq!(filter.exclude_allergens, vec![Allergen::Gluten, Allergen::Nuts]);
+
+        assert!(filter.matches(&output(vec![Allergen::Celery], vec![Diet::Vegan, Diet::Vegetarian])));
+        assert!(!filter.matches(&output(vec![Allergen::Celery, Allergen::Nuts], vec![Diet::Vegan])));
+        assert!(!filter.matches(&output(Vec::new(), vec![Diet::Vegetarian])));
+        assert!(MenuFilter::default().matches(&output(vec![Allergen::Gluten], Vec::new())));
+
+        assert!(matches!(MenuFilter::parse(Some("shellfish"), None), Err(AppError::BadRequest(_))));
+        assert!(matches!(MenuFilter::parse(None, Some("keto")), Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_sort_items() {
+        let categories = vec![
+            CategoryOutput { id: 1, name: "Mains".to_string(), display_order: 2 },
+            CategoryOutput { id: 2, name: "Starters".to_string(), display_order: 1 },
+        ];
+        let output = |id, category_id, display_order| MenuOutput {
+            id,
+            name: format!("Item {}", id),
+            description: String::new(),
+            time_to_cook_in_minutes: 5,
+            station_id: None,
+            category_id,
+            display_order,
+            allergens: Vec::new(),
+            diets: Vec::new(),
+            price: 990,
+            currency: DEFAULT_CURRENCY.to_string(),
+            modifiers: Vec::new(),
+        };
+        let mut items = vec![
+            output(1, None, 0),
+            output(2, Some(1), 0),
+            output(3, Some(2), 5),
+            output(4, Some(2), 1),
+            output(5, Some(1), 0),
+            output(6, Some(9), -1),
+        ];
+
+        sort_items(&mut items, &categories);
+        assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![4, 3, 2, 5, 6, 1]);
+    }
+
+    #[test]
+    fn test_modifiers() {
+        let modifier = |name: &str, kind, price_delta| ModifierParams { name: name.to_string(), kind, price_delta, cook_time_delta: 0 };
+        let with_modifiers = |modifiers| Menu { modifiers: Some(modifiers), ..item("Carbonara", 5) };
+
+        assert!(with_modifiers(vec![modifier("Extra cheese", ModifierKind::Add, 150), modifier("No bacon", ModifierKind::Remove, 0)])
+            .validate()
+            .is_ok());
+
+        let invalid = with_modifiers(vec![
+            modifier(" ", ModifierKind::Add, 0),
+            modifier("No bacon", ModifierKind::Remove, -100),
+            modifier("no bacon", ModifierKind::Remove, 0),
+        ]);
+        match invalid.validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                let fields: Vec<&str> = extensions["errors"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|error| error["field"].as_str().unwrap())
+                    .collect();
+                assert_eq!(fields, vec!["modifiers[0].name", "modifiers[1].price_delta", "modifiers[2].name"]);
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let mut out_of_range = modifier("Truffles", ModifierKind::Add, i32::MAX);
+        out_of_range.cook_time_delta = i32::MIN;
+        match with_modifiers(vec![out_of_range]).validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                let fields: Vec<&str> = extensions["errors"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|error| error["field"].as_str().unwrap())
+                    .collect();
+                assert_eq!(fields, vec!["modifiers[0].price_delta", "modifiers[0].cook_time_delta"]);
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let params: ModifierParams = serde_json::from_str(r#"{"name": "Extra cheese", "kind": "add"}"#).unwrap();
+        assert_eq!((params.kind, params.price_delta, params.cook_time_delta), (ModifierKind::Add, 0, 0));
+        assert!(serde_json::from_str::<ModifierParams>(r#"{"name": "Extra cheese", "kind": "double"}"#).is_err());
+        for kind in ModifierKind::ALL {
+            assert_eq!(kind.as_str().parse::<ModifierKind>(), Ok(kind));
+        }
+    }
+}
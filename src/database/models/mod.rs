@@ -1,3 +1,8 @@
 pub mod menu;
 pub mod table;
-pub mod order;
\ No newline at end of file
+pub mod order;
+pub mod station;
+pub mod category;
+pub mod allergen;
+pub mod bill;
+pub mod payment;
@@ -1,7 +1,19 @@
-use std::time::SystemTime;
-use crate::database::model::Model;
+use std::{collections::{BTreeMap, HashMap}, str::FromStr, time::{Duration, SystemTime}};
+use postgres::{Row, Transaction};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use crate::{database::{self, model::Model}, error::AppError, scheduler::{Job, Scheduler}};
 use std::fmt;
-use super::menu::Menu;
+use super::{allergen::{self, Allergen, AllergenWarning}, menu::{Menu, Modifier}, station::Station, table::Table};
+
+/// The most lines an order can have, or menu IDs in the plain list.
+pub const MAX_LINES: usize = 100;
+
+/// The most items of the same kind an order line can have.
+pub const MAX_QUANTITY: i32 = 99;
+
+/// The longest allowed notes of an order line.
+pub const MAX_NOTES_LENGTH: usize = 500;
 
 /// A model for the `order` DB table.
 /// 
@@ -12,17 +24,38 @@ use super::menu::Menu;
 /// - `id` - i32 - order id
 /// - `table_id` - i32 - table id this order is for
 /// - `menu_id` - i32 - menu item id
-/// - `cooked_at` - SystemTime - time when this item will be prepared
-/// - `is_deleted` - bool - is this order has been deleted or not
+/// - `quantity` - i32 - the number of the items, cooked and served together
+/// - `notes` - String - free-text instructions for the kitchen, `None` if there are none
+/// - `cooked_at` - SystemTime - estimated time when this item will be prepared, see `Scheduler`
+/// - `priority` - i32 - items with a higher priority are cooked first if the kitchen uses `SchedulingPolicy::Priority`
+/// - `time_to_cook_in_minutes` - i32 - the cooking time of the menu item with the modifiers when the order was placed
+/// - `station_id` - i32 - the station of the menu item when the order was placed, `None` for the general kitchen
+/// - `price` - i32 - the price of the menu item in minor units when the order was placed, without the modifiers
+/// - `currency` - String - the currency of the price
+/// - `seat` - i32 - the seat the item is for, `None` if it's for the whole table
+/// - `bill_id` - i32 - the bill the item has been paid with, `None` while the table session is open
+/// - `status` - OrderStatus - where the item is in its lifecycle
+/// - `placed_at`, `accepted_at`, ... `voided_at` - SystemTime - when the item has got each status, see `OrderStatus::column`
 /// - `created_at` - SystemTime - order time
 /// - `updated_at` - SystemTime - most recent update time.
+/// 
+/// The modifiers chosen for the line are copied to the `order_modifier` table.
 #[derive(Serialize, Deserialize)]
 pub struct Order {
     pub id: Option<i32>,
     pub table_id: i32,
     pub menu_id: i32,
+    pub quantity: i32,
+    pub notes: Option<String>,
     pub cooked_at: Option<SystemTime>,
-    pub is_deleted: Option<bool>,
+    pub priority: i32,
+    pub time_to_cook_in_minutes: i32,
+    pub station_id: Option<i32>,
+    pub price: i32,
+    pub currency: String,
+    pub seat: Option<i32>,
+    pub bill_id: Option<i32>,
+    pub status: Option<OrderStatus>,
     pub created_at: Option<SystemTime>,
     pub updated_at: Option<SystemTime>,
 }
@@ -31,159 +64,851 @@ impl Model for Order {
     const TABLE_NAME: &'static str = "order";
 }
 
+/// Key of the advisory lock that keeps two requests from rescheduling the kitchen at once.
+const SCHEDULE_LOCK_KEY: i64 = 7_878_002;
+
 impl Order {
-    /// Returns all orders that are currently preparing and not removed.
+    /// Returns all orders, including the cancelled and voided ones.
     /// 
     /// TODO: Add a pagination
-    pub fn get_all() -> Result<Vec<OrderOutput>, String> {
-        match Self::query(&select_query(""), &[]) {
-            Ok(rows) => {
-                let mut orders = Vec::new();
-                for row in rows {
-                    orders.push(OrderOutput {
-                        id: row.get("order_id"),
-                        table_id: row.get("table_id"),
-                        seconds_left: row.get("seconds_left"),
-                        is_prepared: row.get("is_prepared"),
-                        is_deleted: row.get("is_deleted"),
-                        menu_id: row.get("menu_id"),
-                        menu_name: row.get("menu_name"),
-                        menu_description: row.get("menu_description"),
-                    });
-                }
-                Ok(orders)
-            },
-            Err(error) => Err(error),
-        }
+    /// 
+    /// # Arguments
+    /// 
+    /// * `now` - the current time to compute the remaining cooking time with.
+    pub fn get_all(now: SystemTime) -> Result<Vec<OrderOutput>, AppError> {
+        let rows = Self::query(&select_query("ORDER BY o.order_id"), &[])?;
+        Ok(rows.iter().map(|row| OrderOutput::from_row(row, now)).collect())
     }
 
     /// Add an order with specified menu items to a database for specified table.
     /// 
-    /// Returns a list of currently preparing items for a specified table.
+    /// Returns the created order lines, all of them `OrderStatus::Placed`.
     /// 
-    /// The time when the order item will be ready is calculated by adding the time it takes to prepare the specified menu item to the time the order is created.
+    /// The time when the order item will be ready is estimated by the scheduler, which also moves the estimates of the other waiting items.
     /// 
-    /// Several menu items may be passed at once.
-    pub fn create(order_params: OrderParams) -> Result<u64, String> {
-        let mut query_string = format!("INSERT INTO \"{}\" (table_id, menu_id, cooked_at, is_deleted, created_at, updated_at) VALUES ", Self::TABLE_NAME);
-        let mut values = Vec::new();
-        for menu_id in order_params.menu_id {
-            values.push(format!("(
-                {},
-                {},
-                NOW() + INTERVAL '1 minute' * (SELECT time_to_cook_in_minutes FROM \"{}\" WHERE menu_id = {}),
-                FALSE,
-                NOW(),
-                NOW())", order_params.table_id, menu_id, Menu::TABLE_NAME, menu_id));
-        }
-        query_string += &values.join(", ");
-        Self::execute(&query_string, &[])
+    /// Several lines may be passed at once. The table, all the menu items and their modifiers are checked first,
+    /// and `AppError::Validation` listing the unknown IDs is returned if any of them doesn't exist or has been deleted.
+    /// If the table is inactive, `AppError::Conflict` is returned.
+    /// Either all lines are created or none.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `order_params` - the table and the order lines;
+    /// * `scheduler` - the kitchen model to estimate the cooking times with;
+    /// * `now` - the order time.
+    pub fn create(order_params: OrderParams, scheduler: &Scheduler, now: SystemTime) -> Result<Vec<OrderOutput>, AppError> {
+        order_params.validate()?;
+        let lines = order_params.lines();
+
+        database::transaction(|transaction| {
+            lock_schedule(transaction)?;
+
+            // The rows are locked, so they can't be deleted before the order is stored
+            let table_row = transaction.query_opt(
+                &format!("SELECT is_active, allergies FROM \"{}\" WHERE table_id = $1 FOR KEY SHARE", Table::TABLE_NAME),
+                &[&order_params.table_id],
+            )?;
+            let table_active = table_row.as_ref().map(|row| row.get("is_active"));
+            let allergies: Vec<Allergen> = table_row.map(|row| allergen::from_column(&row, "allergies")).unwrap_or_default();
+
+            let menu_ids: Vec<i32> = lines.iter().map(|line| line.menu_id).collect();
+            let menu_rows = transaction.query(
+                &format!("
+                    SELECT menu_id, time_to_cook_in_minutes, allergens
+                    FROM \"{}\"
+                    WHERE menu_id = ANY($1) AND is_deleted = FALSE
+                    FOR KEY SHARE", Menu::TABLE_NAME),
+                &[&menu_ids],
+            )?;
+            let menu_items: HashMap<i32, i32> = menu_rows
+                .iter()
+                .map(|row| (row.get("menu_id"), row.get("time_to_cook_in_minutes")))
+                .collect();
+            let menu_allergens: HashMap<i32, Vec<Allergen>> = menu_rows
+                .iter()
+                .map(|row| (row.get("menu_id"), allergen::from_column(row, "allergens")))
+                .collect();
+
+            let modifier_ids: Vec<i32> = lines.iter().flat_map(|line| line.modifiers.iter().copied()).collect();
+            let modifiers: HashMap<i32, (i32, Modifier)> = transaction
+                .query("SELECT * FROM menu_modifier WHERE modifier_id = ANY($1)", &[&modifier_ids])?
+                .iter()
+                .map(|row| (row.get("modifier_id"), (row.get("menu_id"), Modifier::from_row(row))))
+                .collect();
+
+            order_params.check_references(table_active, &menu_items, &modifiers)?;
+
+            // The modifiers are copied, so later menu changes don't affect the order
+            let line_modifiers: Vec<Vec<Modifier>> = lines
+                .iter()
+                .map(|line| line.modifiers.iter().map(|id| modifiers[id].1.clone()).collect())
+                .collect();
+            let quantities: Vec<i32> = lines.iter().map(|line| line.quantity).collect();
+            let notes: Vec<Option<&str>> = lines.iter().map(OrderLine::notes).collect();
+            let cook_times: Vec<i32> = lines
+                .iter()
+                .zip(&line_modifiers)
+                .map(|(line, modifiers)| {
+                    cook_time(menu_items[&line.menu_id], modifiers).ok_or_else(|| cook_time_out_of_range(line.menu_id))
+                })
+                .collect::<Result<_, _>>()?;
+
+            // The IDs are taken before the insert, so that every line knows its order whatever order the rows are inserted in
+            let rows = transaction.query(
+                &format!("
+                    WITH line AS (
+                        SELECT nextval(pg_get_serial_sequence('\"{0}\"', 'order_id'))::INTEGER AS order_id, line.*
+                        FROM UNNEST($2::INTEGER[], $7::INTEGER[], $8::TEXT[], $9::INTEGER[])
+                            WITH ORDINALITY AS line(menu_id, quantity, notes, time_to_cook_in_minutes, position)
+                    ), inserted AS (
+                        INSERT INTO \"{0}\" (
+                            order_id, table_id, menu_id, quantity, notes, cooked_at, priority, time_to_cook_in_minutes, station_id,
+                            price, currency, seat, status, placed_at, created_at, updated_at
+                        )
+                        SELECT
+                            line.order_id,
+                            $1,
+                            line.menu_id,
+                            line.quantity,
+                            line.notes,
+                            $3,
+                            $4,
+                            line.time_to_cook_in_minutes,
+                            m.station_id,
+                            m.price,
+                            m.currency,
+                            $6,
+                            $5,
+                            $3,
+                            $3,
+                            $3
+                        FROM line
+                        JOIN \"{1}\" AS m ON m.menu_id = line.menu_id
+                        RETURNING order_id
+                    )
+                    SELECT inserted.order_id, line.position
+                    FROM inserted
+                    JOIN line ON line.order_id = inserted.order_id
+                    ORDER BY line.position",
+                    Self::TABLE_NAME,
+                    Menu::TABLE_NAME,
+                ),
+                &[
+                    &order_params.table_id,
+                    &menu_ids,
+                    &now,
+                    &order_params.priority,
+                    &OrderStatus::Placed.as_str(),
+                    &order_params.seat,
+                    &quantities,
+                    &notes,
+                    &cook_times,
+                ],
+            )?;
+            // All the menu items have been checked, so every line has been inserted
+            let mut order_ids = vec![0; lines.len()];
+            for row in &rows {
+                let position: i64 = row.get("position");
+                order_ids[position as usize - 1] = row.get("order_id");
+            }
+
+            insert_modifiers(transaction, &order_ids, &line_modifiers)?;
+            reschedule(transaction, scheduler, now)?;
+
+            let rows = transaction.query(&select_query("WHERE o.order_id = ANY($1) ORDER BY o.order_id"), &[&order_ids])?;
+            let mut orders: Vec<OrderOutput> = rows.iter().map(|row| OrderOutput::from_row(row, now)).collect();
+            warn_allergies(&mut orders, &allergies, &menu_allergens);
+            Ok(orders)
+        })
+    }
+
+    /// Moves the order to another status and returns the updated order.
+    /// 
+    /// The time of the transition is stored in the column of the new status.
+    /// The cooking times of the waiting items are estimated again, e.g. they move earlier if an item is cancelled.
+    /// 
+    /// ##The order can not be moved in the cases:
+    /// 
+    /// - if there is no order found with the specified ID (`AppError::NotFound`)
+    /// - if the transition is not allowed, see `OrderStatus::next` (`AppError::Conflict`).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `order_id` - the order to move;
+    /// * `status` - the new status;
+    /// * `scheduler` - the kitchen model to estimate the cooking times with;
+    /// * `now` - the time of the transition.
+    pub fn transition(order_id: i32, status: OrderStatus, scheduler: &Scheduler, now: SystemTime) -> Result<OrderOutput, AppError> {
+        database::transaction(|transaction| {
+            lock_schedule(transaction)?;
+
+            let current: OrderStatus = transaction.query_opt(
+                &format!("SELECT status FROM \"{}\" WHERE order_id = $1 FOR UPDATE", Self::TABLE_NAME),
+                &[&order_id],
+            )?
+                .ok_or_else(|| not_found(order_id))?
+                .get::<_, &str>("status")
+                .parse()
+                .map_err(AppError::Internal)?;
+            current.check_transition(status, order_id)?;
+
+            // A ready item has been cooked now, whatever the estimate was
+            transaction.execute(
+                &format!("
+                    UPDATE \"{}\"
+                    SET
+                        status = $2,
+                        {} = $3,
+                        cooked_at = CASE WHEN $4 THEN $3 ELSE cooked_at END,
+                        updated_at = $3
+                    WHERE order_id = $1",
+                    Self::TABLE_NAME,
+                    status.column(),
+                ),
+                &[&order_id, &status.as_str(), &now, &(status == OrderStatus::Ready)],
+            )?;
+
+            reschedule(transaction, scheduler, now)?;
+
+            let row = transaction.query_one(&select_query("WHERE o.order_id = $1"), &[&order_id])?;
+            Ok(OrderOutput::from_row(&row, now))
+        })
     }
 
-    /// Delete the order by its ID.
+    /// Returns the items the station still has to cook, in the order it cooks them.
+    /// 
+    /// The items being cooked come first, then the waiting ones in the order of the scheduling policy.
     /// 
-    /// A record in a table will not be deleted but marked as deleted.
+    /// If there is no such station, `AppError::NotFound` is returned.
     /// 
-    /// ##The order can not be deleted in the cases:
+    /// # Arguments
     /// 
-    /// - if there is no order found with the specified ID
-    /// - if the specified order does not belong to the specified table
-    /// - if the specified order is already deleted
-    /// - if the order is already prepared.
-    pub fn delete_one_for_table(table_id: i32, order_id: i32) -> Result<u64, String> {
-        Self::execute(&format!("
-            UPDATE\"{}\"
-            SET is_deleted = true
-            WHERE order_id = $1
-            AND table_id = $2
-            AND is_deleted = false
-            AND cooked_at > NOW()", Self::TABLE_NAME), &[&order_id, &table_id])
+    /// * `station_id` - the station;
+    /// * `scheduler` - the kitchen model that defines the cook order;
+    /// * `now` - the current time to compute the remaining cooking time with.
+    pub fn get_station_queue(station_id: i32, scheduler: &Scheduler, now: SystemTime) -> Result<Vec<OrderOutput>, AppError> {
+        Station::get_one(station_id)?;
+
+        let rows = Self::query(&select_query("WHERE o.station_id = $1 AND o.status = ANY($2)"), &[&station_id, &scheduled_statuses()])?;
+        let jobs: Vec<Job> = rows.iter().map(job_from_row).collect();
+        let mut orders: HashMap<i32, OrderOutput> = rows
+            .iter()
+            .map(|row| OrderOutput::from_row(row, now))
+            .map(|order| (order.id, order))
+            .collect();
+
+        Ok(scheduler
+            .queue(&jobs)
+            .into_iter()
+            .filter_map(|job| orders.remove(&job.id))
+            .collect())
     }
 
     /// Returns all orders for specified tables.
-    pub fn get_for_tables(table_list: Vec<i32>) -> Result<Vec<OrderOutput>, String> {
-        match Self::query(&select_query("WHERE o.table_id = ANY($1)"), &[&table_list]) {
-            Ok(rows) => {
-                let mut orders = Vec::new();
-                for row in rows {
-                    orders.push(OrderOutput {
-                        id: row.get("order_id"),
-                        table_id: row.get("table_id"),
-                        seconds_left: row.get("seconds_left"),
-                        is_prepared: row.get("is_prepared"),
-                        is_deleted: row.get("is_deleted"),
-                        menu_id: row.get("menu_id"),
-                        menu_name: row.get("menu_name"),
-                        menu_description: row.get("menu_description"),
-                    });
-                }
-                Ok(orders)
-            },
-            Err(error) => Err(error),
-        }
+    pub fn get_for_tables(table_list: Vec<i32>, now: SystemTime) -> Result<Vec<OrderOutput>, AppError> {
+        let rows = Self::query(&select_query("WHERE o.table_id = ANY($1) ORDER BY o.order_id"), &[&table_list])?;
+        Ok(rows.iter().map(|row| OrderOutput::from_row(row, now)).collect())
     }
 
     /// Returns an order with specified ID for specified table.
     /// 
-    /// If the specified order does not belong to a specified table nothing will be returned.
-    pub fn get_one_for_table(table_id: i32, order_id: i32) -> Result<OrderOutput, String> {
-        Self::query_one(&select_query("WHERE o.table_id = $1 AND o.order_id = $2"), &[&table_id, &order_id])
-        .map(|row| OrderOutput {
-            id: row.get("order_id"),
-            table_id: row.get("table_id"),
-            seconds_left: row.get("seconds_left"),
-            is_prepared: row.get("is_prepared"),
-            is_deleted: row.get("is_deleted"),
-            menu_id: row.get("menu_id"),
-            menu_name: row.get("menu_name"),
-            menu_description: row.get("menu_description"),
-        })
+    /// If the specified order does not belong to a specified table, `AppError::NotFound` is returned.
+    pub fn get_one_for_table(table_id: i32, order_id: i32, now: SystemTime) -> Result<OrderOutput, AppError> {
+        Self::query_opt(&select_query("WHERE o.table_id = $1 AND o.order_id = $2"), &[&table_id, &order_id])?
+            .map(|row| OrderOutput::from_row(&row, now))
+            .ok_or_else(|| AppError::NotFound(format!("Order {} not found for table {}", order_id, table_id)))
     }
 
     /// Returns an order with specified ID.
     /// 
-    /// If the specified order does not belong to a specified table nothing will be returned.
-    pub fn get_one(order_id: i32) -> Result<OrderOutput, String> {
-        Self::query_one(&select_query("WHERE o.order_id = $1"), &[&order_id])
-        .map(|row| OrderOutput {
-            id: row.get("order_id"),
-            table_id: row.get("table_id"),
-            seconds_left: row.get("seconds_left"),
-            is_prepared: row.get("is_prepared"),
-            is_deleted: row.get("is_deleted"),
-            menu_id: row.get("menu_id"),
-            menu_name: row.get("menu_name"),
-            menu_description: row.get("menu_description"),
-        })
+    /// If there is no such order, `AppError::NotFound` is returned.
+    pub fn get_one(order_id: i32, now: SystemTime) -> Result<OrderOutput, AppError> {
+        Self::query_opt(&select_query("WHERE o.order_id = $1"), &[&order_id])?
+            .map(|row| OrderOutput::from_row(&row, now))
+            .ok_or_else(|| not_found(order_id))
     }
 }
 
 
-fn select_query(where_clause: &str) -> String {
+pub(super) fn select_query(where_clause: &str) -> String {
     format!("
         SELECT
-            o.order_id,
-            o.table_id,
-            o.cooked_at,
-            o.is_deleted,
-            (o.cooked_at <= NOW() AND o.is_deleted = false) as is_prepared,
-            CAST(EXTRACT(EPOCH FROM (o.cooked_at - NOW())) AS INTEGER) AS seconds_left,
-            m.menu_id,
+            o.*,
             m.menu_name,
-            m.menu_description
+            m.menu_description,
+            om.*
         FROM \"{}\" as o
         LEFT JOIN
             \"{}\" AS m ON o.menu_id = m.menu_id
+        LEFT JOIN LATERAL (
+            SELECT
+                ARRAY_AGG(modifier_id ORDER BY position) AS modifier_ids,
+                ARRAY_AGG(modifier_name ORDER BY position) AS modifier_names,
+                ARRAY_AGG(kind ORDER BY position) AS modifier_kinds,
+                ARRAY_AGG(price_delta ORDER BY position) AS modifier_price_deltas,
+                ARRAY_AGG(cook_time_delta ORDER BY position) AS modifier_cook_time_deltas
+            FROM order_modifier
+            WHERE order_id = o.order_id
+        ) AS om ON TRUE
         {}", Order::TABLE_NAME, Menu::TABLE_NAME, where_clause)
 }
 
+/// Reads the modifiers of an order line from a row selected by `select_query`.
+fn modifiers_from_row(row: &Row) -> Vec<Modifier> {
+    // The aggregates are NULL if the line has no modifiers
+    let Some(ids) = row.get::<_, Option<Vec<i32>>>("modifier_ids") else {
+        return Vec::new();
+    };
+    let names: Vec<String> = row.get("modifier_names");
+    let kinds: Vec<String> = row.get("modifier_kinds");
+    let price_deltas: Vec<i32> = row.get("modifier_price_deltas");
+    let cook_time_deltas: Vec<i32> = row.get("modifier_cook_time_deltas");
+
+    ids.into_iter()
+        .zip(names)
+        .zip(kinds)
+        .zip(price_deltas.into_iter().zip(cook_time_deltas))
+        .map(|(((id, name), kind), (price_delta, cook_time_delta))| Modifier {
+            id,
+            name,
+            // The column only takes the known kinds
+            kind: kind.parse().expect("Unknown modifier kind stored"),
+            price_delta,
+            cook_time_delta,
+        })
+        .collect()
+}
+
+/// Stores the modifiers of the created order lines.
+/// 
+/// # Arguments
+/// 
+/// * `order_ids` - the created lines;
+/// * `line_modifiers` - the modifiers of every line, in the same order.
+fn insert_modifiers(transaction: &mut Transaction, order_ids: &[i32], line_modifiers: &[Vec<Modifier>]) -> Result<(), AppError> {
+    let mut rows: (Vec<i32>, Vec<i32>, Vec<&Modifier>) = (Vec::new(), Vec::new(), Vec::new());
+    for (order_id, modifiers) in order_ids.iter().zip(line_modifiers) {
+        for (position, modifier) in modifiers.iter().enumerate() {
+            rows.0.push(*order_id);
+            rows.1.push(position as i32 + 1);
+            rows.2.push(modifier);
+        }
+    }
+    let (order_ids, positions, modifiers) = rows;
+
+    let modifier_ids: Vec<i32> = modifiers.iter().map(|modifier| modifier.id).collect();
+    let names: Vec<&str> = modifiers.iter().map(|modifier| modifier.name.as_str()).collect();
+    let kinds: Vec<&str> = modifiers.iter().map(|modifier| modifier.kind.as_str()).collect();
+    let price_deltas: Vec<i32> = modifiers.iter().map(|modifier| modifier.price_delta).collect();
+    let cook_time_deltas: Vec<i32> = modifiers.iter().map(|modifier| modifier.cook_time_delta).collect();
+    transaction.execute("
+        INSERT INTO order_modifier (order_id, position, modifier_id, modifier_name, kind, price_delta, cook_time_delta)
+        SELECT *
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::INTEGER[], $4::TEXT[], $5::TEXT[], $6::INTEGER[], $7::INTEGER[])",
+        &[&order_ids, &positions, &modifier_ids, &names, &kinds, &price_deltas, &cook_time_deltas],
+    )?;
+
+    Ok(())
+}
+
+/// Takes the scheduling lock for the rest of the transaction.
+/// 
+/// Must be taken before any order rows are locked, so two requests never wait for each other's rows.
+fn lock_schedule(transaction: &mut Transaction) -> Result<(), AppError> {
+    transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&SCHEDULE_LOCK_KEY])?;
+    Ok(())
+}
+
+/// Returns the names of the statuses the scheduler estimates the cooking times for.
+fn scheduled_statuses() -> Vec<&'static str> {
+    OrderStatus::ALL
+        .iter()
+        .filter(|status| status.is_scheduled())
+        .map(OrderStatus::as_str)
+        .collect()
+}
+
+/// Estimates the cooking times of all the items the kitchen still has to cook and stores them.
+/// 
+/// Every station cooks its items with its own slots, see `Scheduler::schedule_stations`.
+fn reschedule(transaction: &mut Transaction, scheduler: &Scheduler, now: SystemTime) -> Result<(), AppError> {
+    let rows = transaction.query(
+        &format!("
+            SELECT order_id, station_id, priority, placed_at, cooking_at, cooked_at, time_to_cook_in_minutes
+            FROM \"{}\"
+            WHERE status = ANY($1)", Order::TABLE_NAME),
+        &[&scheduled_statuses()],
+    )?;
+    let jobs: Vec<Job> = rows.iter().map(job_from_row).collect();
+
+    let station_slots: HashMap<i32, usize> = transaction
+        .query(&format!("SELECT station_id, slots FROM \"{}\"", Station::TABLE_NAME), &[])?
+        .iter()
+        .map(|row| (row.get("station_id"), row.get::<_, i32>("slots").max(1) as usize))
+        .collect();
+
+    let (order_ids, cooked_at): (Vec<i32>, Vec<SystemTime>) = scheduler
+        .schedule_stations(&jobs, &station_slots, now)
+        .into_iter()
+        .unzip();
+    transaction.execute(
+        &format!("
+            UPDATE \"{}\" AS o
+            SET cooked_at = s.cooked_at
+            FROM UNNEST($1::INTEGER[], $2::TIMESTAMP[]) AS s(order_id, cooked_at)
+            WHERE o.order_id = s.order_id", Order::TABLE_NAME),
+        &[&order_ids, &cooked_at],
+    )?;
+
+    Ok(())
+}
+
+fn job_from_row(row: &Row) -> Job {
+    job(
+        row.get("order_id"),
+        row.get("station_id"),
+        row.get("priority"),
+        row.get("placed_at"),
+        row.get("cooking_at"),
+        row.get("cooked_at"),
+        row.get("time_to_cook_in_minutes"),
+    )
+}
+
+/// Describes an order item for the scheduler.
+/// 
+/// # Arguments
+/// 
+/// * `station_id` - the station that cooks the item, `None` for the general kitchen;
+/// * `started_at` - when the cooking has started, `None` if the item is waiting;
+/// * `estimated_at` - the previous estimate of when the item will be cooked;
+/// * `time_to_cook_in_minutes` - the cooking time stored with the order.
+pub fn job(
+    id: i32,
+    station_id: Option<i32>,
+    priority: i32,
+    placed_at: SystemTime,
+    started_at: Option<SystemTime>,
+    estimated_at: Option<SystemTime>,
+    time_to_cook_in_minutes: i32,
+) -> Job {
+    Job {
+        id,
+        station_id,
+        priority,
+        placed_at,
+        started_at,
+        estimated_at,
+        cook_time: Duration::from_secs(time_to_cook_in_minutes.max(0) as u64 * 60),
+    }
+}
+
+pub fn not_found(order_id: i32) -> AppError {
+    AppError::NotFound(format!("Order {} not found", order_id))
+}
+
+/// Returns the number of seconds until the item is cooked, negative if it has been cooked already.
+pub fn seconds_left(cooked_at: SystemTime, now: SystemTime) -> i32 {
+    match cooked_at.duration_since(now) {
+        Ok(duration) => duration.as_secs_f64().round() as i32,
+        Err(error) => -(error.duration().as_secs_f64().round() as i32),
+    }
+}
+
+/// Checks if the food is ready, see `OrderStatus::is_prepared`.
+///
+/// An order placed before the statuses existed and never moved since is also ready once its cooking time
+/// has passed, like it was before, see the `0016_legacy_order_status` migration.
+///
+/// # Arguments
+///
+/// * `status` - the status of the order;
+/// * `legacy_ready_at` - the cooking time of an order placed before the statuses, `None` for the others;
+/// * `now` - the current time.
+pub fn is_prepared(status: OrderStatus, legacy_ready_at: Option<SystemTime>, now: SystemTime) -> bool {
+    status.is_prepared() || (status == OrderStatus::Placed && legacy_ready_at.is_some_and(|ready_at| ready_at <= now))
+}
+
+/// Returns the cooking time of an item with the modifiers, at least a minute.
+///
+/// Returns `None` if it doesn't fit in i32, which the limits of the menu, see `menu::MAX_TIME_TO_COOK`, only allow
+/// for the items stored before them.
+pub fn cook_time(time_to_cook_in_minutes: i32, modifiers: &[Modifier]) -> Option<i32> {
+    let delta: i64 = modifiers.iter().map(|modifier| i64::from(modifier.cook_time_delta)).sum();
+    i32::try_from((i64::from(time_to_cook_in_minutes) + delta).max(1)).ok()
+}
+
+/// Returns the error for an order line whose cooking time with the modifiers is out of range, see `cook_time`.
+pub fn cook_time_out_of_range(menu_id: i32) -> AppError {
+    AppError::validation("The cooking time of the item with the modifiers is out of range").with("invalid_menu_id", menu_id)
+}
+
+/// Returns the price of an item with the modifiers, in minor units.
+pub fn unit_price(price: i32, modifiers: &[Modifier]) -> i64 {
+    i64::from(price) + modifiers.iter().map(|modifier| i64::from(modifier.price_delta)).sum::<i64>()
+}
+
+/// Warns about the placed items that contain allergens the table has declared allergies to.
+///
+/// The modifiers are not taken into account, so an item is warned about even if a modifier leaves the allergen out.
+///
+/// # Arguments
+///
+/// * `orders` - the placed items;
+/// * `allergies` - the allergies declared for the table;
+/// * `menu_allergens` - the allergens of the ordered menu items by their IDs.
+pub fn warn_allergies(orders: &mut [OrderOutput], allergies: &[Allergen], menu_allergens: &HashMap<i32, Vec<Allergen>>) {
+    for order in orders {
+        let allergens = menu_allergens.get(&order.menu_id).map(Vec::as_slice).unwrap_or_default();
+        order.allergen_warning = AllergenWarning::new(&order.menu_name, allergens, allergies);
+    }
+}
+
+/// Returns the requested IDs that are not among the found ones, without repetitions, in the order of the request.
+fn missing_ids<V>(requested: &[i32], found: &HashMap<i32, V>) -> Vec<i32> {
+    let mut missing = Vec::new();
+    for id in requested {
+        if !found.contains_key(id) && !missing.contains(id) {
+            missing.push(*id);
+        }
+    }
+    missing
+}
+
+/// The body of an order request, e.g.
+/// `{"table_id": 1, "lines": [{"menu_id": 2, "quantity": 2}, {"menu_id": 2, "notes": "Well done", "modifiers": [3]}]}`.
 #[derive(Serialize, Deserialize)]
 pub struct OrderParams {
     pub table_id: i32,
+    /// A line of one item for every menu item, for the clients that don't send `lines`.
+    #[serde(default)]
     pub menu_id: Vec<i32>,
+    #[serde(default)]
+    pub lines: Vec<OrderLine>,
+    /// Items with a higher priority are cooked first if the kitchen uses `SchedulingPolicy::Priority`.
+    #[serde(default)]
+    pub priority: i32,
+    /// The seat all the items are for, numbered from 1, or none if they are for the whole table.
+    #[serde(default)]
+    pub seat: Option<i32>,
+}
+
+/// A number of the same menu item with the same notes and modifiers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderLine {
+    pub menu_id: i32,
+    #[serde(default = "OrderLine::default_quantity")]
+    pub quantity: i32,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// The IDs of the modifiers of the menu item.
+    #[serde(default)]
+    pub modifiers: Vec<i32>,
+}
+
+impl OrderLine {
+    /// A line of one item without notes and modifiers.
+    pub fn new(menu_id: i32) -> Self {
+        OrderLine { menu_id, quantity: Self::default_quantity(), notes: None, modifiers: Vec::new() }
+    }
+
+    fn default_quantity() -> i32 {
+        1
+    }
+
+    /// Returns the notes without the surrounding whitespace, `None` if they are empty.
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref().map(str::trim).filter(|notes| !notes.is_empty())
+    }
+
+    /// Adds the problems with the line to `errors`.
+    fn check(&self, index: usize, errors: &mut Vec<Value>) {
+        if !(1..=MAX_QUANTITY).contains(&self.quantity) {
+            errors.push(json!({
+                "field": format!("lines[{}].quantity", index),
+                "message": format!("must be between 1 and {}", MAX_QUANTITY),
+            }));
+        }
+        if self.notes().is_some_and(|notes| notes.chars().count() > MAX_NOTES_LENGTH) {
+            errors.push(json!({
+                "field": format!("lines[{}].notes", index),
+                "message": format!("must be at most {} characters long", MAX_NOTES_LENGTH),
+            }));
+        }
+        let mut modifiers = self.modifiers.clone();
+        modifiers.sort_unstable();
+        if modifiers.windows(2).any(|pair| pair[0] == pair[1]) {
+            errors.push(json!({ "field": format!("lines[{}].modifiers", index), "message": "must not repeat" }));
+        }
+    }
+}
+
+impl OrderParams {
+    /// Returns the lines of the order, a line of one item for every ID of `menu_id` if there are no `lines`.
+    pub fn lines(&self) -> Vec<OrderLine> {
+        if self.lines.is_empty() {
+            return self.menu_id.iter().map(|menu_id| OrderLine::new(*menu_id)).collect();
+        }
+        self.lines.clone()
+    }
+
+    /// Checks the parameters that don't depend on the stored data.
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !self.menu_id.is_empty() && !self.lines.is_empty() {
+            return Err(AppError::validation("Either menu_id or lines must be specified, not both"));
+        }
+        if self.menu_id.is_empty() && self.lines.is_empty() {
+            return Err(AppError::validation("At least one menu item is required"));
+        }
+        if self.seat.is_some_and(|seat| seat < 1) {
+            return Err(AppError::validation("The seat must be a positive number"));
+        }
+
+        let mut errors = Vec::new();
+        let (field, count) = if self.lines.is_empty() { ("menu_id", self.menu_id.len()) } else { ("lines", self.lines.len()) };
+        if count > MAX_LINES {
+            errors.push(json!({ "field": field, "message": format!("must be at most {}", MAX_LINES) }));
+        }
+        for (index, line) in self.lines.iter().enumerate() {
+            line.check(index, &mut errors);
+        }
+        if !errors.is_empty() {
+            return Err(AppError::validation("The order lines are invalid").with("errors", errors));
+        }
+        Ok(())
+    }
+
+    /// Checks that the table, all the menu items and their modifiers exist and the table takes orders.
+    ///
+    /// Returns `AppError::Validation` listing the unknown IDs,
+    /// or `AppError::Conflict` if the table is inactive.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_active` - whether the found table is active, `None` if it hasn't been found;
+    /// * `menu_items` - the found menu items by their IDs;
+    /// * `modifiers` - the found modifiers by their IDs, with the IDs of their menu items.
+    pub fn check_references<V>(
+        &self,
+        table_active: Option<bool>,
+        menu_items: &HashMap<i32, V>,
+        modifiers: &HashMap<i32, (i32, Modifier)>,
+    ) -> Result<(), AppError> {
+        let lines = self.lines();
+        let menu_ids: Vec<i32> = lines.iter().map(|line| line.menu_id).collect();
+        let invalid_menu_ids = missing_ids(&menu_ids, menu_items);
+
+        // A modifier of another menu item is as unknown as a modifier that doesn't exist
+        let mut invalid_modifier_ids = Vec::new();
+        for line in &lines {
+            for id in &line.modifiers {
+                let valid = modifiers.get(id).is_some_and(|(menu_id, _)| *menu_id == line.menu_id);
+                if !valid && !invalid_modifier_ids.contains(id) {
+                    invalid_modifier_ids.push(*id);
+                }
+            }
+        }
+
+        if table_active.is_none() || !invalid_menu_ids.is_empty() || !invalid_modifier_ids.is_empty() {
+            let mut error = AppError::validation("The order refers to a table, menu items or modifiers that don't exist");
+            if table_active.is_none() {
+                error = error.with("invalid_table_id", self.table_id);
+            }
+            if !invalid_menu_ids.is_empty() {
+                error = error.with("invalid_menu_ids", invalid_menu_ids);
+            }
+            if !invalid_modifier_ids.is_empty() {
+                error = error.with("invalid_modifier_ids", invalid_modifier_ids);
+            }
+            return Err(error);
+        }
+
+        if table_active == Some(false) {
+            return Err(AppError::Conflict(format!("Table {} is inactive and doesn't take orders", self.table_id)));
+        }
+        Ok(())
+    }
+}
+
+
+/// Where an order item is in its lifecycle.
+/// 
+/// An item is placed by a waiter, accepted and cooked by the kitchen, and served when it's ready.
+/// Before the cooking starts it may be cancelled, afterwards the food exists and it can only be voided.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Placed,
+    Accepted,
+    Cooking,
+    Ready,
+    Served,
+    Cancelled,
+    Voided,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 7] = [
+        OrderStatus::Placed,
+        OrderStatus::Accepted,
+        OrderStatus::Cooking,
+        OrderStatus::Ready,
+        OrderStatus::Served,
+        OrderStatus::Cancelled,
+        OrderStatus::Voided,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Placed => "placed",
+            OrderStatus::Accepted => "accepted",
+            OrderStatus::Cooking => "cooking",
+            OrderStatus::Ready => "ready",
+            OrderStatus::Served => "served",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Voided => "voided",
+        }
+    }
+
+    /// Returns the column that stores the time the order has got this status.
+    pub fn column(&self) -> &'static str {
+        match self {
+            OrderStatus::Placed => "placed_at",
+            OrderStatus::Accepted => "accepted_at",
+            OrderStatus::Cooking => "cooking_at",
+            OrderStatus::Ready => "ready_at",
+            OrderStatus::Served => "served_at",
+            OrderStatus::Cancelled => "cancelled_at",
+            OrderStatus::Voided => "voided_at",
+        }
+    }
+
+    /// Returns the statuses an order may be moved to from this one.
+    pub fn next(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Placed => &[OrderStatus::Accepted, OrderStatus::Cancelled],
+            OrderStatus::Accepted => &[OrderStatus::Cooking, OrderStatus::Cancelled],
+            OrderStatus::Cooking => &[OrderStatus::Ready, OrderStatus::Voided],
+            OrderStatus::Ready => &[OrderStatus::Served, OrderStatus::Voided],
+            OrderStatus::Served => &[OrderStatus::Voided],
+            OrderStatus::Cancelled | OrderStatus::Voided => &[],
+        }
+    }
+
+    /// Checks that an order may be moved from this status to the specified one.
+    /// 
+    /// Returns `AppError::Conflict` otherwise.
+    pub fn check_transition(&self, status: OrderStatus, order_id: i32) -> Result<(), AppError> {
+        if self.next().contains(&status) {
+            return Ok(());
+        }
+
+        let detail = if *self == status {
+            format!("Order {} is already {}", order_id, status.as_str())
+        } else if self.next().is_empty() {
+            format!("Order {} is {} and can't be changed", order_id, self.as_str())
+        } else {
+            let allowed: Vec<&str> = self.next().iter().map(OrderStatus::as_str).collect();
+            format!(
+                "Order {} can't go from {} to {}, it may only become {}",
+                order_id,
+                self.as_str(),
+                status.as_str(),
+                allowed.join(" or "),
+            )
+        };
+        Err(AppError::Conflict(detail))
+    }
+
+    /// Checks if the order has been cancelled or voided.
+    pub fn is_deleted(&self) -> bool {
+        matches!(self, OrderStatus::Cancelled | OrderStatus::Voided)
+    }
+
+    /// Checks if the food is ready: cooked and not voided.
+    pub fn is_prepared(&self) -> bool {
+        matches!(self, OrderStatus::Ready | OrderStatus::Served)
+    }
+
+    /// Checks if the item still has to be cooked, so its cooking time is estimated by the scheduler.
+    pub fn is_scheduled(&self) -> bool {
+        matches!(self, OrderStatus::Placed | OrderStatus::Accepted | OrderStatus::Cooking)
+    }
+
+    /// Checks if the order still has to be cooked or served.
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::Placed | OrderStatus::Accepted | OrderStatus::Cooking | OrderStatus::Ready)
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        OrderStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| format!("Undefined order status {}", value))
+    }
+}
+
+/// The body of a transition request.
+#[derive(Serialize, Deserialize)]
+pub struct TransitionParams {
+    pub status: OrderStatus,
+}
+
+
+/// When an order item has got each status, `None` for the statuses it hasn't had.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderTimestamps {
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub placed_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub accepted_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cooking_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ready_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub served_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cancelled_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub voided_at: Option<OffsetDateTime>,
+}
+
+impl OrderTimestamps {
+    /// Records the time the order has got the status.
+    pub fn set(&mut self, status: OrderStatus, at: SystemTime) {
+        let at = Some(OffsetDateTime::from(at));
+        match status {
+            OrderStatus::Placed => self.placed_at = at,
+            OrderStatus::Accepted => self.accepted_at = at,
+            OrderStatus::Cooking => self.cooking_at = at,
+            OrderStatus::Ready => self.ready_at = at,
+            OrderStatus::Served => self.served_at = at,
+            OrderStatus::Cancelled => self.cancelled_at = at,
+            OrderStatus::Voided => self.voided_at = at,
+        }
+    }
+
+    fn from_row(row: &Row) -> Self {
+        let mut timestamps = OrderTimestamps::default();
+        for status in OrderStatus::ALL {
+            if let Some(at) = row.get::<_, Option<SystemTime>>(status.column()) {
+                timestamps.set(status, at);
+            }
+        }
+        timestamps
+    }
 }
 
 
+/// An order item as the API returns it.
+/// 
+/// `is_prepared` and `is_deleted` are derived from `status` for the clients that don't know the statuses yet.
 #[derive(Serialize, Deserialize)]
 pub struct OrderOutput {
     pub id: i32,
@@ -191,27 +916,359 @@ pub struct OrderOutput {
     pub menu_id: i32,
     pub menu_name: String,
     pub menu_description: String,
+    pub quantity: i32,
+    pub notes: Option<String>,
+    /// The modifiers as they were when the order was placed.
+    pub modifiers: Vec<Modifier>,
+    pub station_id: Option<i32>,
+    /// The price of one item in minor units, as it was when the order was placed, without the modifiers.
+    pub price: i32,
+    /// The price of the line with the modifiers, see `OrderOutput::unit_price`, times the quantity.
+    pub amount: i64,
+    pub currency: String,
+    /// The seat the item is for, `None` if it's for the whole table.
+    pub seat: Option<i32>,
+    /// The bill the item has been paid with, `None` until the table is checked out.
+    /// An item shared by several bills of a split has the first of them.
+    pub bill_id: Option<i32>,
+    pub status: OrderStatus,
+    pub priority: i32,
     pub seconds_left: i32,
     pub is_prepared: bool,
     pub is_deleted: bool,
+    pub timestamps: OrderTimestamps,
+    /// The allergens of the item the table has declared allergies to, only given when the order is placed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allergen_warning: Option<AllergenWarning>,
+}
+
+impl OrderOutput {
+    /// Builds the output from a row selected by `select_query`.
+    pub(super) fn from_row(row: &Row, now: SystemTime) -> Self {
+        // The column only takes the known statuses
+        let status: OrderStatus = row.get::<_, &str>("status").parse().expect("Unknown order status stored");
+        let price: i32 = row.get("price");
+        let quantity: i32 = row.get("quantity");
+        let modifiers = modifiers_from_row(row);
+
+        OrderOutput {
+            id: row.get("order_id"),
+            table_id: row.get("table_id"),
+            menu_id: row.get("menu_id"),
+            menu_name: row.get("menu_name"),
+            menu_description: row.get("menu_description"),
+            quantity,
+            notes: row.get("notes"),
+            station_id: row.get("station_id"),
+            price,
+            amount: unit_price(price, &modifiers) * i64::from(quantity),
+            modifiers,
+            currency: row.get("currency"),
+            seat: row.get("seat"),
+            bill_id: row.get("bill_id"),
+            status,
+            priority: row.get("priority"),
+            seconds_left: seconds_left(row.get("cooked_at"), now),
+            is_prepared: is_prepared(status, row.get("legacy_ready_at"), now),
+            is_deleted: status.is_deleted(),
+            timestamps: OrderTimestamps::from_row(row),
+            allergen_warning: None,
+        }
+    }
+
+    /// Returns the price of one item with the modifiers, in minor units.
+    pub fn unit_price(&self) -> i64 {
+        unit_price(self.price, &self.modifiers)
+    }
 }
 
 impl fmt::Debug for OrderOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Order {} for table {}: {} ({}){}",
+            "Order {} for table {}: {} x {} ({}) [{}{}]",
             self.id,
             self.table_id,
+            self.quantity,
             self.menu_name,
             self.menu_id,
-            if self.is_deleted {
-                " [deleted]".to_string()
-            } else if self.is_prepared {
-                " [completed]".to_string()
+            self.status.as_str(),
+            if self.status.is_open() && !self.is_prepared {
+                format!(", {} sec left", self.seconds_left)
             } else {
-                format!("[{} sec left]", self.seconds_left)
+                String::new()
             }
         )
     }
-}
\ No newline at end of file
+}
+
+
+/// The orders of some tables with the amounts they have to pay.
+#[derive(Serialize, Deserialize)]
+pub struct TableOrders {
+    pub orders: Vec<OrderOutput>,
+    pub totals: Vec<TableTotal>,
+}
+
+/// The amount a table has to pay in one currency.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableTotal {
+    pub table_id: i32,
+    pub currency: String,
+    /// The sum of the line amounts in minor units.
+    pub amount: i64,
+    /// The number of the items paid for, counting the quantities.
+    pub items: usize,
+}
+
+impl TableOrders {
+    /// Sums up the prices of the orders by table and currency, the table with the lower ID first.
+    /// 
+    /// The cancelled and voided items are listed, but not paid for, and neither are the items that have been billed already.
+    pub fn new(orders: Vec<OrderOutput>) -> Self {
+        let mut totals: BTreeMap<(i32, &str), TableTotal> = BTreeMap::new();
+        for order in orders.iter().filter(|order| !order.status.is_deleted() && order.bill_id.is_none()) {
+            let total = totals
+                .entry((order.table_id, order.currency.as_str()))
+                .or_insert_with(|| TableTotal {
+                    table_id: order.table_id,
+                    currency: order.currency.clone(),
+                    amount: 0,
+                    items: 0,
+                });
+            total.amount += order.amount;
+            total.items += order.quantity as usize;
+        }
+
+        let totals = totals.into_values().collect();
+        TableOrders { orders, totals }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::database::models::menu::{ModifierKind, MAX_MODIFIERS, MAX_PRICE};
+
+    #[test]
+    fn test_seconds_left() {
+        let cooked_at = SystemTime::UNIX_EPOCH + Duration::from_secs(600);
+
+        assert_eq!(seconds_left(cooked_at, cooked_at - Duration::from_millis(90_400)), 90);
+        assert_eq!(seconds_left(cooked_at, cooked_at + Duration::from_secs(5)), -5);
+    }
+
+    #[test]
+    fn test_transitions() {
+        use OrderStatus::*;
+
+        let allowed = |from: OrderStatus, to| from.check_transition(to, 1).is_ok();
+        assert!(allowed(Placed, Accepted) && allowed(Accepted, Cooking) && allowed(Cooking, Ready) && allowed(Ready, Served));
+        assert!(allowed(Placed, Cancelled) && allowed(Accepted, Cancelled));
+        assert!(allowed(Cooking, Voided) && allowed(Ready, Voided) && allowed(Served, Voided));
+
+        assert!(!allowed(Placed, Ready));
+        assert!(!allowed(Cooking, Cancelled));
+        assert!(!allowed(Served, Ready));
+        for status in OrderStatus::ALL {
+            assert!(!allowed(Cancelled, status) && !allowed(Voided, status));
+        }
+
+        match Cooking.check_transition(Cancelled, 7) {
+            Err(AppError::Conflict(detail)) => assert_eq!(detail, "Order 7 can't go from cooking to cancelled, it may only become ready or voided"),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_status_names() {
+        for status in OrderStatus::ALL {
+            assert_eq!(status.as_str().parse::<OrderStatus>(), Ok(status));
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+            assert_eq!(status.column(), format!("{}_at", status.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let mut timestamps = OrderTimestamps::default();
+        timestamps.set(OrderStatus::Placed, SystemTime::UNIX_EPOCH + Duration::from_secs(90));
+
+        let json = serde_json::to_value(&timestamps).unwrap();
+        assert_eq!(json["placed_at"], "1970-01-01T00:01:30Z");
+        assert_eq!(json["ready_at"], serde_json::Value::Null);
+        assert_eq!(serde_json::from_value::<OrderTimestamps>(json).unwrap(), timestamps);
+    }
+
+    #[test]
+    fn test_table_totals() {
+        let order = |id, table_id, price, currency: &str, status| OrderOutput {
+            id,
+            table_id,
+            menu_id: 1,
+            menu_name: "Borscht".to_string(),
+            menu_description: String::new(),
+            quantity: 1,
+            notes: None,
+            modifiers: Vec::new(),
+            station_id: None,
+            price,
+            amount: i64::from(price),
+            currency: currency.to_string(),
+            seat: None,
+            bill_id: None,
+            status,
+            priority: 0,
+            seconds_left: 0,
+            is_prepared: false,
+            is_deleted: status.is_deleted(),
+            timestamps: OrderTimestamps::default(),
+            allergen_warning: None,
+        };
+
+        let table_orders = TableOrders::new(vec![
+            order(1, 2, 1200, "USD", OrderStatus::Served),
+            order(2, 1, 850, "USD", OrderStatus::Placed),
+            order(3, 2, 1500, "USD", OrderStatus::Ready),
+            order(4, 2, 990, "EUR", OrderStatus::Cooking),
+            order(5, 2, 5000, "USD", OrderStatus::Voided),
+            order(6, 3, 700, "USD", OrderStatus::Cancelled),
+            // Paid already
+            OrderOutput { bill_id: Some(1), ..order(7, 1, 400, "USD", OrderStatus::Served) },
+            // Two soups
+            OrderOutput { quantity: 2, amount: 1700, ..order(8, 1, 850, "USD", OrderStatus::Served) },
+        ]);
+
+        let totals: Vec<(i32, &str, i64, usize)> = table_orders.totals
+            .iter()
+            .map(|total| (total.table_id, total.currency.as_str(), total.amount, total.items))
+            .collect();
+        assert_eq!(totals, vec![(1, "USD", 2550, 3), (2, "EUR", 990, 1), (2, "USD", 2700, 2)]);
+        assert_eq!(table_orders.orders.len(), 8);
+    }
+
+    fn modifier(id: i32, price_delta: i32, cook_time_delta: i32) -> Modifier {
+        Modifier { id, name: format!("Modifier {}", id), kind: ModifierKind::Add, price_delta, cook_time_delta }
+    }
+
+    #[test]
+    fn test_legacy_prepared() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(3600);
+        let minute = Duration::from_secs(60);
+
+        // An order placed before the statuses is ready by its cooking time until it's moved on
+        assert!(is_prepared(OrderStatus::Placed, Some(now - minute), now));
+        assert!(!is_prepared(OrderStatus::Placed, Some(now + minute), now));
+        assert!(!is_prepared(OrderStatus::Cancelled, Some(now - minute), now));
+        assert!(!is_prepared(OrderStatus::Cooking, Some(now - minute), now));
+        assert!(is_prepared(OrderStatus::Served, Some(now - minute), now));
+
+        // The others only by their status
+        assert!(!is_prepared(OrderStatus::Placed, None, now));
+        assert!(is_prepared(OrderStatus::Ready, None, now));
+    }
+
+    #[test]
+    fn test_line_prices() {
+        let modifiers = [modifier(1, 150, 2), modifier(2, 0, -1)];
+
+        assert_eq!(unit_price(1450, &modifiers), 1600);
+        assert_eq!(unit_price(1450, &[]), 1450);
+        assert_eq!(cook_time(5, &modifiers), Some(6));
+        assert_eq!(cook_time(1, &[modifier(3, 0, -2)]), Some(1));
+
+        // The sums don't overflow even for the items stored before the limits of the menu
+        assert_eq!(unit_price(i32::MAX, &[modifier(4, 1, 0)]), i64::from(i32::MAX) + 1);
+        assert_eq!(unit_price(MAX_PRICE, &vec![modifier(5, MAX_PRICE, 0); MAX_MODIFIERS]), 2_100_000_000);
+        assert_eq!(cook_time(i32::MAX, &[modifier(6, 0, 1)]), None);
+        assert_eq!(cook_time(i32::MIN, &[modifier(7, 0, -1)]), Some(1));
+    }
+
+    #[test]
+    fn test_order_lines() {
+        let params: OrderParams = serde_json::from_str(r#"{"table_id": 1, "menu_id": [2, 2]}"#).unwrap();
+        assert_eq!(params.lines(), vec![OrderLine::new(2), OrderLine::new(2)]);
+        assert!(params.validate().is_ok());
+
+        let params: OrderParams = serde_json::from_str(
+            r#"{"table_id": 1, "lines": [{"menu_id": 2, "quantity": 2}, {"menu_id": 2, "notes": " No pepper ", "modifiers": [3]}]}"#,
+        ).unwrap();
+        let lines = params.lines();
+        assert_eq!((lines[0].quantity, lines[0].notes(), lines[0].modifiers.len()), (2, None, 0));
+        assert_eq!((lines[1].quantity, lines[1].notes(), lines[1].modifiers.clone()), (1, Some("No pepper"), vec![3]));
+        assert!(params.validate().is_ok());
+
+        let invalid: OrderParams = serde_json::from_str(&format!(
+            r#"{{"table_id": 1, "lines": [{{"menu_id": 2, "quantity": 0}}, {{"menu_id": 2, "notes": "{}", "modifiers": [3, 3]}}]}}"#,
+            "x".repeat(MAX_NOTES_LENGTH + 1),
+        )).unwrap();
+        match invalid.validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                let fields: Vec<&str> = extensions["errors"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|error| error["field"].as_str().unwrap())
+                    .collect();
+                assert_eq!(fields, vec!["lines[0].quantity", "lines[1].notes", "lines[1].modifiers"]);
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let both: OrderParams = serde_json::from_str(r#"{"table_id": 1, "menu_id": [2], "lines": [{"menu_id": 2}]}"#).unwrap();
+        assert!(matches!(both.validate(), Err(AppError::Validation { .. })));
+        let none: OrderParams = serde_json::from_str(r#"{"table_id": 1}"#).unwrap();
+        assert!(matches!(none.validate(), Err(AppError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_max_lines() {
+        let params = |menu_ids: Vec<i32>| OrderParams { menu_id: menu_ids, ..serde_json::from_str(r#"{"table_id": 1}"#).unwrap() };
+        assert!(params(vec![2; MAX_LINES]).validate().is_ok());
+        match params(vec![2; MAX_LINES + 1]).validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                assert_eq!(extensions["errors"], json!([{ "field": "menu_id", "message": "must be at most 100" }]));
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let lines = OrderParams { lines: vec![OrderLine::new(2); MAX_LINES + 1], ..params(Vec::new()) };
+        match lines.validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => assert_eq!(extensions["errors"][0]["field"], "lines"),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_check_modifiers() {
+        let menu_items = HashMap::from([(1, ()), (2, ())]);
+        // Modifier 3 belongs to the item 2, modifier 4 to the item 1
+        let modifiers = HashMap::from([(3, (2, modifier(3, 0, 0))), (4, (1, modifier(4, 200, 1)))]);
+        let params = |modifiers: Vec<i32>| OrderParams {
+            table_id: 1,
+            menu_id: Vec::new(),
+            lines: vec![OrderLine { modifiers, ..OrderLine::new(2) }],
+            priority: 0,
+            seat: None,
+        };
+
+        assert!(params(vec![3]).check_references(Some(true), &menu_items, &modifiers).is_ok());
+        match params(vec![4, 3, 9]).check_references(Some(true), &menu_items, &modifiers).unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                assert_eq!(extensions["invalid_modifier_ids"], json!([4, 9]));
+                assert!(extensions.get("invalid_menu_ids").is_none());
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_missing_ids() {
+        let found = HashMap::from([(1, ()), (2, ())]);
+
+        assert_eq!(missing_ids(&[1, 2, 2, 1], &found), Vec::<i32>::new());
+        assert_eq!(missing_ids(&[7, 1, 3, 7, 2], &found), vec![7, 3]);
+    }
+}
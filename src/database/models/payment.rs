@@ -0,0 +1,689 @@
+use std::{collections::HashMap, str::FromStr, time::SystemTime};
+
+use postgres::{Row, Transaction};
+use serde_json::json;
+use time::OffsetDateTime;
+
+use crate::{
+    database::{self, model::Model},
+    error::AppError,
+    payments::{Card, PaymentError, PaymentProvider},
+};
+use std::fmt;
+use super::bill::{self, Bill};
+
+/// Selects the amount paid for the bill `b` as `paid`: the approved payments less their refunds.
+pub const PAID_COLUMN: &str = "(
+    SELECT COALESCE(SUM(p.amount - p.refunded), 0)::BIGINT
+    FROM payment AS p
+    WHERE p.bill_id = b.bill_id AND p.status = 'approved'
+) AS paid";
+
+/// A model for the `payment` DB table.
+///
+/// DB table: `payment`
+///
+/// ## Fields:
+///
+/// - `id` - i32 - payment id
+/// - `bill_id` - i32 - the bill paid, fully or partially
+/// - `amount` - i64 - the amount charged in minor units
+/// - `currency` - String - the currency of the bill
+/// - `status` - PaymentStatus - whether the provider has charged the card
+/// - `provider` - String - the name of the payment provider, see `PaymentProvider::name`
+/// - `reference` - String - the provider's reference of the charge, `None` unless it's approved
+/// - `card_last4` - String - the last four digits of the card, the rest of the number is never stored
+/// - `decline_reason` - String - the provider's reason if the payment is declined
+/// - `created_at` - SystemTime - payment time.
+///
+/// Every attempt is stored, the declined and timed out ones too. Only the approved payments count towards the bill.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Payment {
+    pub id: Option<i32>,
+    pub bill_id: i32,
+    pub amount: i64,
+    pub currency: String,
+    pub status: PaymentStatus,
+    pub provider: String,
+    pub reference: Option<String>,
+    pub card_last4: String,
+    pub decline_reason: Option<String>,
+    pub created_at: SystemTime,
+}
+
+impl Model for Payment {
+    const TABLE_NAME: &'static str = "payment";
+}
+
+/// A model for the `refund` DB table: a part of an approved payment given back.
+///
+/// DB table: `refund`
+///
+/// ## Fields:
+///
+/// - `id` - i32 - refund id
+/// - `payment_id` - i32 - the payment refunded
+/// - `amount` - i64 - the amount given back in minor units
+/// - `reason` - String - why, e.g. a dish sent back
+/// - `reference` - String - the provider's reference of the refund
+/// - `created_at` - SystemTime - refund time.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Refund {
+    pub id: Option<i32>,
+    pub payment_id: i32,
+    pub amount: i64,
+    pub reason: Option<String>,
+    pub reference: String,
+    pub created_at: SystemTime,
+}
+
+impl Model for Refund {
+    const TABLE_NAME: &'static str = "refund";
+}
+
+impl Payment {
+    /// Pays a bill, fully or partially, and returns the payment whether the provider has charged the card or not.
+    ///
+    /// The provider may take long to answer, so it is asked outside any transaction, with the bill unlocked.
+    /// The amount is checked again when the payment is stored, and if another payment has covered it meanwhile
+    /// or the payment can't be stored, the charge is given back, see `Payment::void`.
+    ///
+    /// ##The bill can not be paid in the cases:
+    ///
+    /// - if there is no bill with the specified ID (`AppError::NotFound`)
+    /// - if the card number or the amount is invalid (`AppError::Validation`)
+    /// - if the bill has been paid, or the amount is more than what is left to pay (`AppError::Conflict`).
+    ///
+    /// # Arguments
+    ///
+    /// * `bill_id` - the bill to pay;
+    /// * `params` - the card and the amount;
+    /// * `provider` - the payment provider to charge the card with;
+    /// * `now` - the payment time.
+    pub fn create(bill_id: i32, params: PaymentParams, provider: &dyn PaymentProvider, now: SystemTime) -> Result<PaymentOutput, AppError> {
+        let card = params.validate()?;
+
+        let (amount, currency) = database::transaction(|transaction| {
+            let (total, paid, currency) = lock_bill(transaction, bill_id)?;
+            Ok((payment_amount(bill_id, total, paid, &currency, params.amount)?, currency))
+        })?;
+
+        let payment = Payment::attempt(bill_id, &card, amount, currency, provider, now);
+
+        let stored = database::transaction(|transaction| {
+            // Only the approved payments count towards the bill
+            if payment.status == PaymentStatus::Approved {
+                let (total, paid, currency) = lock_bill(transaction, bill_id)?;
+                payment_amount(bill_id, total, paid, &currency, Some(payment.amount))?;
+            }
+
+            let row = transaction.query_one(
+                &format!("
+                    INSERT INTO \"{}\" (bill_id, amount, currency, status, provider, reference, card_last4, decline_reason, created_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    RETURNING *", Self::TABLE_NAME),
+                &[
+                    &payment.bill_id,
+                    &payment.amount,
+                    &payment.currency,
+                    &payment.status.as_str(),
+                    &payment.provider,
+                    &payment.reference,
+                    &payment.card_last4,
+                    &payment.decline_reason,
+                    &payment.created_at,
+                ],
+            )?;
+
+            Ok(PaymentOutput::from_row(&row, Vec::new()))
+        });
+
+        if stored.is_err() {
+            payment.void(provider);
+        }
+        stored
+    }
+
+    /// Returns all the payments of a bill with their refunds, the earliest first.
+    ///
+    /// If there is no such bill, `AppError::NotFound` is returned.
+    pub fn get_for_bill(bill_id: i32) -> Result<Vec<PaymentOutput>, AppError> {
+        database::transaction(|transaction| {
+            transaction.query_opt(&format!("SELECT 1 FROM \"{}\" WHERE bill_id = $1", Bill::TABLE_NAME), &[&bill_id])?
+                .ok_or_else(|| bill::not_found(bill_id))?;
+
+            let rows = transaction.query(
+                &format!("SELECT * FROM \"{}\" WHERE bill_id = $1 ORDER BY payment_id", Self::TABLE_NAME),
+                &[&bill_id],
+            )?;
+            with_refunds(transaction, &rows)
+        })
+    }
+
+    /// Returns a payment with specified ID and its refunds.
+    ///
+    /// If there is no such payment, `AppError::NotFound` is returned.
+    pub fn get_one(payment_id: i32) -> Result<PaymentOutput, AppError> {
+        database::transaction(|transaction| {
+            let row = transaction.query_opt(
+                &format!("SELECT * FROM \"{}\" WHERE payment_id = $1", Self::TABLE_NAME),
+                &[&payment_id],
+            )?.ok_or_else(|| not_found(payment_id))?;
+
+            Ok(with_refunds(transaction, &[row])?.remove(0))
+        })
+    }
+
+    /// Gives back a part of an approved payment and returns the refund.
+    ///
+    /// The bill is paid that much less, so it may become open again.
+    ///
+    /// ##The payment can not be refunded in the cases:
+    ///
+    /// - if there is no payment with the specified ID (`AppError::NotFound`)
+    /// - if the amount is invalid (`AppError::Validation`)
+    /// - if the payment hasn't been approved, the amount is more than what is left of it,
+    ///   or the provider refuses (`AppError::Conflict`)
+    /// - if the provider doesn't answer (`AppError::Unavailable`).
+    ///
+    /// # Arguments
+    ///
+    /// * `payment_id` - the payment to refund;
+    /// * `params` - the amount and the reason;
+    /// * `provider` - the payment provider that has charged the card;
+    /// * `now` - the refund time.
+    pub fn refund(payment_id: i32, params: RefundParams, provider: &dyn PaymentProvider, now: SystemTime) -> Result<RefundOutput, AppError> {
+        params.validate()?;
+
+        database::transaction(|transaction| {
+            // The payment is locked, so it can't be refunded twice at once
+            let row = transaction.query_opt(
+                &format!("SELECT * FROM \"{}\" WHERE payment_id = $1 FOR UPDATE", Self::TABLE_NAME),
+                &[&payment_id],
+            )?.ok_or_else(|| not_found(payment_id))?;
+            let payment = PaymentOutput::from_row(&row, Vec::new());
+
+            let amount = refund_amount(&payment, params.amount)?;
+            let refund = Refund::attempt(&payment, amount, params.reason, provider, now)?;
+
+            let row = transaction.query_one(
+                &format!("
+                    INSERT INTO \"{}\" (payment_id, amount, reason, reference, created_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    RETURNING *", Refund::TABLE_NAME),
+                &[&refund.payment_id, &refund.amount, &refund.reason, &refund.reference, &refund.created_at],
+            )?;
+            transaction.execute(
+                &format!("UPDATE \"{}\" SET refunded = refunded + $2 WHERE payment_id = $1", Self::TABLE_NAME),
+                &[&payment_id, &amount],
+            )?;
+
+            Ok(RefundOutput::from_row(&row))
+        })
+    }
+
+    /// Charges the card and returns the payment to store, whatever the provider has answered.
+    ///
+    /// # Arguments
+    ///
+    /// * `bill_id` - the bill to pay;
+    /// * `card` - the card to charge;
+    /// * `amount` - the amount in minor units, see `payment_amount`;
+    /// * `currency` - the currency of the bill;
+    /// * `provider` - the payment provider to charge the card with;
+    /// * `now` - the payment time.
+    pub fn attempt(
+        bill_id: i32,
+        card: &Card,
+        amount: i64,
+        currency: String,
+        provider: &dyn PaymentProvider,
+        now: SystemTime,
+    ) -> Payment {
+        let (status, reference, decline_reason) = match provider.charge(card, amount, &currency) {
+            Ok(reference) => (PaymentStatus::Approved, Some(reference), None),
+            Err(PaymentError::Declined(reason)) => (PaymentStatus::Declined, None, Some(reason)),
+            Err(PaymentError::Timeout) => (PaymentStatus::TimedOut, None, None),
+        };
+
+        Payment {
+            id: None,
+            bill_id,
+            amount,
+            currency,
+            status,
+            provider: provider.name().to_string(),
+            reference,
+            card_last4: card.last4().to_string(),
+            decline_reason,
+            created_at: now,
+        }
+    }
+
+    /// Gives back the charge of a payment that hasn't been stored, so the guest doesn't pay for nothing.
+    ///
+    /// Does nothing unless the payment has been approved. If the provider doesn't give the money back,
+    /// the charge is logged, so that it can be refunded by hand.
+    pub fn void(&self, provider: &dyn PaymentProvider) {
+        let (PaymentStatus::Approved, Some(reference)) = (self.status, &self.reference) else {
+            return;
+        };
+
+        if let Err(error) = provider.refund(reference, self.amount, &self.currency) {
+            println!(
+                "Error: charge {} of {} {} for bill {} hasn't been stored nor refunded: {:?}",
+                reference,
+                self.amount,
+                self.currency,
+                self.bill_id,
+                error,
+            );
+        }
+    }
+}
+
+impl Refund {
+    /// Asks the provider to give the amount back and returns the refund to store.
+    ///
+    /// Returns `AppError::Conflict` if the provider refuses, `AppError::Unavailable` if it doesn't answer.
+    ///
+    /// # Arguments
+    ///
+    /// * `payment` - the payment to refund;
+    /// * `amount` - the amount in minor units, see `refund_amount`;
+    /// * `reason` - why the amount is given back;
+    /// * `provider` - the payment provider that has charged the card;
+    /// * `now` - the refund time.
+    pub fn attempt(
+        payment: &PaymentOutput,
+        amount: i64,
+        reason: Option<String>,
+        provider: &dyn PaymentProvider,
+        now: SystemTime,
+    ) -> Result<Refund, AppError> {
+        let charge = payment.reference.as_deref().unwrap_or_default();
+        let reference = provider.refund(charge, amount, &payment.currency).map_err(|error| match error {
+            PaymentError::Declined(reason) => AppError::Conflict(format!("The payment provider has refused the refund: {}", reason)),
+            PaymentError::Timeout => AppError::Unavailable(format!("The payment provider hasn't answered a refund of payment {}", payment.id)),
+        })?;
+
+        Ok(Refund {
+            id: None,
+            payment_id: payment.id,
+            amount,
+            reason,
+            reference,
+            created_at: now,
+        })
+    }
+}
+
+/// Locks the bill and returns its total, the amount paid already and its currency.
+///
+/// If there is no such bill, `AppError::NotFound` is returned.
+fn lock_bill(transaction: &mut Transaction, bill_id: i32) -> Result<(i64, i64, String), AppError> {
+    let row = transaction.query_opt(
+        &format!("SELECT b.total, b.currency, {} FROM \"{}\" AS b WHERE b.bill_id = $1 FOR UPDATE", PAID_COLUMN, Bill::TABLE_NAME),
+        &[&bill_id],
+    )?.ok_or_else(|| bill::not_found(bill_id))?;
+
+    Ok((row.get("total"), row.get("paid"), row.get("currency")))
+}
+
+/// Adds the refunds to the payment rows.
+fn with_refunds(transaction: &mut Transaction, rows: &[Row]) -> Result<Vec<PaymentOutput>, AppError> {
+    let payment_ids: Vec<i32> = rows.iter().map(|row| row.get("payment_id")).collect();
+    let mut refunds: HashMap<i32, Vec<RefundOutput>> = HashMap::new();
+    for row in transaction.query(
+        &format!("SELECT * FROM \"{}\" WHERE payment_id = ANY($1) ORDER BY refund_id", Refund::TABLE_NAME),
+        &[&payment_ids],
+    )? {
+        let refund = RefundOutput::from_row(&row);
+        refunds.entry(refund.payment_id).or_default().push(refund);
+    }
+
+    Ok(rows
+        .iter()
+        .map(|row| PaymentOutput::from_row(row, refunds.remove(&row.get::<_, i32>("payment_id")).unwrap_or_default()))
+        .collect())
+}
+
+/// Returns the amount to charge for a bill: the requested one, or everything left to pay.
+///
+/// Returns `AppError::Conflict` if the bill has been paid or the amount is more than what is left to pay.
+///
+/// # Arguments
+///
+/// * `bill_id` - the bill to pay;
+/// * `total` - the total of the bill;
+/// * `paid` - the amount paid already, see `PAID_COLUMN`;
+/// * `currency` - the currency of the bill;
+/// * `requested` - the amount the guest wants to pay.
+pub fn payment_amount(bill_id: i32, total: i64, paid: i64, currency: &str, requested: Option<i64>) -> Result<i64, AppError> {
+    let due = (total - paid).max(0);
+    if due == 0 {
+        return Err(AppError::Conflict(format!("Bill {} has been paid", bill_id)));
+    }
+
+    match requested {
+        Some(amount) if amount > due => Err(AppError::Conflict(format!(
+            "Bill {} has only {} {} left to pay",
+            bill_id,
+            due,
+            currency,
+        ))),
+        Some(amount) => Ok(amount),
+        None => Ok(due),
+    }
+}
+
+/// Returns the amount to refund: the requested one, or everything left of the payment.
+///
+/// Returns `AppError::Conflict` if the payment hasn't been approved or the amount is more than what is left of it.
+pub fn refund_amount(payment: &PaymentOutput, requested: Option<i64>) -> Result<i64, AppError> {
+    if payment.status != PaymentStatus::Approved {
+        return Err(AppError::Conflict(format!(
+            "Payment {} hasn't been approved, there is nothing to refund",
+            payment.id,
+        )));
+    }
+
+    let left = payment.amount - payment.refunded;
+    if left == 0 {
+        return Err(AppError::Conflict(format!("Payment {} has been refunded in full", payment.id)));
+    }
+    match requested {
+        Some(amount) if amount > left => Err(AppError::Conflict(format!(
+            "Payment {} has only {} {} left to refund",
+            payment.id,
+            left,
+            payment.currency,
+        ))),
+        Some(amount) => Ok(amount),
+        None => Ok(left),
+    }
+}
+
+pub fn not_found(payment_id: i32) -> AppError {
+    AppError::NotFound(format!("Payment {} not found", payment_id))
+}
+
+
+/// The body of a payment request, e.g. `{"card_number": "4242 4242 4242 4242", "amount": 1500}`.
+#[derive(Serialize, Deserialize)]
+pub struct PaymentParams {
+    pub card_number: String,
+    /// In minor units, everything left to pay if it's not specified.
+    pub amount: Option<i64>,
+}
+
+impl PaymentParams {
+    /// Checks the parameters that don't depend on the stored data and returns the card.
+    ///
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors`.
+    pub fn validate(&self) -> Result<Card, AppError> {
+        let mut errors = Vec::new();
+        let card = Card::parse(&self.card_number);
+        if card.is_none() {
+            errors.push(json!({ "field": "card_number", "message": "must have 12 to 19 digits" }));
+        }
+        if self.amount.is_some_and(|amount| amount <= 0) {
+            errors.push(json!({ "field": "amount", "message": "must be positive" }));
+        }
+
+        match card {
+            Some(card) if errors.is_empty() => Ok(card),
+            _ => Err(AppError::validation("The payment data is invalid").with("errors", errors)),
+        }
+    }
+}
+
+/// The body of a refund request, e.g. `{"amount": 500, "reason": "The soup was cold"}`.
+#[derive(Default, Serialize, Deserialize)]
+pub struct RefundParams {
+    /// In minor units, everything left of the payment if it's not specified.
+    pub amount: Option<i64>,
+    pub reason: Option<String>,
+}
+
+impl RefundParams {
+    /// Checks the parameters that don't depend on the stored data.
+    ///
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors`.
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.amount.is_some_and(|amount| amount <= 0) {
+            let errors = vec![json!({ "field": "amount", "message": "must be positive" })];
+            return Err(AppError::validation("The refund data is invalid").with("errors", errors));
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Whether the provider has charged the card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Approved,
+    Declined,
+    /// The provider hasn't answered in time, the card is considered not charged.
+    TimedOut,
+}
+
+impl PaymentStatus {
+    pub const ALL: [PaymentStatus; 3] = [PaymentStatus::Approved, PaymentStatus::Declined, PaymentStatus::TimedOut];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Approved => "approved",
+            PaymentStatus::Declined => "declined",
+            PaymentStatus::TimedOut => "timed_out",
+        }
+    }
+}
+
+impl FromStr for PaymentStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        PaymentStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| format!("Undefined payment status {}", value))
+    }
+}
+
+
+/// A payment as the API returns it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PaymentOutput {
+    pub id: i32,
+    pub bill_id: i32,
+    pub amount: i64,
+    pub currency: String,
+    pub status: PaymentStatus,
+    pub provider: String,
+    pub reference: Option<String>,
+    pub card_last4: String,
+    pub decline_reason: Option<String>,
+    /// The sum of the refunds in minor units.
+    pub refunded: i64,
+    pub refunds: Vec<RefundOutput>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl PaymentOutput {
+    /// Builds the output of a payment stored with the specified ID.
+    pub fn new(payment: Payment, payment_id: i32) -> Self {
+        PaymentOutput {
+            id: payment_id,
+            bill_id: payment.bill_id,
+            amount: payment.amount,
+            currency: payment.currency,
+            status: payment.status,
+            provider: payment.provider,
+            reference: payment.reference,
+            card_last4: payment.card_last4,
+            decline_reason: payment.decline_reason,
+            refunded: 0,
+            refunds: Vec::new(),
+            created_at: OffsetDateTime::from(payment.created_at),
+        }
+    }
+
+    fn from_row(row: &Row, refunds: Vec<RefundOutput>) -> Self {
+        PaymentOutput {
+            id: row.get("payment_id"),
+            bill_id: row.get("bill_id"),
+            amount: row.get("amount"),
+            currency: row.get("currency"),
+            // The column only takes the known statuses
+            status: row.get::<_, &str>("status").parse().expect("Unknown payment status stored"),
+            provider: row.get("provider"),
+            reference: row.get("reference"),
+            card_last4: row.get("card_last4"),
+            decline_reason: row.get("decline_reason"),
+            refunded: row.get("refunded"),
+            refunds,
+            created_at: OffsetDateTime::from(row.get::<_, SystemTime>("created_at")),
+        }
+    }
+}
+
+impl fmt::Debug for PaymentOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Payment {} for bill {}: {} {} [{}], card ending with {}",
+            self.id,
+            self.bill_id,
+            self.amount,
+            self.currency,
+            self.status.as_str(),
+            self.card_last4,
+        )
+    }
+}
+
+/// A refund as the API returns it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RefundOutput {
+    pub id: i32,
+    pub payment_id: i32,
+    pub amount: i64,
+    pub reason: Option<String>,
+    pub reference: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl RefundOutput {
+    /// Builds the output of a refund stored with the specified ID.
+    pub fn new(refund: Refund, refund_id: i32) -> Self {
+        RefundOutput {
+            id: refund_id,
+            payment_id: refund.payment_id,
+            amount: refund.amount,
+            reason: refund.reason,
+            reference: refund.reference,
+            created_at: OffsetDateTime::from(refund.created_at),
+        }
+    }
+
+    fn from_row(row: &Row) -> Self {
+        RefundOutput {
+            id: row.get("refund_id"),
+            payment_id: row.get("payment_id"),
+            amount: row.get("amount"),
+            reason: row.get("reason"),
+            reference: row.get("reference"),
+            created_at: OffsetDateTime::from(row.get::<_, SystemTime>("created_at")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payments::FakeProvider;
+
+    fn conflict<T>(result: Result<T, AppError>) -> String {
+        match result {
+            Err(AppError::Conflict(detail)) => detail,
+            Err(error) => panic!("unexpected error {:?}", error),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    fn payment(number: &str, amount: i64) -> PaymentOutput {
+        let card = Card::parse(number).unwrap();
+        let payment = Payment::attempt(1, &card, amount, "USD".to_string(), &FakeProvider::new(), SystemTime::UNIX_EPOCH);
+        PaymentOutput::new(payment, 1)
+    }
+
+    #[test]
+    fn test_attempt() {
+        let approved = payment("4242 4242 4242 4242", 1000);
+        assert_eq!(approved.status, PaymentStatus::Approved);
+        assert!(approved.reference.is_some());
+        assert_eq!((approved.card_last4.as_str(), approved.provider.as_str()), ("4242", "fake"));
+
+        let declined = payment("4000 0000 0000 9995", 1000);
+        assert_eq!(declined.status, PaymentStatus::Declined);
+        assert_eq!(declined.decline_reason.as_deref(), Some("insufficient_funds"));
+        assert_eq!(declined.reference, None);
+
+        assert_eq!(payment("4000 0000 0000 0119", 1000).status, PaymentStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_payment_amount() {
+        assert_eq!(payment_amount(3, 5000, 0, "USD", None).unwrap(), 5000);
+        assert_eq!(payment_amount(3, 5000, 2000, "USD", None).unwrap(), 3000);
+        assert_eq!(payment_amount(3, 5000, 2000, "USD", Some(1000)).unwrap(), 1000);
+
+        assert_eq!(conflict(payment_amount(3, 5000, 2000, "USD", Some(3001))), "Bill 3 has only 3000 USD left to pay");
+        assert_eq!(conflict(payment_amount(3, 5000, 5000, "USD", None)), "Bill 3 has been paid");
+    }
+
+    #[test]
+    fn test_refund_amount() {
+        let mut approved = payment("4242 4242 4242 4242", 1000);
+        assert_eq!(refund_amount(&approved, None).unwrap(), 1000);
+
+        approved.refunded = 400;
+        assert_eq!(refund_amount(&approved, Some(600)).unwrap(), 600);
+        assert_eq!(conflict(refund_amount(&approved, Some(601))), "Payment 1 has only 600 USD left to refund");
+
+        approved.refunded = 1000;
+        assert_eq!(conflict(refund_amount(&approved, None)), "Payment 1 has been refunded in full");
+
+        let declined = payment("4000 0000 0000 0002", 1000);
+        assert_eq!(conflict(refund_amount(&declined, None)), "Payment 1 hasn't been approved, there is nothing to refund");
+    }
+
+    #[test]
+    fn test_params() {
+        let params = |card_number: &str, amount| PaymentParams { card_number: card_number.to_string(), amount };
+
+        assert_eq!(params("4242 4242 4242 4242", None).validate().unwrap().last4(), "4242");
+        match params("4242", Some(0)).validate() {
+            Err(AppError::Validation { extensions, .. }) => assert_eq!(extensions["errors"].as_array().unwrap().len(), 2),
+            _ => panic!("unexpected result"),
+        }
+
+        assert!(RefundParams::default().validate().is_ok());
+        assert!(RefundParams { amount: Some(-5), reason: None }.validate().is_err());
+    }
+
+    #[test]
+    fn test_status_names() {
+        for status in PaymentStatus::ALL {
+            assert_eq!(status.as_str().parse::<PaymentStatus>(), Ok(status));
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+        }
+    }
+}
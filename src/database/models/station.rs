@@ -0,0 +1,74 @@
+use postgres::Row;
+
+use crate::{database::model::Model, error::AppError};
+use std::fmt;
+
+/// A model for the `station` DB table.
+/// 
+/// DB table: `station`
+/// 
+/// ## Fields:
+/// 
+/// - `id` - i32 - station id
+/// - `name` - String - name of the station, e.g. grill, fryer, cold or bar
+/// - `slots` - i32 - number of items the station cooks at the same time.
+#[derive(Serialize, Deserialize)]
+pub struct Station {
+    pub id: Option<i32>,
+    pub name: String,
+    pub slots: i32,
+}
+
+impl Model for Station {
+    const TABLE_NAME: &'static str = "station";
+}
+
+impl Station {
+    /// Returns all stations.
+    pub fn get_all() -> Result<Vec<StationOutput>, AppError> {
+        let rows = Self::query(&format!("SELECT * FROM \"{}\" ORDER BY station_id", Self::TABLE_NAME), &[])?;
+        Ok(rows.iter().map(StationOutput::from_row).collect())
+    }
+
+    /// Returns a station with specified ID.
+    /// 
+    /// If there is no such station, `AppError::NotFound` is returned.
+    pub fn get_one(station_id: i32) -> Result<StationOutput, AppError> {
+        Self::query_opt(&format!("SELECT * FROM \"{}\" WHERE station_id = $1", Self::TABLE_NAME), &[&station_id])?
+            .map(|row| StationOutput::from_row(&row))
+            .ok_or_else(|| not_found(station_id))
+    }
+}
+
+pub fn not_found(station_id: i32) -> AppError {
+    AppError::NotFound(format!("Station {} not found", station_id))
+}
+
+/// Returns the error for a menu item that refers to an unknown station.
+pub fn invalid_station(station_id: i32) -> AppError {
+    AppError::validation("The station doesn't exist").with("invalid_station_id", station_id)
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StationOutput {
+    pub id: i32,
+    pub name: String,
+    pub slots: i32,
+}
+
+impl StationOutput {
+    fn from_row(row: &Row) -> Self {
+        StationOutput {
+            id: row.get("station_id"),
+            name: row.get("station_name"),
+            slots: row.get("slots"),
+        }
+    }
+}
+
+impl fmt::Debug for StationOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}. {} ({} slots)", self.id, self.name, self.slots)
+    }
+}
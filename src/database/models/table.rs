@@ -1,5 +1,10 @@
-use crate::database::model::Model;
-use std::fmt;
+use std::{fmt, str::FromStr};
+
+use postgres::Row;
+use serde_json::json;
+
+use crate::{database::{self, model::Model}, error::AppError};
+use super::{allergen::{self, Allergen}, order::Order};
 
 /// A model for the `table` DB table.
 /// 
@@ -7,13 +12,21 @@ use std::fmt;
 /// 
 /// ## Fields:
 /// 
-/// - `id` - i32 - table identificator
-/// - `description` - String - description of the table, e.g. a place in the restaurant.
-
+/// - `id` - i32 - table identificator, the number of the table in the restaurant
+/// - `description` - String - description of the table, e.g. a place in the restaurant
+/// - `capacity` - i32 - number of seats
+/// - `zone` - Zone - section of the restaurant the table is in
+/// - `allergies` - Vec<Allergen> - the allergies the guests at the table have declared, the orders warn about them
+/// - `is_active` - bool - inactive tables are kept for the order history, but can't take new orders.
 #[derive(Serialize, Deserialize)]
 pub struct Table {
     pub id: Option<i32>,
     pub description: String,
+    pub capacity: i32,
+    pub zone: Zone,
+    pub is_active: Option<bool>,
+    #[serde(default)]
+    pub allergies: Vec<Allergen>,
 }
 
 impl Model for Table {
@@ -21,35 +34,248 @@ impl Model for Table {
 }
 
 impl Table {
-    /// Returns all table list.
-    pub fn get_all_tables() -> Result<Vec<TableOutput>, String> {
-        let rows = Self::query_all_rows()?;
-        let mut tables = Vec::new();
-        for row in rows {
-            tables.push(TableOutput {
-                id: row.get("table_id"),
-                description: row.get("table_description"),
-            });
+    /// Returns all table list, including the inactive tables.
+    pub fn get_all_tables() -> Result<Vec<TableOutput>, AppError> {
+        let rows = Self::query(&format!("SELECT * FROM \"{}\" ORDER BY table_id", Self::TABLE_NAME), &[])?;
+        Ok(rows.iter().map(TableOutput::from_row).collect())
+    }
+
+    /// Returns a table with specified ID.
+    /// 
+    /// If there is no such table, `AppError::NotFound` is returned.
+    pub fn get_one(table_id: i32) -> Result<TableOutput, AppError> {
+        Self::query_opt(&format!("SELECT * FROM \"{}\" WHERE table_id = $1", Self::TABLE_NAME), &[&table_id])?
+            .map(|row| TableOutput::from_row(&row))
+            .ok_or_else(|| not_found(table_id))
+    }
+
+    /// Adds a table and returns it.
+    /// 
+    /// If the ID is not specified, the next free one is taken.
+    /// If a table with the specified ID already exists, `AppError::Conflict` is returned.
+    pub fn create(table: Table) -> Result<TableOutput, AppError> {
+        table.validate()?;
+
+        database::transaction(|transaction| {
+            if let Some(table_id) = table.id {
+                let exists = transaction.query_opt(
+                    &format!("SELECT 1 FROM \"{}\" WHERE table_id = $1", Self::TABLE_NAME),
+                    &[&table_id],
+                )?.is_some();
+                if exists {
+                    return Err(already_exists(table_id));
+                }
+            }
+
+            let row = transaction.query_one(
+                &format!("
+                    INSERT INTO \"{0}\" (table_id, table_description, capacity, zone, is_active, allergies)
+                    VALUES (COALESCE($1::INTEGER, nextval(pg_get_serial_sequence('\"{0}\"', 'table_id'))), $2, $3, $4, $5, $6)
+                    RETURNING *", Self::TABLE_NAME),
+                &[&table.id, &table.description.trim(), &table.capacity, &table.zone.as_str(), &table.is_active(), &table.allergy_names()],
+            )?;
+
+            // A table added with an explicit ID must not be given out again
+            transaction.execute(
+                &format!("SELECT setval(pg_get_serial_sequence('\"{0}\"', 'table_id'), MAX(table_id)) FROM \"{0}\"", Self::TABLE_NAME),
+                &[],
+            )?;
+
+            Ok(TableOutput::from_row(&row))
+        })
+    }
+
+    /// Replaces a table with specified ID and returns the updated table.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `table_id` - the table to update, `AppError::NotFound` is returned if there is no such table;
+    /// * `table` - the new data, the `id` field is ignored.
+    pub fn update(table_id: i32, table: Table) -> Result<TableOutput, AppError> {
+        table.validate()?;
+
+        Self::query_opt(
+            &format!("
+                UPDATE \"{}\"
+                SET table_description = $2, capacity = $3, zone = $4, is_active = $5, allergies = $6
+                WHERE table_id = $1
+                RETURNING *", Self::TABLE_NAME),
+            &[&table_id, &table.description.trim(), &table.capacity, &table.zone.as_str(), &table.is_active(), &table.allergy_names()],
+        )?
+            .map(|row| TableOutput::from_row(&row))
+            .ok_or_else(|| not_found(table_id))
+    }
+
+    /// Delete a table by its ID.
+    /// 
+    /// ##The table can not be deleted in the cases:
+    /// 
+    /// - if there is no table with the specified ID (`AppError::NotFound`)
+    /// - if there are orders for this table (`AppError::Conflict`): it should be deactivated instead.
+    pub fn delete(table_id: i32) -> Result<(), AppError> {
+        database::transaction(|transaction| {
+            // The row is locked, so no order can be created for this table until it is deleted
+            transaction.query_opt(
+                &format!("SELECT 1 FROM \"{}\" WHERE table_id = $1 FOR UPDATE", Self::TABLE_NAME),
+                &[&table_id],
+            )?.ok_or_else(|| not_found(table_id))?;
+
+            let has_orders = transaction.query_opt(
+                &format!("SELECT 1 FROM \"{}\" WHERE table_id = $1 LIMIT 1", Order::TABLE_NAME),
+                &[&table_id],
+            )?.is_some();
+            if has_orders {
+                return Err(has_orders_error(table_id));
+            }
+
+            transaction.execute(&format!("DELETE FROM \"{}\" WHERE table_id = $1", Self::TABLE_NAME), &[&table_id])?;
+            Ok(())
+        })
+    }
+
+    /// Checks the table before it is stored.
+    /// 
+    /// Returns `AppError::Validation` with the list of invalid fields in `errors` otherwise.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        if matches!(self.id, Some(id) if id <= 0) {
+            errors.push(json!({ "field": "id", "message": "must be positive" }));
+        }
+        if self.description.trim().is_empty() {
+            errors.push(json!({ "field": "description", "message": "must not be empty" }));
+        }
+        if self.capacity <= 0 {
+            errors.push(json!({ "field": "capacity", "message": "must be positive" }));
+        }
+
+        if errors.is_empty() {
+            return Ok(());
         }
-        Ok(tables)
+        Err(AppError::validation("The table is invalid").with("errors", errors))
+    }
+
+    /// Returns whether the table takes orders, `true` if it's not specified.
+    pub fn is_active(&self) -> bool {
+        self.is_active.unwrap_or(true)
+    }
+
+    /// Returns the allergies as they are stored, sorted and without repeats.
+    fn allergy_names(&self) -> Vec<&'static str> {
+        allergen::normalize(&self.allergies).iter().map(Allergen::as_str).collect()
     }
 }
 
+pub fn not_found(table_id: i32) -> AppError {
+    AppError::NotFound(format!("Table {} not found", table_id))
+}
 
+pub fn already_exists(table_id: i32) -> AppError {
+    AppError::Conflict(format!("Table {} already exists", table_id))
+}
 
-#[derive(Serialize, Deserialize)]
+pub fn has_orders_error(table_id: i32) -> AppError {
+    AppError::Conflict(format!("Table {} has orders and can't be deleted, deactivate it instead", table_id))
+}
+
+
+/// A section of the restaurant.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Zone {
+    Patio,
+    Bar,
+    #[default]
+    Main,
+}
+
+impl Zone {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Zone::Patio => "patio",
+            Zone::Bar => "bar",
+            Zone::Main => "main",
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "patio" => Ok(Zone::Patio),
+            "bar" => Ok(Zone::Bar),
+            "main" => Ok(Zone::Main),
+            _ => Err(format!("Undefined zone {}", value)),
+        }
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TableOutput {
     pub id: i32,
     pub description: String,
+    pub capacity: i32,
+    pub zone: Zone,
+    pub is_active: bool,
+    pub allergies: Vec<Allergen>,
+}
+
+impl TableOutput {
+    fn from_row(row: &Row) -> Self {
+        TableOutput {
+            id: row.get("table_id"),
+            description: row.get("table_description"),
+            capacity: row.get("capacity"),
+            // The column only takes the known zones
+            zone: row.get::<_, &str>("zone").parse().expect("Unknown zone stored"),
+            is_active: row.get("is_active"),
+            allergies: allergen::from_column(row, "allergies"),
+        }
+    }
 }
 
 impl fmt::Debug for TableOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}. {}",
+            "{}. {} ({}, {} seats){}",
             self.id,
-            self.description
+            self.description,
+            self.zone.as_str(),
+            self.capacity,
+            if self.is_active { "" } else { " [inactive]" }
         )
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut table = Table { id: None, description: "By the window".to_string(), capacity: 2, zone: Zone::Patio, is_active: None, allergies: Vec::new() };
+        assert!(table.validate().is_ok());
+        assert!(table.is_active());
+
+        table.id = Some(0);
+        table.description = " ".to_string();
+        table.capacity = 0;
+        match table.validate().unwrap_err() {
+            AppError::Validation { extensions, .. } => assert_eq!(extensions["errors"].as_array().unwrap().len(), 3),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_zone() {
+        for zone in [Zone::Patio, Zone::Bar, Zone::Main] {
+            assert_eq!(zone.as_str().parse::<Zone>(), Ok(zone));
+            assert_eq!(serde_json::to_value(zone).unwrap(), zone.as_str());
+        }
+        assert!("roof".parse::<Zone>().is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use postgres::error::SqlState;
+use serde_json::{json, Map, Value};
+
+use crate::http::{Response, Status};
+
+/// Errors of the models and the route handlers.
+///
+/// Turned into an `application/problem+json` response (RFC 9457) with a stable `code`
+/// that clients can match on. Database and internal errors are logged,
+/// while the client only gets a generic message.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request can't be read, e.g. an invalid identifier or broken JSON.
+    BadRequest(String),
+    /// The requested resource doesn't exist.
+    NotFound(String),
+    /// The request is well-formed but its content is invalid.
+    ///
+    /// `extensions` are added to the response body, e.g. the list of invalid fields.
+    Validation { detail: String, extensions: Map<String, Value> },
+    /// The request conflicts with the current state of the resource.
+    Conflict(String),
+    /// The database can't be reached or has no free connections.
+    Unavailable(String),
+    Database(postgres::Error),
+    Internal(String),
+}
+
+impl AppError {
+    /// Creates a validation error without extensions.
+    pub fn validation(detail: impl Into<String>) -> Self {
+        AppError::Validation { detail: detail.into(), extensions: Map::new() }
+    }
+
+    /// Adds a member to the response body of a validation error.
+    ///
+    /// Other errors are returned unchanged.
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        if let AppError::Validation { extensions, .. } = &mut self {
+            extensions.insert(name.to_string(), value.into());
+        }
+
+        self
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            AppError::BadRequest(_) => Status::BadRequest,
+            AppError::NotFound(_) => Status::NotFound,
+            AppError::Validation { .. } => Status::UnprocessableEntity,
+            AppError::Conflict(_) => Status::Conflict,
+            AppError::Unavailable(_) => Status::ServiceUnavailable,
+            AppError::Database(_) | AppError::Internal(_) => Status::InternalServerError,
+        }
+    }
+
+    /// Returns the stable error code of the response body.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation { .. } => "validation_failed",
+            AppError::Conflict(_) => "conflict",
+            AppError::Unavailable(_) => "service_unavailable",
+            AppError::Database(_) | AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Returns the message for the client. Details of the server side errors are not exposed.
+    fn detail(&self) -> &str {
+        match self {
+            AppError::BadRequest(detail)
+            | AppError::NotFound(detail)
+            | AppError::Validation { detail, .. }
+            | AppError::Conflict(detail) => detail,
+            AppError::Unavailable(_) => "The service is temporarily unavailable, try again later",
+            AppError::Database(_) | AppError::Internal(_) => "An unexpected error occurred",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(detail)
+            | AppError::NotFound(detail)
+            | AppError::Validation { detail, .. }
+            | AppError::Conflict(detail)
+            | AppError::Unavailable(detail)
+            | AppError::Internal(detail) => write!(f, "{}", detail),
+            AppError::Database(error) => write!(f, "Database error: {}", error),
+        }
+    }
+}
+
+impl From<postgres::Error> for AppError {
+    /// Constraint violations mean the request doesn't fit the stored data, everything else is a server error.
+    fn from(error: postgres::Error) -> Self {
+        match error.code() {
+            Some(&SqlState::UNIQUE_VIOLATION) | Some(&SqlState::FOREIGN_KEY_VIOLATION) => {
+                AppError::Conflict("The request conflicts with the stored data".to_string())
+            },
+            _ => AppError::Database(error),
+        }
+    }
+}
+
+impl From<AppError> for Response {
+    fn from(error: AppError) -> Self {
+        let status = error.status();
+        if status == Status::InternalServerError || status == Status::ServiceUnavailable {
+            println!("Error: {}", error);
+        }
+
+        let mut body = json!({
+            "type": "about:blank",
+            "title": status.reason(),
+            "status": status.code(),
+            "detail": error.detail(),
+            "code": error.code(),
+        });
+        if let (AppError::Validation { extensions, .. }, Value::Object(body)) = (error, &mut body) {
+            body.extend(extensions);
+        }
+
+        Response::new(status)
+            .json(&body)
+            .header("Content-Type", "application/problem+json")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(response: &Response) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_problem_response() {
+        let response = Response::from(AppError::NotFound("Order 5 not found".to_string()));
+
+        assert_eq!(response.status, Status::NotFound);
+        assert_eq!(response.headers.get("Content-Type"), Some("application/problem+json"));
+        assert_eq!(body(&response), json!({
+            "type": "about:blank",
+            "title": "Not Found",
+            "status": 404,
+            "detail": "Order 5 not found",
+            "code": "not_found",
+        }));
+    }
+
+    #[test]
+    fn test_validation_extensions() {
+        let error = AppError::validation("Unknown menu items").with("invalid_menu_ids", vec![7, 9]);
+        let body = body(&Response::from(error));
+
+        assert_eq!(body["status"], 422);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["invalid_menu_ids"], json!([7, 9]));
+    }
+
+    #[test]
+    fn test_internal_details_hidden() {
+        let response = Response::from(AppError::Internal("connection refused at 10.0.0.5".to_string()));
+        let body = body(&response);
+
+        assert_eq!(response.status, Status::InternalServerError);
+        assert_eq!(body["code"], "internal_error");
+        assert_eq!(body["detail"], "An unexpected error occurred");
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    net::{self, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use crate::{error::AppError, shutdown::Shutdown};
+
+use super::{
+    request::{Request, RequestError},
+    response::Response,
+    router::Router,
+    status::Status,
+};
+
+/// How long to wait for the client to stop sending before closing the connection.
+const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many bytes of unread input to discard before closing the connection.
+const LINGER_MAX_BYTES: u64 = 64 * 1024;
+
+/// Reads from the connection either until a deadline or with a timeout on every read.
+///
+/// The read timeout of the socket applies to every read, so on its own it would let a client
+/// that sends a byte now and then hold the connection for as long as it likes.
+struct DeadlineStream<'a> {
+    stream: &'a TcpStream,
+    /// When the reads must be over, `None` if only every read is timed.
+    deadline: Option<Instant>,
+    /// How long a single read may wait for input.
+    read_timeout: Duration,
+}
+
+impl DeadlineStream<'_> {
+    /// Gives the reads from now on `timeout` in total.
+    fn set_deadline(&mut self, timeout: Duration) {
+        self.deadline = Some(Instant::now() + timeout);
+    }
+
+    /// Gives every read from now on `timeout`, however long the reads take in total.
+    fn set_read_timeout(&mut self, timeout: Duration) {
+        self.deadline = None;
+        self.read_timeout = timeout;
+    }
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = match self.deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => self.read_timeout,
+        };
+        if timeout.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "The deadline of the request has passed"));
+        }
+
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Settings of persistent connections.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionConfig {
+    /// How long to wait for the next request before closing an idle connection,
+    /// and how long the body of a request may stall.
+    pub idle_timeout: Duration,
+    /// How long the request line and the headers may take to arrive once the request has started.
+    pub header_timeout: Duration,
+    /// How many requests may be served over a single connection.
+    pub max_requests: usize,
+}
+
+/// Serves requests coming over the connection until it is closed.
+///
+/// The connection is kept open between requests (`Connection: keep-alive`) unless the client asks to close it,
+/// it stays idle longer than `idle_timeout` or it has served `max_requests` requests.
+/// Once a request has started, its headers must arrive within `header_timeout`, and its body may pause for at most
+/// `idle_timeout` at a time, however long it takes in total. Otherwise the connection is closed.
+/// Once a shutdown is requested, the request in progress is answered and the connection is closed.
+///
+/// Pipelined requests are read from the same buffer and answered in the order they arrived.
+///
+/// # Arguments
+///
+/// * `stream` - the client connection;
+/// * `router` - the router to dispatch the requests with;
+/// * `state` - the application state passed to the handlers;
+/// * `config` - persistent connection settings;
+/// * `shutdown` - the server shutdown flag.
+pub fn handle_connection<S>(stream: TcpStream, router: &Router<S>, state: &S, config: ConnectionConfig, shutdown: &Shutdown) {
+    let mut reader = BufReader::new(DeadlineStream { stream: &stream, deadline: None, read_timeout: config.idle_timeout });
+
+    for served in 1..=config.max_requests {
+        // Only the wait for the next request is limited by the idle timeout
+        reader.get_mut().set_read_timeout(config.idle_timeout);
+        match reader.fill_buf() {
+            Ok([]) => return,
+            Ok(_) => {},
+            Err(e) => {
+                if !is_timeout(&e) {
+                    println!("Error: {}", e);
+                }
+                return;
+            },
+        }
+
+        reader.get_mut().set_deadline(config.header_timeout);
+        let request = Request::read_from_with(&mut reader, |reader| reader.get_mut().set_read_timeout(config.idle_timeout));
+        let (response, keep_alive) = match request {
+            Ok(request) => {
+                let keep_alive = request.keep_alive() && served < config.max_requests;
+                let response = match panic::catch_unwind(AssertUnwindSafe(|| dispatch(router, state, request))) {
+                    Ok(response) => response,
+                    Err(payload) => {
+                        // The client still gets an answer, then the panic goes on to the thread pool that counts it.
+                        let _ = Response::new(Status::InternalServerError)
+                            .header("Connection", "close")
+                            .text("Internal server error")
+                            .write_to(&mut &stream);
+                        let _ = stream.shutdown(net::Shutdown::Write);
+                        panic::resume_unwind(payload);
+                    },
+                };
+                (response, keep_alive && !shutdown.is_requested())
+            },
+            Err(RequestError::ConnectionClosed) => return,
+            Err(RequestError::Io(e)) => {
+                if !is_timeout(&e) {
+                    println!("Error: {}", e);
+                }
+                return;
+            },
+            // We can't tell where the next request starts after a broken one, so the connection is closed.
+            Err(error) => (error_response(error), false),
+        };
+
+        let response = if keep_alive {
+            response
+                .header("Connection", "keep-alive")
+                .header("Keep-Alive", format!(
+                    "timeout={}, max={}",
+                    config.idle_timeout.as_secs(),
+                    config.max_requests - served,
+                ))
+        } else {
+            response.header("Connection", "close")
+        };
+
+        if let Err(e) = response.write_to(&mut &stream) {
+            println!("Error: {}", e);
+            return;
+        }
+
+        if !keep_alive {
+            return linger_close(reader);
+        }
+    }
+}
+
+/// Answers a connection the server has no capacity for with `503 Service Unavailable` and closes it.
+///
+/// Meant to be called from the listener thread, so it never waits for the client:
+/// the request itself is not read, only the input that has already arrived is discarded.
+///
+/// # Arguments
+///
+/// * `stream` - the client connection;
+/// * `retry_after` - when the client may try again.
+pub fn reject_connection(stream: TcpStream, retry_after: Duration) {
+    let response = Response::new(Status::ServiceUnavailable)
+        .header("Retry-After", retry_after.as_secs().to_string())
+        .header("Connection", "close")
+        .text("The server is too busy, try again later");
+
+    if stream.set_nonblocking(true).is_err() || response.write_to(&mut &stream).is_err() {
+        return;
+    }
+
+    let _ = stream.shutdown(net::Shutdown::Write);
+    let _ = io::copy(&mut (&stream).take(LINGER_MAX_BYTES), &mut io::sink());
+}
+
+/// Closes the connection after the last response.
+///
+/// If we closed the socket while there is still unread input (e.g. pipelined requests we won't answer),
+/// the peer would get a reset and could lose the response we have just sent.
+/// So we shut down our side first and discard whatever the client is still sending for a short while.
+fn linger_close(mut reader: BufReader<DeadlineStream>) {
+    if reader.get_ref().stream.shutdown(net::Shutdown::Write).is_err() {
+        return;
+    }
+
+    reader.get_mut().set_deadline(LINGER_TIMEOUT);
+    let _ = io::copy(&mut reader.take(LINGER_MAX_BYTES), &mut io::sink());
+}
+
+/// Finds a handler for the request and calls it.
+pub fn dispatch<S>(router: &Router<S>, state: &S, mut request: Request) -> Response {
+    match router.get_handler(&request.path, request.method) {
+        Ok((handler, params)) => {
+            request.params = params;
+            handler(&request, state)
+        },
+        Err(error) => AppError::NotFound(error).into(),
+    }
+}
+
+fn error_response(error: RequestError) -> Response {
+    let status = match error {
+        RequestError::PayloadTooLarge => Status::PayloadTooLarge,
+        RequestError::UnsupportedMethod(_) => Status::NotImplemented,
+        _ => Status::BadRequest,
+    };
+
+    Response::new(status).text(error.to_string())
+}
+
+/// Read timeouts are reported as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Write,
+        net::TcpListener,
+        thread,
+    };
+
+    fn serve(config: ConnectionConfig) -> TcpStream {
+        serve_until(config, Shutdown::new())
+    }
+
+    fn serve_until(config: ConnectionConfig, shutdown: Shutdown) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let router = Router::new("/test")
+                .get("/panic", panic_handler)
+                .get("/:name", echo_handler)
+                .post("/upload", upload_handler);
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, &router, &(), config, &shutdown);
+        });
+
+        TcpStream::connect(address).unwrap()
+    }
+
+    fn config(max_requests: usize) -> ConnectionConfig {
+        ConnectionConfig {
+            idle_timeout: Duration::from_secs(5),
+            header_timeout: Duration::from_secs(5),
+            max_requests,
+        }
+    }
+
+    /// Reads everything until the server closes the connection.
+    fn read_all(mut stream: TcpStream) -> String {
+        let mut output = String::new();
+        stream.read_to_string(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_pipelined_requests() {
+        let mut stream = serve(config(10));
+        stream.write_all(b"GET /test/one HTTP/1.1\r\n\r\nGET /test/two HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+
+        let output = read_all(stream);
+        let first = output.find("hello one").unwrap();
+        let second = output.find("hello two").unwrap();
+        assert!(first < second);
+        assert_eq!(output.matches("Content-Length: 9\r\n").count(), 2);
+        assert!(output.contains("Connection: keep-alive\r\n"));
+        assert!(output.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_max_requests() {
+        let mut stream = serve(config(2));
+        stream.write_all(b"GET /test/1 HTTP/1.1\r\n\r\nGET /test/2 HTTP/1.1\r\n\r\nGET /test/3 HTTP/1.1\r\n\r\n").unwrap();
+
+        let output = read_all(stream);
+        assert!(output.contains("Keep-Alive: timeout=5, max=1\r\n"));
+        assert!(output.contains("hello 2"));
+        assert!(!output.contains("hello 3"));
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let stream = serve(ConnectionConfig {
+            idle_timeout: Duration::from_millis(100),
+            header_timeout: Duration::from_secs(5),
+            max_requests: 10,
+        });
+
+        assert_eq!(read_all(stream), "");
+    }
+
+    /// Sends the bytes one by one with a pause after each, then reads the response until the server closes the connection.
+    fn send_slowly(stream: TcpStream, bytes: &'static [u8], pause: Duration) -> Vec<u8> {
+        let mut writer = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            for byte in bytes {
+                if writer.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(pause);
+            }
+        });
+
+        let mut output = Vec::new();
+        // The server may reset the connection, as it closes it with the input still coming
+        let _ = (&stream).read_to_end(&mut output);
+        output
+    }
+
+    #[test]
+    fn test_slow_headers() {
+        let stream = serve(ConnectionConfig {
+            idle_timeout: Duration::from_secs(5),
+            header_timeout: Duration::from_millis(300),
+            max_requests: 10,
+        });
+
+        // Every byte comes well within the idle timeout, but the headers would take seconds
+        let started = Instant::now();
+        let request = b"GET /test/one HTTP/1.1\r\nX-Padding: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n\r\n";
+        let output = send_slowly(stream, request, Duration::from_millis(50));
+        assert!(output.is_empty(), "{}", String::from_utf8_lossy(&output));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_slow_body() {
+        let stream = serve(ConnectionConfig {
+            idle_timeout: Duration::from_millis(300),
+            header_timeout: Duration::from_millis(300),
+            max_requests: 10,
+        });
+
+        // The body takes longer than both timeouts, but it never pauses for long
+        let request = b"POST /test/upload HTTP/1.1\r\nContent-Length: 20\r\nConnection: close\r\n\r\naaaaaaaaaaaaaaaaaaaa";
+        let (head, body) = request.split_at(request.len() - 20);
+        let mut writer = stream.try_clone().unwrap();
+        writer.write_all(head).unwrap();
+
+        let output = String::from_utf8(send_slowly(stream, body, Duration::from_millis(50))).unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{}", output);
+        assert!(output.ends_with("received 20 bytes"));
+    }
+
+    #[test]
+    fn test_stalled_body() {
+        let mut stream = serve(ConnectionConfig {
+            idle_timeout: Duration::from_millis(100),
+            header_timeout: Duration::from_secs(5),
+            max_requests: 10,
+        });
+        stream.write_all(b"POST /test/upload HTTP/1.1\r\nContent-Length: 20\r\n\r\naaaa").unwrap();
+
+        let started = Instant::now();
+        assert_eq!(read_all(stream), "");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_shutdown_closes_connection() {
+        let shutdown = Shutdown::new();
+        shutdown.request();
+
+        let mut stream = serve_until(config(10), shutdown);
+        stream.write_all(b"GET /test/one HTTP/1.1\r\n\r\nGET /test/two HTTP/1.1\r\n\r\n").unwrap();
+
+        let output = read_all(stream);
+        assert!(output.contains("hello one"));
+        assert!(output.contains("Connection: close\r\n"));
+        assert!(!output.contains("hello two"));
+    }
+
+    #[test]
+    fn test_reject_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(b"GET /test/one HTTP/1.1\r\n\r\n").unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        reject_connection(stream, Duration::from_secs(3));
+
+        let output = read_all(client);
+        assert!(output.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(output.contains("Retry-After: 3\r\n"));
+        assert!(output.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_handler_panic() {
+        let mut stream = serve(config(10));
+        stream.write_all(b"GET /test/panic HTTP/1.1\r\n\r\nGET /test/one HTTP/1.1\r\n\r\n").unwrap();
+
+        let output = read_all(stream);
+        assert!(output.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(output.contains("Connection: close\r\n"));
+        assert!(!output.contains("hello"));
+    }
+
+    #[test]
+    fn test_malformed_request_closes_connection() {
+        let mut stream = serve(config(10));
+        stream.write_all(b"GET /test/one HTTP/1.1\r\nbroken\r\n\r\nGET /test/two HTTP/1.1\r\n\r\n").unwrap();
+
+        let output = read_all(stream);
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(output.contains("Connection: close\r\n"));
+        assert!(!output.contains("hello"));
+    }
+
+    fn panic_handler(_: &Request, _: &()) -> Response {
+        panic!("handler failed");
+    }
+
+    fn echo_handler(request: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text(format!("hello {}", request.param("name").unwrap()))
+    }
+
+    fn upload_handler(request: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text(format!("received {} bytes", request.body.len()))
+    }
+}
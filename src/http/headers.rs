@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use uncased::{Uncased, UncasedStr};
+
+/// A map of HTTP header fields.
+///
+/// Header names are compared case-insensitively, so `content-length`
+/// and `Content-Length` refer to the same field.
+#[derive(Clone, Debug, Default)]
+pub struct Headers {
+    map: HashMap<Uncased<'static>, String>,
+}
+
+impl Headers {
+    /// Creates an empty header map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the header field with the specified name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(UncasedStr::new(name)).map(String::as_str)
+    }
+
+    /// Checks if the header field with the specified name is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.map.contains_key(UncasedStr::new(name))
+    }
+
+    /// Sets the header field, replacing any previous value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.map.insert(Uncased::new(name.into()), value.into());
+    }
+
+    /// Adds a value to the header field.
+    ///
+    /// If the field is already present, the values are combined into a comma-separated list,
+    /// as allowed for repeated header fields by RFC 9110.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let value = value.into();
+        self.map
+            .entry(Uncased::new(name.into()))
+            .and_modify(|current| {
+                current.push_str(", ");
+                current.push_str(&value);
+            })
+            .or_insert_with(|| value.clone());
+    }
+
+    /// Returns an iterator over the header fields as `(name, value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive() {
+        let mut headers = Headers::new();
+        headers.insert("Content-Type", "application/json");
+
+        assert_eq!(headers.get("content-type"), Some("application/json"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("application/json"));
+        assert!(headers.contains("Content-type"));
+
+        headers.insert("content-type", "text/plain");
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("Content-Type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut headers = Headers::new();
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+
+        assert_eq!(headers.get("Accept"), Some("text/html, application/json"));
+    }
+}
@@ -10,6 +10,10 @@ pub enum Method {
     Get,
     /// The `POST` method.
     Post,
+    /// The `PUT` method.
+    Put,
+    /// The `PATCH` method.
+    Patch,
     /// The `DELETE` method.
     Delete,
 }
@@ -19,6 +23,8 @@ impl Method {
         match self {
             Method::Get => "GET",
             Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
             Method::Delete => "DELETE",
         }
     }
@@ -31,6 +37,8 @@ impl FromStr for Method {
         match s {
             m if uncased::eq(m, Method::Get.as_str()) => Ok(Method::Get),
             m if uncased::eq(m, Method::Post.as_str()) => Ok(Method::Post),
+            m if uncased::eq(m, Method::Put.as_str()) => Ok(Method::Put),
+            m if uncased::eq(m, Method::Patch.as_str()) => Ok(Method::Patch),
             m if uncased::eq(m, Method::Delete.as_str()) => Ok(Method::Delete),
             _ => Err(format!("Undefined method {}", s)),
         }
@@ -53,6 +61,8 @@ mod tests {
     fn test_as_str() {
         assert_eq!(Method::Get.as_str(), "GET");
         assert_eq!(Method::Post.as_str(), "POST");
+        assert_eq!(Method::Put.as_str(), "PUT");
+        assert_eq!(Method::Patch.as_str(), "PATCH");
         assert_eq!(Method::Delete.as_str(), "DELETE");
     }
 
@@ -66,6 +76,9 @@ mod tests {
         assert_eq!(Method::from_str("Post").unwrap(), Method::Post);
         assert_eq!(Method::from_str("POST").unwrap(), Method::Post);
 
+        assert_eq!(Method::from_str("put").unwrap(), Method::Put);
+        assert_eq!(Method::from_str("PATCH").unwrap(), Method::Patch);
+
         assert_eq!(Method::from_str("delete").unwrap(), Method::Delete);
         assert_eq!(Method::from_str("Delete").unwrap(), Method::Delete);
         assert_eq!(Method::from_str("DELETE").unwrap(), Method::Delete);
@@ -1,8 +1,14 @@
+pub mod connection;
+pub mod headers;
 pub mod method;
+pub mod request;
+pub mod response;
 pub mod router;
+pub mod status;
 
-pub const OK_RESPONSE: &str = "200 OK\r\nContent-Type: application/json";
-pub const NOT_FOUND: &str = "404 NOT FOUND";
-pub const BAD_REQUEST: &str = "400 BAD REQUEST";
-pub const INTERNAL_SERVER_ERROR: &str = "500 INTERNAL SERVER ERROR";
-
+pub use self::{
+    method::Method,
+    request::Request,
+    response::Response,
+    status::Status,
+};
@@ -0,0 +1,488 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Read},
+    str::{self, FromStr},
+};
+
+use serde::de::DeserializeOwned;
+
+use super::{headers::Headers, method::Method};
+
+/// Maximum length of the request line or of a single header line, in bytes.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Maximum number of header fields in a request.
+const MAX_HEADERS: usize = 100;
+
+/// Maximum size of a request body, in bytes.
+pub const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Errors that may occur while reading a request.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The peer closed the connection before sending any data.
+    ConnectionClosed,
+    /// The request does not follow the HTTP/1.1 message syntax.
+    Malformed(String),
+    /// The request body exceeds `MAX_BODY_SIZE`.
+    PayloadTooLarge,
+    /// The request method is not supported by the server.
+    UnsupportedMethod(String),
+    /// The underlying stream failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::ConnectionClosed => write!(f, "Connection closed"),
+            RequestError::Malformed(reason) => write!(f, "Malformed request: {}", reason),
+            RequestError::PayloadTooLarge => write!(f, "Request body exceeds {} bytes", MAX_BODY_SIZE),
+            RequestError::UnsupportedMethod(method) => write!(f, "Unsupported method {}", method),
+            RequestError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => RequestError::Malformed("Unexpected end of stream".to_string()),
+            _ => RequestError::Io(error),
+        }
+    }
+}
+
+fn malformed(reason: &str) -> RequestError {
+    RequestError::Malformed(reason.to_string())
+}
+
+
+/// An HTTP/1.1 request.
+#[derive(Debug)]
+pub struct Request {
+    pub method: Method,
+    /// The path part of the request target, without the query string.
+    pub path: String,
+    /// Decoded query string parameters.
+    pub query: HashMap<String, String>,
+    /// Named path parameters, filled by the router, e.g. `order_id` for `/orders/:order_id`.
+    pub params: HashMap<String, String>,
+    /// The protocol version, e.g. `HTTP/1.1`.
+    pub version: String,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a single request from the reader.
+    ///
+    /// Reads the request line and the header fields, then exactly `Content-Length` bytes of the body
+    /// or, if the request uses `Transfer-Encoding: chunked`, decodes the chunked body.
+    ///
+    /// Nothing past the end of the request is consumed, so several requests sent over
+    /// the same connection may be read one after another from the same reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - a buffered reader over the connection stream.
+    pub fn read_from<R: BufRead>(reader: &mut R) -> Result<Request, RequestError> {
+        Self::read_from_with(reader, |_| {})
+    }
+
+    /// Reads a single HTTP/1.x request like `read_from`, calling `before_body` once the header section has been read.
+    ///
+    /// Lets the caller read the body differently from the headers, e.g. with another timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - a buffered reader over the connection stream;
+    /// * `before_body` - called with the reader right before the body is read.
+    pub fn read_from_with<R: BufRead>(reader: &mut R, before_body: impl FnOnce(&mut R)) -> Result<Request, RequestError> {
+        // RFC 9112 asks servers to ignore empty lines received before the request line.
+        let request_line = loop {
+            match read_line(reader)? {
+                None => return Err(RequestError::ConnectionClosed),
+                Some(line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+
+        let mut parts = request_line.split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(m), Some(t), Some(v), None) if !m.is_empty() && !t.is_empty() => (m, t, v),
+            _ => return Err(malformed("Invalid request line")),
+        };
+        if !version.starts_with("HTTP/1.") {
+            return Err(malformed("Unsupported protocol version"));
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let path = percent_decode(path).ok_or_else(|| malformed("Invalid request target"))?;
+        let query = parse_query(query).ok_or_else(|| malformed("Invalid query string"))?;
+
+        let headers = read_headers(reader)?;
+        before_body(reader);
+        let body = read_body(reader, &headers)?;
+
+        // The message is read in full even for an unsupported method,
+        // so that the connection stays in a consistent state.
+        let method = Method::from_str(method)
+            .map_err(|_| RequestError::UnsupportedMethod(method.to_string()))?;
+
+        Ok(Request {
+            method,
+            path,
+            query,
+            params: HashMap::new(),
+            version: version.to_string(),
+            headers,
+            body,
+        })
+    }
+
+    /// Creates a request without headers and body.
+    ///
+    /// The query string, if any, is split from the path like in a received request.
+    #[cfg(test)]
+    pub fn new(method: Method, target: &str) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        Request {
+            method,
+            path: path.to_string(),
+            query: parse_query(query).unwrap(),
+            params: HashMap::new(),
+            version: "HTTP/1.1".to_string(),
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns the named path parameter.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Returns the named path parameter parsed into `T`.
+    ///
+    /// Returns `None` if the parameter is missing or can not be parsed.
+    pub fn param_as<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.param(name)?.parse().ok()
+    }
+
+    /// Returns the query string parameter.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+
+    /// Returns the value of the header field with the specified name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Checks if the client wants to keep the connection open after this request.
+    ///
+    /// HTTP/1.1 connections are persistent unless the client sends `Connection: close`,
+    /// HTTP/1.0 connections are closed unless the client sends `Connection: keep-alive`.
+    pub fn keep_alive(&self) -> bool {
+        let has_option = |option: &str| {
+            self.header("Connection")
+                .map(|value| value.split(',').any(|token| token.trim().eq_ignore_ascii_case(option)))
+                .unwrap_or(false)
+        };
+
+        match self.version.as_str() {
+            "HTTP/1.0" => has_option("keep-alive"),
+            _ => !has_option("close"),
+        }
+    }
+
+    /// Returns the body as a string, replacing any invalid UTF-8 sequences.
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Deserializes the JSON body.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+
+/// Parses a query string like `a=1&b=two` into a map.
+///
+/// Returns `None` if any of the parts are not correctly percent-encoded.
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((
+                percent_decode(&key.replace('+', " "))?,
+                percent_decode(&value.replace('+', " "))?,
+            ))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escape sequences.
+///
+/// Returns `None` for an invalid escape sequence or if the result is not valid UTF-8.
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // `from_str_radix` takes a sign too, so `%+1` would be decoded otherwise
+            let hex = bytes.get(i + 1..i + 3).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            decoded.push(u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+
+/// Reads a line terminated by CRLF (or a bare LF) and returns it without the terminator.
+///
+/// Returns `None` if the stream ended before any byte was read.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, RequestError> {
+    let mut line = Vec::new();
+    let size = reader
+        .by_ref()
+        .take(MAX_LINE_LENGTH as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+
+    if size == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        if size > MAX_LINE_LENGTH {
+            return Err(malformed("Line is too long"));
+        }
+        return Err(malformed("Unexpected end of stream"));
+    }
+
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| malformed("Line is not valid UTF-8"))
+}
+
+/// Reads header fields up to and including the empty line that ends the header section.
+fn read_headers<R: BufRead>(reader: &mut R) -> Result<Headers, RequestError> {
+    let mut headers = Headers::new();
+    let mut lines = 0;
+
+    loop {
+        let line = read_line(reader)?.ok_or_else(|| malformed("Unexpected end of stream"))?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        // The lines are counted rather than the headers, as the repeated names are merged into one header
+        lines += 1;
+        if lines > MAX_HEADERS {
+            return Err(malformed("Too many header fields"));
+        }
+
+        let (name, value) = line.split_once(':').ok_or_else(|| malformed("Invalid header field"))?;
+        if name.is_empty() || name.ends_with(|c: char| c.is_whitespace()) {
+            return Err(malformed("Invalid header field name"));
+        }
+        headers.append(name, value.trim());
+    }
+}
+
+fn read_body<R: BufRead>(reader: &mut R, headers: &Headers) -> Result<Vec<u8>, RequestError> {
+    if let Some(encoding) = headers.get("Transfer-Encoding") {
+        // Chunked must be the final encoding, and we don't support any other.
+        if !encoding.eq_ignore_ascii_case("chunked") {
+            return Err(malformed("Unsupported transfer encoding"));
+        }
+        return read_chunked_body(reader);
+    }
+
+    let length = match headers.get("Content-Length") {
+        None => return Ok(Vec::new()),
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| malformed("Invalid Content-Length"))?,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(RequestError::PayloadTooLarge);
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`.
+///
+/// Chunk extensions and trailer fields are read and discarded.
+fn read_chunked_body<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, RequestError> {
+    let mut body = Vec::new();
+
+    loop {
+        let line = read_line(reader)?.ok_or_else(|| malformed("Unexpected end of stream"))?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        if !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(malformed("Invalid chunk size"));
+        }
+        let size = usize::from_str_radix(size, 16).map_err(|_| malformed("Invalid chunk size"))?;
+
+        if size == 0 {
+            break;
+        }
+        // The size comes from the client, so it isn't added to the length, which could overflow
+        if size > MAX_BODY_SIZE - body.len() {
+            return Err(RequestError::PayloadTooLarge);
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+
+        match read_line(reader)? {
+            Some(line) if line.is_empty() => {}
+            _ => return Err(malformed("Missing chunk terminator")),
+        }
+    }
+
+    // Skip the trailer section
+    read_headers(reader)?;
+
+    Ok(body)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn parse(raw: &[u8]) -> Result<Request, RequestError> {
+        Request::read_from(&mut BufReader::new(raw))
+    }
+
+    #[test]
+    fn test_request_line() {
+        let request = parse(b"GET /v1/orders/?limit=10 HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.path, "/v1/orders/");
+        assert_eq!(request.query_param("limit"), Some("10"));
+        assert_eq!(request.version, "HTTP/1.1");
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn test_query() {
+        let request = parse(b"GET /menu?name=Fish+%26+Chips&empty=&flag&x=%E2%9C%93 HTTP/1.1\r\n\r\n").unwrap();
+
+        assert_eq!(request.query_param("name"), Some("Fish & Chips"));
+        assert_eq!(request.query_param("empty"), Some(""));
+        assert_eq!(request.query_param("flag"), Some(""));
+        assert_eq!(request.query_param("x"), Some("\u{2713}"));
+        assert_eq!(request.query_param("missing"), None);
+
+        assert!(matches!(parse(b"GET /menu?name=%zz HTTP/1.1\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET /menu?name=%+1 HTTP/1.1\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET /menu%-1 HTTP/1.1\r\n\r\n"), Err(RequestError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_headers() {
+        let request = parse(b"GET / HTTP/1.1\r\nX-Test:  first \r\nx-test: second\r\nACCEPT: */*\r\n\r\n").unwrap();
+
+        assert_eq!(request.header("X-Test"), Some("first, second"));
+        assert_eq!(request.header("Accept"), Some("*/*"));
+
+        let repeated = |count| format!("GET / HTTP/1.1\r\n{}\r\n", "X-Test: a\r\n".repeat(count));
+        assert!(parse(repeated(MAX_HEADERS).as_bytes()).is_ok());
+        assert!(matches!(parse(repeated(MAX_HEADERS + 1).as_bytes()), Err(RequestError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        assert!(parse(b"GET / HTTP/1.1\r\n\r\n").unwrap().keep_alive());
+        assert!(parse(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n").unwrap().keep_alive());
+        assert!(!parse(b"GET / HTTP/1.1\r\nConnection: Upgrade, Close\r\n\r\n").unwrap().keep_alive());
+
+        assert!(!parse(b"GET / HTTP/1.0\r\n\r\n").unwrap().keep_alive());
+        assert!(parse(b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").unwrap().keep_alive());
+    }
+
+    #[test]
+    fn test_content_length_body() {
+        let body = format!("{{\"table_id\": 1, \"menu_id\": [{}]}}", vec!["1"; 2000].join(", "));
+        let raw = format!("POST /v1/orders HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+
+        let request = parse(raw.as_bytes()).unwrap();
+        assert_eq!(request.body_str(), body);
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n6;ext=1\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\nX-Trailer: 1\r\n\r\n";
+
+        let request = parse(raw).unwrap();
+        assert_eq!(request.body_str(), "Wikipedia in \r\n\r\nchunks.");
+
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n+4\r\nWiki\r\n0\r\n\r\n";
+        assert!(matches!(parse(raw), Err(RequestError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_pipelined_requests() {
+        let raw = b"POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = BufReader::new(&raw[..]);
+
+        let first = Request::read_from(&mut reader).unwrap();
+        assert_eq!(first.path, "/a");
+        assert_eq!(first.body_str(), "hello");
+
+        let second = Request::read_from(&mut reader).unwrap();
+        assert_eq!(second.path, "/b");
+
+        assert!(matches!(Request::read_from(&mut reader), Err(RequestError::ConnectionClosed)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(matches!(parse(b"GET\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET / FTP/1.0\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET / HTTP/1.1\r\nNo colon\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET / HTTP/1.1\r\nContent-Length: abc\r\n\r\n"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort"), Err(RequestError::Malformed(_))));
+        assert!(matches!(parse(b"GET / HTTP/1.1\r\nHost: localhost"), Err(RequestError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_unsupported_method() {
+        assert!(matches!(parse(b"BREW /pot HTTP/1.1\r\n\r\n"), Err(RequestError::UnsupportedMethod(_))));
+    }
+
+    #[test]
+    fn test_payload_too_large() {
+        let raw = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+        assert!(matches!(parse(raw.as_bytes()), Err(RequestError::PayloadTooLarge)));
+
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\n";
+        assert!(matches!(parse(raw), Err(RequestError::PayloadTooLarge)));
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use super::{headers::Headers, status::Status};
+
+/// An HTTP/1.1 response.
+///
+/// Built by chaining calls, e.g. `Response::new(Status::Ok).json(&items)`.
+#[derive(Debug)]
+pub struct Response {
+    pub status: Status,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates an empty response with the specified status.
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Sets a header field, replacing any previous value.
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+
+        self
+    }
+
+    /// Sets a plain text body.
+    pub fn text(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into().into_bytes();
+
+        self.header("Content-Type", "text/plain; charset=utf-8")
+    }
+
+    /// Sets a JSON body serialized from the value.
+    ///
+    /// If the value can not be serialized, the response turns into `500 Internal Server Error`.
+    pub fn json<T: Serialize>(mut self, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => {
+                self.body = body;
+                self.header("Content-Type", "application/json")
+            },
+            Err(error) => Response::new(Status::InternalServerError).text(error.to_string()),
+        }
+    }
+
+    /// Serializes the response into the writer.
+    ///
+    /// The `Content-Length` header is always set from the actual body size,
+    /// except for `204 No Content` which has neither the header nor a body.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let has_body = self.status != Status::NoContent;
+
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in self.headers.iter() {
+            if !name.eq_ignore_ascii_case("Content-Length") {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        if has_body {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        if has_body {
+            writer.write_all(&self.body)?;
+        }
+        writer.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_to() {
+        let mut output = Vec::new();
+        Response::new(Status::NotFound)
+            .text("Not here")
+            .write_to(&mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(output.contains("\r\nContent-Type: text/plain; charset=utf-8\r\n"));
+        assert!(output.ends_with("\r\nContent-Length: 8\r\n\r\nNot here"));
+    }
+
+    #[test]
+    fn test_no_content() {
+        let mut output = Vec::new();
+        Response::new(Status::NoContent)
+            .write_to(&mut output)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "HTTP/1.1 204 No Content\r\n\r\n");
+    }
+
+    #[test]
+    fn test_json() {
+        let response = Response::new(Status::Ok).json(&vec![1, 2, 3]);
+
+        assert_eq!(response.status, Status::Ok);
+        assert_eq!(response.headers.get("content-type"), Some("application/json"));
+        assert_eq!(response.body, b"[1,2,3]");
+    }
+}
@@ -1,16 +1,24 @@
-use std::{
-    str::FromStr,
-    collections::HashMap,
-};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
 
-use super::method::Method;
+use super::{method::Method, request::Request, response::Response};
 
 const DYN_PATH_KEY: &str = ":dyn";
 
-type Handler = fn(&str, &Vec<&str>) -> (String, String);
-pub type Handlers<'a> = HashMap<Method, Handler>;
-pub type Routes<'a> = HashMap<&'a str, Router<'a>>;
+/// A request handler. `S` is the application state shared by all handlers, e.g. the storage.
+pub type Handler<S> = fn(&Request, &S) -> Response;
+pub type Handlers<'a, S> = HashMap<Method, Endpoint<'a, S>>;
+pub type Routes<'a, S> = HashMap<&'a str, Router<'a, S>>;
+
+/// A handler registered for an endpoint along with the names of the endpoint's dynamic parts.
+///
+/// Different endpoints may share the same dynamic path part under different names
+/// (`/:table_id_list/orders` and `/:table_id/orders/:order_id`),
+/// so the names are stored per handler, in the order they appear in the endpoint.
+pub struct Endpoint<'a, S> {
+    handler: Handler<S>,
+    param_names: Vec<&'a str>,
+}
 
 fn no_handler_error(method: &Method, endpoint: &str) -> String {
     format!("No handler for {} {} found.", method, endpoint)
@@ -21,14 +29,16 @@ fn undefined_endpoint_error(endpoint: &str) -> String {
 }
 
 /// The struct that defines endpoints and their handlers.
-pub struct Router<'a> {
+///
+/// `S` is the type of the state passed to the handlers along with the request.
+pub struct Router<'a, S> {
     base: Option<&'a str>,
-    pub handlers: Handlers<'a>,
-    pub routes: Routes<'a>,
+    pub handlers: Handlers<'a, S>,
+    pub routes: Routes<'a, S>,
 }
 
 
-impl<'a> Router<'a> {
+impl<'a, S> Router<'a, S> {
     /// Creates a new `Router`.
     ///
     /// # Arguments
@@ -75,7 +85,7 @@ impl<'a> Router<'a> {
     /// # Arguments
     /// 
     /// * `other_router` - a router which routes should be merged to current router.
-    pub fn merge_from(mut self, mut other_router: Router<'a>) -> Self {
+    pub fn merge_from(mut self, mut other_router: Router<'a, S>) -> Self {
         match self.base {
             None => self.routes.extend(other_router.routes.drain()),
             Some(b) => self.routes.entry(b).or_insert(Router::new("")).routes.extend(other_router.routes.drain()),
@@ -84,49 +94,51 @@ impl<'a> Router<'a> {
         self
     }
 
-    /// Returns a handler for specified endpoint and method.
+    /// Returns a handler for specified endpoint and method along with the named endpoint parameters.
     /// 
     /// # Arguments
     /// 
     /// * `endpoint` - an endpoint path;
-    /// * `method` - a string representation of the request method.
-    pub fn get_handler(&self, endpoint: &'a str, method: &str) -> Result<(&Handler, Vec<&str>), String> {
-        let method = Method::from_str(method)?;
-
-        let handler = self.handler(Self::get_path(endpoint), 0, method, Vec::new(), &endpoint);
-        return match handler {
-            Ok((h, p)) => Ok((h, p)),
-            Err(error) => Err(error),
-        }
+    /// * `method` - the request method.
+    pub fn get_handler(&self, endpoint: &str, method: Method) -> Result<(Handler<S>, HashMap<String, String>), String> {
+        let (endpoint_handler, values) = self.handler(&get_path(endpoint), 0, method, Vec::new(), endpoint)?;
+
+        let params = endpoint_handler.param_names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Ok((endpoint_handler.handler, params))
     }
 
     /// A recursive function that iterates all the endpoint's paths of the router and returns handler for the specified method
-    fn handler(&self, path: Vec<&'a str>, depth: usize, method: Method, mut params: Vec<&'a str>, init_endpoint: &'a str) -> Result<(&Handler, Vec<&str>), String> {
+    fn handler<'p>(&self, path: &[&'p str], depth: usize, method: Method, mut params: Vec<&'p str>, init_endpoint: &str) -> Result<(&Endpoint<'a, S>, Vec<&'p str>), String> {
 
         // If we reached a path's end (there is no next element in path),
         // we should store a handler in current router's handlers
         if depth == path.len() {
             return match self.handlers.get(&method) {
-                None => Err(no_handler_error(&method, &init_endpoint)),
+                None => Err(no_handler_error(&method, init_endpoint)),
                 Some(hh) => Ok((hh, params)),
             }
         }
 
         // We try to get a router by the key where the key is an endpoint's path.
-        if self.routes.contains_key(path[depth]) {
-            return self.routes.get(path[depth]).unwrap().handler(path, depth + 1, method, params, &init_endpoint);
+        if let Some(router) = self.routes.get(path[depth]) {
+            return router.handler(path, depth + 1, method, params, init_endpoint);
         }
 
         // If there no such key in routes, we try to check if there is a DYN_PATH_KEY key.
         // If it is, we suppose that this is a variable parameter and store it in params.
         // And then we proceed with DYN_PATH_KEY's router.
-        if self.routes.contains_key(DYN_PATH_KEY) {
+        if let Some(router) = self.routes.get(DYN_PATH_KEY) {
             params.push(path[depth]);
-            return self.routes.get(DYN_PATH_KEY).unwrap().handler(path, depth + 1, method, params, &init_endpoint);
+            return router.handler(path, depth + 1, method, params, init_endpoint);
         }
 
         // If no related key found this is a 404 error
-        return Err(undefined_endpoint_error(&init_endpoint));
+        Err(undefined_endpoint_error(init_endpoint))
     }
 
     /// Add GET method handler for a specified endpoint.
@@ -139,8 +151,8 @@ impl<'a> Router<'a> {
     /// # Panics
     ///
     /// Panics if the endpoint is incorrect.
-    pub fn get(mut self, endpoint: &'a str, handler: Handler) -> Self {
-        self.add_route(Self::get_path(endpoint), 0, Method::Get, handler);
+    pub fn get(mut self, endpoint: &'a str, handler: Handler<S>) -> Self {
+        self.add_route(get_path(endpoint), 0, Method::Get, Endpoint::new(endpoint, handler));
 
         self
     }
@@ -155,8 +167,40 @@ impl<'a> Router<'a> {
     /// # Panics
     ///
     /// Panics if the endpoint is incorrect.
-    pub fn post(mut self, endpoint: &'a str, handler: Handler) -> Self {
-        self.add_route(Self::get_path(endpoint), 0, Method::Post, handler);
+    pub fn post(mut self, endpoint: &'a str, handler: Handler<S>) -> Self {
+        self.add_route(get_path(endpoint), 0, Method::Post, Endpoint::new(endpoint, handler));
+
+        self
+    }
+
+    /// Add PUT method handler for a specified endpoint.
+    ///
+    /// # Arguments
+    /// 
+    /// * `endpoint` - a path relative to base;
+    /// * `handler` - a pointer to function that handles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint is incorrect.
+    pub fn put(mut self, endpoint: &'a str, handler: Handler<S>) -> Self {
+        self.add_route(get_path(endpoint), 0, Method::Put, Endpoint::new(endpoint, handler));
+
+        self
+    }
+
+    /// Add PATCH method handler for a specified endpoint.
+    ///
+    /// # Arguments
+    /// 
+    /// * `endpoint` - a path relative to base;
+    /// * `handler` - a pointer to function that handles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint is incorrect.
+    pub fn patch(mut self, endpoint: &'a str, handler: Handler<S>) -> Self {
+        self.add_route(get_path(endpoint), 0, Method::Patch, Endpoint::new(endpoint, handler));
 
         self
     }
@@ -171,14 +215,14 @@ impl<'a> Router<'a> {
     /// # Panics
     ///
     /// Panics if the endpoint is incorrect.
-    pub fn delete(mut self, endpoint: &'a str, handler: Handler) -> Self {
-        self.add_route(Self::get_path(endpoint), 0, Method::Delete, handler);
+    pub fn delete(mut self, endpoint: &'a str, handler: Handler<S>) -> Self {
+        self.add_route(get_path(endpoint), 0, Method::Delete, Endpoint::new(endpoint, handler));
 
         self
     }
 
     /// A recursive function that fills a router with all the endpoint's paths recursively
-    fn add_route(&mut self, path: Vec<&'a str>, depth: usize, method: Method, handler: Handler) {
+    fn add_route(&mut self, path: Vec<&'a str>, depth: usize, method: Method, endpoint: Endpoint<'a, S>) {
         // If the current router has a base option, we should store a newly added routes under it base's key router.
         let mut router = match self.base {
             None => self,
@@ -190,7 +234,7 @@ impl<'a> Router<'a> {
         if depth == path.len() {
             router.handlers
                 .entry(method)
-                .or_insert(handler);
+                .or_insert(endpoint);
             return;
         }
 
@@ -198,28 +242,43 @@ impl<'a> Router<'a> {
         // If the key starts with `:`this means that this part is dynamicly generated,
         // i.e. it represents some variable.
         // We store this route in a specific key `:dyn`.
-        let key = if path[depth].starts_with(":") { DYN_PATH_KEY } else { path[depth] };
+        let key = if path[depth].starts_with(':') { DYN_PATH_KEY } else { path[depth] };
         router = router.routes
             .entry(key)
             .or_insert(Router::new(""));
 
         // Proceed to the next part of the path storing it under current router
-        router.add_route(path, depth + 1, method, handler);
+        router.add_route(path, depth + 1, method, endpoint);
     }
 
-    fn get_path(endpoint: &str) -> Vec<&str> {
-        endpoint.split("/").filter(| x | !x.is_empty()).collect()
+}
+
+fn get_path(endpoint: &str) -> Vec<&str> {
+    endpoint.split('/').filter(| x | !x.is_empty()).collect()
+}
+
+
+impl<'a, S> Endpoint<'a, S> {
+    fn new(endpoint: &'a str, handler: Handler<S>) -> Self {
+        Self {
+            handler,
+            // `:order_id` is stored as `order_id`
+            param_names: get_path(endpoint)
+                .into_iter()
+                .filter_map(|part| part.strip_prefix(':'))
+                .collect(),
+        }
     }
 }
 
 
 /// Formats the Router instance for checking purpoises
-impl<'a> Display for Router<'a> {
+impl<'a, S> Display for Router<'a, S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        fn print_routes(routes: &Routes, f: &mut Formatter<'_>, depth: usize) -> fmt::Result {
+        fn print_routes<S>(routes: &Routes<S>, f: &mut Formatter<'_>, depth: usize) -> fmt::Result {
             for (key, router) in routes {
                 writeln!(f, "{:indent$}/{}", "", key, indent = depth * 2)?;
-                for (method, _) in &router.handlers {
+                for method in router.handlers.keys() {
                     writeln!(f, "{:indent$}-{}", "", method, indent = (depth + 1) * 2)?;
                 }
                 print_routes(&router.routes, f, depth + 1)?;
@@ -236,16 +295,17 @@ impl<'a> Display for Router<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::status::Status;
 
     #[test]
     #[should_panic]
     fn test_base_start_slash() {
-        Router::new("test");
+        Router::<()>::new("test");
     }
 
     #[test]
     fn test_empty_base() {
-        let router = Router::new("");
+        let router = Router::<()>::new("");
         assert_eq!(router.base, None);
     }
 
@@ -262,38 +322,52 @@ mod tests {
         assert_eq!(router.base, Some("test"));
 
         // testing existing endpoints
-        let (mut handler, mut params) = router.get_handler("/test/", "post").unwrap();
-        assert_eq!(handler("", &params), ("post status".to_string(), "post message".to_string()));
-
-        (handler, params) = router.get_handler("/test/post/post/", "post").unwrap();
-        assert_eq!(handler("", &params), ("post status".to_string(), "post message".to_string()));
-
-        (handler, params) = router.get_handler("/test/get/", "get").unwrap();
-        assert_eq!(handler("", &params), ("get status".to_string(), "get message: ".to_string()));
-
-        (handler, params) = router.get_handler("/test/get/11", "get").unwrap();
-        assert_eq!(handler("", &params), ("get status".to_string(), "get message: 11".to_string()));
-
-        (handler, params) = router.get_handler("/test/get/22/key/33", "get").unwrap();
-        assert_eq!(handler("", &params), ("get status".to_string(), "get message: 22,33".to_string()));
+        assert_eq!(call(&router, "/test/", Method::Post), "post message");
+        assert_eq!(call(&router, "/test/post/post/", Method::Post), "post message");
+        assert_eq!(call(&router, "/test/get/", Method::Get), "get message: ");
+        assert_eq!(call(&router, "/test/get/11", Method::Get), "get message: 11");
+        assert_eq!(call(&router, "/test/get/22/key/33", Method::Get), "get message: 22,33");
 
 
         // testing not found endpoints
-        let mut err = router.get_handler("/test/", "delete").unwrap_err();
+        let mut err = router.get_handler("/test/", Method::Delete).unwrap_err();
         assert_eq!(err, no_handler_error(&Method::Delete, "/test/"));
 
-        err = router.get_handler("/test/", "get").unwrap_err();
+        err = router.get_handler("/test/", Method::Get).unwrap_err();
         assert_eq!(err, no_handler_error(&Method::Get, "/test/"));
 
-        err = router.get_handler("/delete/45/post/12", "post").unwrap_err();
+        err = router.get_handler("/delete/45/post/12", Method::Post).unwrap_err();
         assert_eq!(err, undefined_endpoint_error("/delete/45/post/12"));
     }
+
+    #[test]
+    fn test_put_patch() {
+        let router = Router::new("/items")
+            .put("/:key1", get_handler)
+            .patch("/:key1", post_handler)
+        ;
+
+        assert_eq!(call(&router, "/items/7", Method::Put), "get message: 7");
+        assert_eq!(call(&router, "/items/7", Method::Patch), "post message");
+        assert!(router.get_handler("/items/7", Method::Get).is_err());
+    }
+
+    #[test]
+    fn test_named_params() {
+        let router = Router::new("/tables")
+            .get("/:table_id_list/orders", list_handler)
+            .get("/:table_id/orders/:order_id", item_handler)
+        ;
+
+        assert_eq!(call(&router, "/tables/1,2,3/orders", Method::Get), "tables 1,2,3");
+        assert_eq!(call(&router, "/tables/4/orders/5", Method::Get), "table 4, order 5");
+    }
  
     #[test]
     fn test_merge() {
         let router1 = Router::new("/test1")
             .post("/", post_handler)
-            .get("/get/:key", get_handler)
+            .get("/get/:key1", get_handler)
         ;
 
         let router2 = Router::new("/test2")
@@ -302,52 +376,65 @@ mod tests {
         ;
 
         let router_merged = Router::new("/merged")
-            .get("/get/:key", get_handler)
+            .get("/get/:key1", get_handler)
             .merge_from(router1)
             .merge_from(router2)
         ;
 
         // testing existing endpoints
-        let (mut handler, mut params) = router_merged.get_handler("/merged/test1/", "post").unwrap();
-        assert_eq!(handler("", &params), ("post status".to_string(), "post message".to_string()));
-
-        (handler, params) = router_merged.get_handler("/merged/test1/get/2023", "get").unwrap();
-        assert_eq!(handler("", &params), ("get status".to_string(), "get message: 2023".to_string()));
-
-        (handler, params) = router_merged.get_handler("/merged/test2", "post").unwrap();
-        assert_eq!(handler("", &params), ("post status".to_string(), "post message".to_string()));
-
-        (handler, params) = router_merged.get_handler("/merged/test2/delete", "delete").unwrap();
-        assert_eq!(handler("", &params), ("delete status".to_string(), "delete message".to_string()));
-
-        (handler, params) = router_merged.get_handler("/merged/get/2024", "get").unwrap();
-        assert_eq!(handler("", &params), ("get status".to_string(), "get message: 2024".to_string()));
+        assert_eq!(call(&router_merged, "/merged/test1/", Method::Post), "post message");
+        assert_eq!(call(&router_merged, "/merged/test1/get/2023", Method::Get), "get message: 2023");
+        assert_eq!(call(&router_merged, "/merged/test2", Method::Post), "post message");
+        assert_eq!(call(&router_merged, "/merged/test2/delete", Method::Delete), "delete message");
+        assert_eq!(call(&router_merged, "/merged/get/2024", Method::Get), "get message: 2024");
 
 
         // testing not found endpoints
-        let mut err = router_merged.get_handler("/test1", "post").unwrap_err();
+        let mut err = router_merged.get_handler("/test1", Method::Post).unwrap_err();
         assert_eq!(err, undefined_endpoint_error("/test1"));
 
-        err = router_merged.get_handler("/test2", "post").unwrap_err();
+        err = router_merged.get_handler("/test2", Method::Post).unwrap_err();
         assert_eq!(err, undefined_endpoint_error("/test2"));
 
-        err = router_merged.get_handler("/test2/delete", "get").unwrap_err();
+        err = router_merged.get_handler("/test2/delete", Method::Get).unwrap_err();
         assert_eq!(err, undefined_endpoint_error("/test2/delete"));
 
-        err = router_merged.get_handler("/merged", "post").unwrap_err();
+        err = router_merged.get_handler("/merged", Method::Post).unwrap_err();
         assert_eq!(err, no_handler_error(&Method::Post, "/merged"));
     }
 
 
-    fn get_handler(_: &str, params: &Vec<&str>) -> (String, String) {
-        ("get status".to_string(), format!("get message: {}", params.join(",")))
+    /// Routes a request and returns the response body.
+    fn call(router: &Router<()>, endpoint: &str, method: Method) -> String {
+        let (handler, params) = router.get_handler(endpoint, method).unwrap();
+        let mut request = Request::new(method, endpoint);
+        request.params = params;
+
+        String::from_utf8(handler(&request, &()).body).unwrap()
+    }
+
+    fn get_handler(request: &Request, _: &()) -> Response {
+        let params: Vec<&str> = ["key1", "key2"].iter().filter_map(|key| request.param(key)).collect();
+        Response::new(Status::Ok).text(format!("get message: {}", params.join(",")))
+    }
+
+    fn post_handler(_: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text("post message")
+    }
+
+    fn delete_handler(_: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text("delete message")
     }
 
-    fn post_handler(_: &str, _: &Vec<&str>) -> (String, String) {
-        ("post status".to_string(), "post message".to_string())
+    fn list_handler(request: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text(format!("tables {}", request.param("table_id_list").unwrap()))
     }
 
-    fn delete_handler(_: &str, _: &Vec<&str>) -> (String, String) {
-        ("delete status".to_string(), "delete message".to_string())
+    fn item_handler(request: &Request, _: &()) -> Response {
+        Response::new(Status::Ok).text(format!(
+            "table {}, order {}",
+            request.param("table_id").unwrap(),
+            request.param("order_id").unwrap(),
+        ))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+/// Enumerates the http response status codes used by the application.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// `200 OK`
+    Ok,
+    /// `201 Created`
+    Created,
+    /// `204 No Content`
+    NoContent,
+    /// `400 Bad Request`
+    BadRequest,
+    /// `402 Payment Required`
+    PaymentRequired,
+    /// `404 Not Found`
+    NotFound,
+    /// `409 Conflict`
+    Conflict,
+    /// `413 Payload Too Large`
+    PayloadTooLarge,
+    /// `422 Unprocessable Entity`
+    UnprocessableEntity,
+    /// `500 Internal Server Error`
+    InternalServerError,
+    /// `501 Not Implemented`
+    NotImplemented,
+    /// `503 Service Unavailable`
+    ServiceUnavailable,
+    /// `504 Gateway Timeout`
+    GatewayTimeout,
+}
+
+impl Status {
+    /// Returns the numeric status code.
+    pub fn code(self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::Created => 201,
+            Status::NoContent => 204,
+            Status::BadRequest => 400,
+            Status::PaymentRequired => 402,
+            Status::NotFound => 404,
+            Status::Conflict => 409,
+            Status::PayloadTooLarge => 413,
+            Status::UnprocessableEntity => 422,
+            Status::InternalServerError => 500,
+            Status::NotImplemented => 501,
+            Status::ServiceUnavailable => 503,
+            Status::GatewayTimeout => 504,
+        }
+    }
+
+    /// Returns the reason phrase for the status code.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Created => "Created",
+            Status::NoContent => "No Content",
+            Status::BadRequest => "Bad Request",
+            Status::PaymentRequired => "Payment Required",
+            Status::NotFound => "Not Found",
+            Status::Conflict => "Conflict",
+            Status::PayloadTooLarge => "Payload Too Large",
+            Status::UnprocessableEntity => "Unprocessable Entity",
+            Status::InternalServerError => "Internal Server Error",
+            Status::NotImplemented => "Not Implemented",
+            Status::ServiceUnavailable => "Service Unavailable",
+            Status::GatewayTimeout => "Gateway Timeout",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Status::Ok.to_string(), "200 OK");
+        assert_eq!(Status::NotFound.to_string(), "404 Not Found");
+        assert_eq!(Status::InternalServerError.to_string(), "500 Internal Server Error");
+    }
+}
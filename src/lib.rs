@@ -0,0 +1,20 @@
+//! Restaurant API.
+//!
+//! A restaurant application which accepts menu items from various serving staff in the restaurant.
+
+#[macro_use]
+extern crate serde_derive;
+
+pub mod thread_pool;
+pub mod http;
+pub mod routes;
+pub mod database;
+pub mod shutdown;
+pub mod clock;
+pub mod config;
+pub mod error;
+pub mod state;
+pub mod storage;
+pub mod scheduler;
+pub mod billing;
+pub mod payments;
@@ -1,113 +1,253 @@
 use std::{
-    io::prelude::*,
-    net::{TcpListener, TcpStream},
+    env,
+    io,
+    net::TcpListener,
+    process,
     sync::Arc,
-    env, usize,
     time::Duration,
     thread,
-    str
 };
 use rand::Rng;
 
-#[macro_use]
-extern crate serde_derive;
+/// How long a client should wait before retrying when the server is too busy.
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// How often the listener checks for new connections and for a shutdown request.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+use restaurant_api::{
+    config::{self, Config, StorageBackend},
+    database::{self, migrations, models::{menu::MenuOutput, order::{OrderOutput, TableOrders}}},
+    http::connection::{handle_connection, reject_connection, ConnectionConfig},
+    routes,
+    shutdown::Shutdown,
+    state::AppState,
+    storage::{MemoryStorage, PostgresStorage},
+    thread_pool::{ExecuteError, ThreadPool},
+};
+use serde_json::json;
 
-const SERVER_URI: &str = env!("SERVER_URI");
-const SERVER_PORT: &str = env!("SERVER_PORT");
-const THREADS_NUMBER: &str = env!("THREADS_NUMBER");
 
-mod thread_pool;
-mod http;
-mod routes;
-mod database;
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", config::USAGE);
+        return;
+    }
 
-use http::router::Router;
-use serde_json::json;
-use thread_pool::*;
+    // `migrate [up | down <VERSION> | status]` runs the migrations instead of the server
+    let migrate_command: Option<Vec<String>> = if args.first().map(String::as_str) == Some("migrate") {
+        let length = args.iter().skip(1).take_while(|arg| !arg.starts_with("--")).count();
+        Some(args.drain(..=length).skip(1).collect())
+    } else {
+        None
+    };
 
-use crate::database::models::{menu::MenuOutput, order::OrderOutput};
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Run with --help to see the available options.");
+            process::exit(2);
+        },
+    };
+
+    if let Some(command) = migrate_command {
+        if let Err(e) = run_migrate_command(&config, &command) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
+    let state = match create_state(&config) {
+        Ok(state) => Arc::new(state),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        },
+    };
 
-fn main() {
-    // Warm up database
-    if let Err(e) = database::set_database() {
+    let shutdown = Shutdown::new();
+    if let Err(e) = shutdown.listen_for_signals() {
         println!("{}", e);
         return;
     }
 
-    let listener = TcpListener::bind(get_server_address()).unwrap();
-    println!("Server has started on port {}", SERVER_PORT);
+    let server_address = config.server.address();
+    let listener = TcpListener::bind(&server_address).unwrap();
+    // The listener doesn't block, so the loop below can notice a shutdown request between connections.
+    listener.set_nonblocking(true).unwrap();
+    println!("Server has started on port {}", config.server.port);
 
     let router = Arc::new(routes::create("/v1"));
 
     println!("{}", router); // temporarily for testing
 
-    let pool = ThreadPool::new(THREADS_NUMBER.parse::<usize>().unwrap_or(1) + 10);
+    let pool = ThreadPool::new(config.server.threads, config.server.queue_capacity);
 
     // “Clients” can be simulated as simple threads calling the main server application with a variety of requests.
     // There should be more than one, preferably around 5-10 running at any one time.
+    // They run outside of the pool, so that they don't hold up the workers during a shutdown.
     for table_num in 1..=10 {
-        pool.execute(move || {
-            client(table_num);
+        let server_address = server_address.clone();
+        thread::spawn(move || {
+            client(&server_address, table_num);
         });
     }
 
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
+    let connection_config = ConnectionConfig {
+        idle_timeout: Duration::from_secs(config.server.keep_alive_timeout),
+        header_timeout: Duration::from_secs(config.server.header_timeout),
+        max_requests: config.server.keep_alive_max_requests,
+    };
+
+    while !shutdown.is_requested() {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            },
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            },
+        };
+
+        if let Err(e) = stream.set_nonblocking(false) {
+            println!("Error: {}", e);
+            continue;
+        }
+
+        // Keep a handle to the connection to be able to answer it if the pool has no room for the job.
+        let rejected_stream = match stream.try_clone() {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            },
+        };
+
         let router = Arc::clone(&router);
+        let state = Arc::clone(&state);
+        let shutdown = shutdown.clone();
 
-        pool.execute(|| {
-            handle_connection(stream, router);
+        let result = pool.try_execute(move || {
+            handle_connection(stream, &router, &state, connection_config, &shutdown);
         });
+
+        if let Err(ExecuteError::Full) = result {
+            reject_connection(rejected_stream, RETRY_AFTER);
+        }
     }
 
     println!("Shutting down.");
-}
-
 
-fn handle_connection(mut stream: TcpStream, router: Arc<Router>) {
-    let mut buffer = [0; 1024];
-    let mut request = String::new();
-
-    match stream.read(&mut buffer) {
-        Ok(size) => {
-            request.push_str(String::from_utf8_lossy(&buffer[..size]).as_ref());
+    // Stop accepting connections and let the workers finish what has already been queued.
+    drop(listener);
+    let unfinished = pool.shutdown(Duration::from_secs(config.server.shutdown_timeout));
+    if unfinished > 0 {
+        println!("{} workers were still busy after the shutdown timeout.", unfinished);
+    }
 
-            let request_arr: Vec<&str> = request
-                .lines()
-                .next()
-                .unwrap()
-                .split_whitespace()
-                .collect();
+    database::close_pool();
+}
 
-            let (status_line, content) = match router.get_handler(request_arr[1].split("?").nth(0).unwrap(), request_arr[0]) {
-                Ok((handler, params)) => handler(&request, &params),
-                Err(error) => (http::NOT_FOUND.to_string(), error),
-            };
 
-            let response = format!("HTTP/1.1 {status_line}\r\n\r\n{content}");
-            stream.write_all(response.as_bytes()).unwrap();
-        }
-        Err(e) => {
-            println!("Error: {}", e);
-        }
+/// Sets up the configured storage backend.
+///
+/// For PostgreSQL, warms up the connection pool and brings the database schema up to date.
+fn create_state(config: &Config) -> Result<AppState, String> {
+    match config.storage.backend {
+        StorageBackend::Postgres => {
+            database::init_pool(&config.database)
+                .and_then(|pool| pool.warm_up())
+                .and_then(|_| apply_migrations())?;
+
+            let storage = PostgresStorage::new()
+                .with_scheduler(config.kitchen.scheduler())
+                .with_billing(config.billing.rules());
+            Ok(AppState::new(storage))
+        },
+        StorageBackend::Memory => {
+            println!("Using the in-memory storage, the data will be lost when the server stops.");
+            let storage = MemoryStorage::new()
+                .with_scheduler(config.kitchen.scheduler())
+                .with_billing(config.billing.rules());
+            Ok(AppState::new(storage))
+        },
     }
 }
 
-fn get_server_address() -> String {
-    format!("{}:{}", SERVER_URI, SERVER_PORT)
+/// Applies the pending migrations.
+fn apply_migrations() -> Result<(), String> {
+    let applied = migrations::migrate(&mut *database::pool().get()?)?;
+    for migration in applied {
+        println!("Applied migration {}", migration.name);
+    }
+
+    Ok(())
 }
 
+/// Runs the `migrate` subcommand.
+///
+/// # Arguments
+///
+/// * `config` - the application configuration;
+/// * `command` - the subcommand arguments: nothing or `up`, `down <VERSION>` or `status`.
+fn run_migrate_command(config: &Config, command: &[String]) -> Result<(), String> {
+    let pool = database::init_pool(&config.database)?;
+    let mut client = pool.get()?;
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+
+    let result = match command.as_slice() {
+        [] | ["up"] => {
+            migrations::migrate(&mut client).map(|applied| {
+                if applied.is_empty() {
+                    println!("The database is up to date.");
+                }
+                for migration in applied {
+                    println!("Applied migration {}", migration.name);
+                }
+            })
+        },
+        ["down", version] => {
+            version
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid migration version {}", version))
+                .and_then(|version| migrations::rollback(&mut client, version))
+                .map(|reverted| {
+                    for migration in reverted {
+                        println!("Reverted migration {}", migration.name);
+                    }
+                })
+        },
+        ["status"] => {
+            migrations::applied(&mut client).map(|applied| {
+                for migration in migrations::MIGRATIONS {
+                    let state = if applied.iter().any(|row| row.version == migration.version) { "applied" } else { "pending" };
+                    println!("{:<40} {}", migration.name, state);
+                }
+            })
+        },
+        _ => Err(format!("Unknown migrate command \"{}\", expected up, down <VERSION> or status", command.join(" "))),
+    };
+
+    drop(client);
+    pool.close();
+    result
+}
 
 
 /// Simulates client requests
-fn client(table_id: usize) {
+fn client(server_address: &str, table_id: usize) {
     println!("Table {} client started", table_id);
     thread::sleep(Duration::from_secs(5));
 
 
     // List a menu
-    let menu_list = reqwest::blocking::get(get_client_address("/v1/menu/"))
+    let menu_list = reqwest::blocking::get(get_client_address(server_address, "/v1/menu/"))
         .unwrap()
         .json::<Vec<MenuOutput>>()
         .unwrap();
@@ -130,7 +270,7 @@ fn client(table_id: usize) {
         ]
     });
     let client = reqwest::blocking::Client::new();
-    let order_list = client.post(get_client_address("/v1/orders/"))
+    let order_list = client.post(get_client_address(server_address, "/v1/orders/"))
         .body(serde_json::to_string(&order).unwrap())
         .send()
         .unwrap()
@@ -144,12 +284,13 @@ fn client(table_id: usize) {
 
     // delete one random order for current table
     let client = reqwest::blocking::Client::new();
-    let order_list = client.delete(get_client_address(&format!("/v1/tables/{}/orders/{}", table_id, order_list.get(rng.gen_range(0..order_list.len())).unwrap().id)))
+    let table_orders = client.delete(get_client_address(server_address, &format!("/v1/tables/{}/orders/{}", table_id, order_list.get(rng.gen_range(0..order_list.len())).unwrap().id)))
         .send()
         .unwrap()
-        .json::<Vec<OrderOutput>>()
+        .json::<TableOrders>()
         .unwrap();
-    println!("Table {} orders after deletion: {:#?}", table_id, order_list);
+    let order_list = table_orders.orders;
+    println!("Table {} orders after deletion: {:#?}, totals: {:?}", table_id, order_list, table_orders.totals);
 
 
     thread::sleep(Duration::from_secs(5));
@@ -157,7 +298,7 @@ fn client(table_id: usize) {
 
     // Show a specified item for current table
     let order_id = order_list.get(rng.gen_range(0..order_list.len())).unwrap().id;
-    let order = reqwest::blocking::get(get_client_address(&format!("/v1/tables/{}/orders/{}", table_id, order_id)))
+    let order = reqwest::blocking::get(get_client_address(server_address, &format!("/v1/tables/{}/orders/{}", table_id, order_id)))
         .unwrap()
         .json::<OrderOutput>()
         .unwrap();
@@ -167,14 +308,14 @@ fn client(table_id: usize) {
     thread::sleep(Duration::from_secs(200));
 
     // Show all items for current table
-    let orders = reqwest::blocking::get(get_client_address(&format!("/v1/tables/{}/orders", table_id)))
+    let table_orders = reqwest::blocking::get(get_client_address(server_address, &format!("/v1/tables/{}/orders", table_id)))
         .unwrap()
-        .json::<Vec<OrderOutput>>()
+        .json::<TableOrders>()
         .unwrap();
-    println!("Table {} orders after 200 sec: {:#?}", table_id, orders);
+    println!("Table {} orders after 200 sec: {:#?}, totals: {:?}", table_id, table_orders.orders, table_orders.totals);
 }
 
 
-fn get_client_address(url: &str) -> String {
-    format!("http://{}{}", &get_server_address(), url)
+fn get_client_address(server_address: &str, url: &str) -> String {
+    format!("http://{}{}", server_address, url)
 }
@@ -0,0 +1,170 @@
+//! Charges the guests' cards through a payment provider.
+//!
+//! The provider is behind the `PaymentProvider` trait, so a real one can be plugged in without touching the bills.
+//! `FakeProvider` answers locally, which is handy for development and tests.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// A card number as the guest has given it.
+///
+/// Only the last four digits are ever stored or printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Card {
+    number: String,
+}
+
+impl Card {
+    /// Reads a card number, the digits may be separated by spaces or dashes.
+    ///
+    /// Returns `None` if it doesn't have 12 to 19 digits.
+    pub fn parse(number: &str) -> Option<Card> {
+        let digits: String = number.chars().filter(|c| *c != ' ' && *c != '-').collect();
+        if !(12..=19).contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(Card { number: digits })
+    }
+
+    /// Returns the last four digits.
+    pub fn last4(&self) -> &str {
+        &self.number[self.number.len() - 4..]
+    }
+
+    /// Returns whether the number ends with the digits.
+    pub fn ends_with(&self, digits: &str) -> bool {
+        self.number.ends_with(digits)
+    }
+}
+
+impl fmt::Debug for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Card ending with {}", self.last4())
+    }
+}
+
+/// Why the provider hasn't charged or refunded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaymentError {
+    /// The card issuer has refused, with the provider's reason, e.g. `insufficient_funds`.
+    Declined(String),
+    /// The provider hasn't answered in time.
+    Timeout,
+}
+
+/// A payment service that charges cards.
+pub trait PaymentProvider: Send + Sync {
+    /// The name stored with the payments, e.g. `fake`.
+    fn name(&self) -> &'static str;
+
+    /// Charges the card and returns the provider's reference of the charge.
+    ///
+    /// # Arguments
+    ///
+    /// * `card` - the card to charge;
+    /// * `amount` - the amount in minor units, positive;
+    /// * `currency` - the ISO 4217 code of the currency.
+    fn charge(&self, card: &Card, amount: i64, currency: &str) -> Result<String, PaymentError>;
+
+    /// Refunds a part of a charge and returns the provider's reference of the refund.
+    ///
+    /// # Arguments
+    ///
+    /// * `charge` - the provider's reference of the charge;
+    /// * `amount` - the amount in minor units, positive and not more than what is left of the charge;
+    /// * `currency` - the ISO 4217 code of the currency.
+    fn refund(&self, charge: &str, amount: i64, currency: &str) -> Result<String, PaymentError>;
+}
+
+/// A provider that answers at once, depending on the card number:
+///
+/// - `...0002` is declined as `card_declined`;
+/// - `...9995` is declined as `insufficient_funds`;
+/// - `...0119` times out;
+/// - any other number is charged.
+///
+/// Refunds always succeed.
+#[derive(Debug)]
+pub struct FakeProvider {
+    next_reference: AtomicU64,
+}
+
+impl FakeProvider {
+    pub fn new() -> Self {
+        // The references of different runs shouldn't repeat
+        let start = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() * 1000);
+
+        Self { next_reference: AtomicU64::new(start) }
+    }
+
+    fn reference(&self, prefix: &str) -> String {
+        format!("{}_{}", prefix, self.next_reference.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for FakeProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PaymentProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn charge(&self, card: &Card, _amount: i64, _currency: &str) -> Result<String, PaymentError> {
+        if card.ends_with("0002") {
+            Err(PaymentError::Declined("card_declined".to_string()))
+        } else if card.ends_with("9995") {
+            Err(PaymentError::Declined("insufficient_funds".to_string()))
+        } else if card.ends_with("0119") {
+            Err(PaymentError::Timeout)
+        } else {
+            Ok(self.reference("fake_ch"))
+        }
+    }
+
+    fn refund(&self, _charge: &str, _amount: i64, _currency: &str) -> Result<String, PaymentError> {
+        Ok(self.reference("fake_re"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card() {
+        let card = Card::parse("4242 4242-4242 4242").unwrap();
+        assert_eq!(card.last4(), "4242");
+        assert_eq!(format!("{:?}", card), "Card ending with 4242");
+
+        assert!(Card::parse("4242 4242 424").is_none());
+        assert!(Card::parse("4242 4242 4242 4242 4242").is_none());
+        assert!(Card::parse("4242 4242 4242 424x").is_none());
+    }
+
+    #[test]
+    fn test_fake_provider() {
+        let provider = FakeProvider::new();
+        let charge = |number| provider.charge(&Card::parse(number).unwrap(), 1000, "USD");
+
+        let first = charge("4242424242424242").unwrap();
+        let second = charge("4242424242424242").unwrap();
+        assert!(first.starts_with("fake_ch_"));
+        assert_ne!(first, second);
+
+        assert_eq!(charge("4000000000000002"), Err(PaymentError::Declined("card_declined".to_string())));
+        assert_eq!(charge("4000000000009995"), Err(PaymentError::Declined("insufficient_funds".to_string())));
+        assert_eq!(charge("4000000000000119"), Err(PaymentError::Timeout));
+        assert!(provider.refund(&first, 500, "USD").unwrap().starts_with("fake_re_"));
+    }
+}
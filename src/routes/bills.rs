@@ -0,0 +1,132 @@
+use crate::{
+    database::models::payment::{PaymentParams, PaymentStatus},
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
+};
+
+/// Creates a router for `/bills` endpoint.
+///
+/// GET `/bills/:bill_id` returns a specified bill.
+///
+/// POST `/bills/:bill_id/payments` charges a card for a specified bill and returns the payment.
+///
+/// GET `/bills/:bill_id/payments` returns all payments of a specified bill with their refunds.
+///
+/// The bills are created by POST `/tables/:table_id/checkout`.
+pub fn create() -> Router<'static, AppState> {
+    Router::new("/bills")
+        .get("/:bill_id", get_bill)
+
+        // A bill may be paid in several payments, e.g. with different cards.
+        .post("/:bill_id/payments", post_payment)
+        .get("/:bill_id/payments", get_payments)
+}
+
+fn get_bill(request: &Request, state: &AppState) -> Response {
+    let bill_id = match request.param_as::<i32>("bill_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid bill identificator".to_string()).into(),
+    };
+
+    match state.storage.bill(bill_id) {
+        Ok(bill) => Response::new(Status::Ok).json(&bill),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_payment(request: &Request, state: &AppState) -> Response {
+    let bill_id = match request.param_as::<i32>("bill_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid bill identificator".to_string()).into(),
+    };
+
+    match request.json::<PaymentParams>() {
+        Ok(params) => match state.storage.pay(bill_id, params) {
+            // The payment is recorded whatever the provider answers, the status tells the client what has happened
+            Ok(payment) => {
+                let status = match payment.status {
+                    PaymentStatus::Approved => Status::Created,
+                    PaymentStatus::Declined => Status::PaymentRequired,
+                    PaymentStatus::TimedOut => Status::GatewayTimeout,
+                };
+                Response::new(status).json(&payment)
+            }
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid payment data: {}", error)).into(),
+    }
+}
+
+fn get_payments(request: &Request, state: &AppState) -> Response {
+    let bill_id = match request.param_as::<i32>("bill_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid bill identificator".to_string()).into(),
+    };
+
+    match state.storage.payments(bill_id) {
+        Ok(payments) => Response::new(Status::Ok).json(&payments),
+        Err(error) => error.into(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    /// Returns a state with bill 1 of 12.00 for a pizza.
+    fn state_with_bill() -> AppState {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 1, "menu_id": [1]}"#);
+        for status in ["accepted", "cooking", "ready", "served"] {
+            call(&state, Method::Post, "/v1/orders/1/transition", &format!(r#"{{"status": "{}"}}"#, status));
+        }
+        call(&state, Method::Post, "/v1/tables/1/checkout", "");
+
+        state
+    }
+
+    #[test]
+    fn test_get_bill() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Get, "/v1/bills/1", "");
+        assert_eq!(status, Status::NotFound);
+        assert_eq!(body["detail"], "Bill 1 not found");
+
+        let (status, _) = call(&state, Method::Get, "/v1/bills/first", "");
+        assert_eq!(status, Status::BadRequest);
+    }
+
+    #[test]
+    fn test_post_payment() {
+        let state = state_with_bill();
+
+        let (status, body) = call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4000 0000 0000 0002"}"#);
+        assert_eq!(status, Status::PaymentRequired);
+        assert_eq!((&body["status"], &body["decline_reason"]), (&"declined".into(), &"card_declined".into()));
+        let (status, _) = call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4000 0000 0000 0119"}"#);
+        assert_eq!(status, Status::GatewayTimeout);
+
+        let (status, body) = call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4242 4242 4242 4242", "amount": 500}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!((&body["amount"], &body["card_last4"]), (&500.into(), &"4242".into()));
+        let (_, bill) = call(&state, Method::Get, "/v1/bills/1", "");
+        assert_eq!((&bill["paid"], &bill["status"]), (&500.into(), &"partially_paid".into()));
+
+        let (status, body) = call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4242 4242 4242 4242", "amount": 800}"#);
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Bill 1 has only 700 USD left to pay");
+        call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "5555 5555 5555 4444"}"#);
+        let (_, bill) = call(&state, Method::Get, "/v1/bills/1", "");
+        assert_eq!((&bill["paid"], &bill["status"]), (&1200.into(), &"paid".into()));
+
+        let (status, body) = call(&state, Method::Get, "/v1/bills/1/payments", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body.as_array().unwrap().len(), 4);
+        let (status, _) = call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4242"}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+    }
+}
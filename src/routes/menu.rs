@@ -1,17 +1,317 @@
-use crate::http::{router::Router, *};
-use crate::database:: models::menu::Menu;
+use crate::{
+    database::models::{
+        category::{CategoryMenu, GroupedMenu},
+        menu::{Menu, MenuFilter, MenuOutput, MenuPatch},
+    },
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
+};
 
 /// Creates a router for `/menu` endpoint
 /// 
-/// GET `/menu` returns a list of menu items.
-pub fn create() -> Router<'static> {
+/// GET `/menu` returns a list of menu items in the order they are shown,
+/// GET `/menu?grouped=true` returns them grouped by categories.
+/// Both can be filtered with `exclude_allergens=gluten,nuts` and `diet=vegan`.
+/// 
+/// GET `/menu/:menu_id` returns a specified menu item.
+/// 
+/// GET `/menu/categories` returns a list of categories, GET `/menu/categories/:category_id` returns a specified category with its items,
+/// which can be filtered the same way.
+/// 
+/// POST `/menu` adds a menu item and returns it.
+/// 
+/// PUT `/menu/:menu_id` replaces a specified menu item, PATCH `/menu/:menu_id` changes only the specified fields.
+/// Both return the updated item.
+/// 
+/// DELETE `/menu/:menu_id` removes a specified menu item, unless there are orders for it that are still being cooked.
+pub fn create() -> Router<'static, AppState> {
     Router::new("/menu")
         .get("/", get_menu)
+        .get("/:menu_id", get_menu_item)
+        .get("/categories", get_categories)
+        .get("/categories/:category_id", get_category)
+        .post("/", post_menu_item)
+        .put("/:menu_id", put_menu_item)
+        .patch("/:menu_id", patch_menu_item)
+        .delete("/:menu_id", delete_menu_item)
+}
+
+fn get_menu(request: &Request, state: &AppState) -> Response {
+    let grouped = match request.query_param("grouped") {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => return AppError::BadRequest("Invalid grouped flag, expected true or false".to_string()).into(),
+    };
+
+    let items = match menu_filter(request).and_then(|filter| filtered_items(state, &filter)) {
+        Ok(items) => items,
+        Err(error) => return error.into(),
+    };
+    if !grouped {
+        return Response::new(Status::Ok).json(&items);
+    }
+
+    let result = state.storage
+        .categories()
+        .map(|categories| GroupedMenu::new(categories, items));
+
+    match result {
+        Ok(menu) => Response::new(Status::Ok).json(&menu),
+        Err(error) => error.into(),
+    }
+}
+
+/// Reads the `exclude_allergens` and `diet` query parameters.
+fn menu_filter(request: &Request) -> Result<MenuFilter, AppError> {
+    MenuFilter::parse(request.query_param("exclude_allergens"), request.query_param("diet"))
+}
+
+/// Returns the menu items that pass the filter, in the order they are shown.
+fn filtered_items(state: &AppState, filter: &MenuFilter) -> Result<Vec<MenuOutput>, AppError> {
+    let mut items = state.storage.menu_items()?;
+    items.retain(|item| filter.matches(item));
+    Ok(items)
+}
+
+fn get_categories(_: &Request, state: &AppState) -> Response {
+    match state.storage.categories() {
+        Ok(categories) => Response::new(Status::Ok).json(&categories),
+        Err(error) => error.into(),
+    }
+}
+
+fn get_category(request: &Request, state: &AppState) -> Response {
+    let category_id = match request.param_as::<i32>("category_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid category identificator".to_string()).into(),
+    };
+
+    let result = menu_filter(request).and_then(|filter| {
+        let category = state.storage.category(category_id)?;
+        let items = filtered_items(state, &filter)?
+            .into_iter()
+            .filter(|item| item.category_id == Some(category_id))
+            .collect();
+        Ok(CategoryMenu { category, items })
+    });
+
+    match result {
+        Ok(category) => Response::new(Status::Ok).json(&category),
+        Err(error) => error.into(),
+    }
+}
+
+fn get_menu_item(request: &Request, state: &AppState) -> Response {
+    let menu_id = match request.param_as::<i32>("menu_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid menu item identificator".to_string()).into(),
+    };
+
+    match state.storage.menu_item(menu_id) {
+        Ok(item) => Response::new(Status::Ok).json(&item),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_menu_item(request: &Request, state: &AppState) -> Response {
+    match request.json::<Menu>() {
+        Ok(item) => match state.storage.create_menu_item(item) {
+            Ok(item) => Response::new(Status::Created).json(&item),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid menu item data: {}", error)).into(),
+    }
+}
+
+fn put_menu_item(request: &Request, state: &AppState) -> Response {
+    let menu_id = match request.param_as::<i32>("menu_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid menu item identificator".to_string()).into(),
+    };
+
+    match request.json::<Menu>() {
+        Ok(item) => match state.storage.update_menu_item(menu_id, item) {
+            Ok(item) => Response::new(Status::Ok).json(&item),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid menu item data: {}", error)).into(),
+    }
+}
+
+fn patch_menu_item(request: &Request, state: &AppState) -> Response {
+    let menu_id = match request.param_as::<i32>("menu_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid menu item identificator".to_string()).into(),
+    };
+
+    let patch = match request.json::<MenuPatch>() {
+        Ok(patch) => patch,
+        Err(error) => return AppError::BadRequest(format!("Invalid menu item data: {}", error)).into(),
+    };
+
+    let result = state.storage
+        .menu_item(menu_id)
+        .and_then(|current| patch.apply(current))
+        .and_then(|item| state.storage.update_menu_item(menu_id, item));
+
+    match result {
+        Ok(item) => Response::new(Status::Ok).json(&item),
+        Err(error) => error.into(),
+    }
+}
+
+fn delete_menu_item(request: &Request, state: &AppState) -> Response {
+    let menu_id = match request.param_as::<i32>("menu_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid menu item identificator".to_string()).into(),
+    };
+
+    match state.storage.delete_menu_item(menu_id) {
+        Ok(()) => Response::new(Status::NoContent),
+        Err(error) => error.into(),
+    }
 }
 
-fn get_menu(_: &str, _: &Vec<&str>) -> (String, String) {
-    match Menu::get_all_items() {
-        Ok(items) => (OK_RESPONSE.to_string(), serde_json::to_string(&items).unwrap()),
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    #[test]
+    fn test_menu_item_lifecycle() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Post, "/v1/menu", r#"{"name": " Borscht ", "time_to_cook_in_minutes": 15, "price": 990}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!(body["id"], 21);
+        assert_eq!(body["name"], "Borscht");
+        assert_eq!(body["description"], "");
+        assert_eq!((&body["price"], &body["currency"]), (&serde_json::json!(990), &serde_json::json!("USD")));
+
+        let (status, body) = call(&state, Method::Put, "/v1/menu/21", r#"{"name": "Borscht", "description": "Beet soup", "time_to_cook_in_minutes": 20, "price": 1090, "currency": "EUR"}"#);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["time_to_cook_in_minutes"], 20);
+        assert_eq!(body["currency"], "EUR");
+
+        let (status, body) = call(&state, Method::Patch, "/v1/menu/21", r#"{"time_to_cook_in_minutes": 25}"#);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["description"], "Beet soup");
+        assert_eq!(body["time_to_cook_in_minutes"], 25);
+
+        let (status, body) = call(&state, Method::Patch, "/v1/menu/21", r#"{"modifiers": [{"name": "Sour cream", "kind": "add", "price_delta": 100}]}"#);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["modifiers"], serde_json::json!([
+            { "id": 9, "name": "Sour cream", "kind": "add", "price_delta": 100, "cook_time_delta": 0 },
+        ]));
+
+        let (status, _) = call(&state, Method::Delete, "/v1/menu/21", "");
+        assert_eq!(status, Status::NoContent);
+
+        let (status, _) = call(&state, Method::Get, "/v1/menu/21", "");
+        assert_eq!(status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_get_grouped_menu() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/menu", r#"{"name": "Tiramisu", "time_to_cook_in_minutes": 2, "price": 750, "category_id": 3, "display_order": 2}"#);
+        call(&state, Method::Post, "/v1/menu", r#"{"name": "Panna Cotta", "time_to_cook_in_minutes": 2, "price": 700, "category_id": 3, "display_order": 1}"#);
+        call(&state, Method::Post, "/v1/menu", r#"{"name": "Bread", "time_to_cook_in_minutes": 1, "price": 200}"#);
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu", "");
+        assert_eq!(status, Status::Ok);
+        let ids: Vec<&serde_json::Value> = body.as_array().unwrap().iter().map(|item| &item["id"]).collect();
+        assert_eq!(ids[..3], [5, 11, 19]);
+        assert_eq!(ids[ids.len() - 3..], [22, 21, 23]);
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu?grouped=true", "");
+        assert_eq!(status, Status::Ok);
+        let names: Vec<&serde_json::Value> = body["categories"].as_array().unwrap().iter().map(|category| &category["name"]).collect();
+        assert_eq!(names, ["Starters", "Mains", "Desserts", "Drinks"]);
+        assert_eq!(body["categories"][0]["items"].as_array().unwrap().len(), 3);
+        assert_eq!(body["categories"][2]["items"][0]["name"], "Panna Cotta");
+        assert_eq!(body["categories"][3]["items"], serde_json::json!([]));
+        assert_eq!(body["uncategorized"][0]["name"], "Bread");
+
+        let (status, _) = call(&state, Method::Get, "/v1/menu?grouped=yes", "");
+        assert_eq!(status, Status::BadRequest);
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu/categories", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body[1], serde_json::json!({ "id": 2, "name": "Mains", "display_order": 2 }));
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu/categories/3", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["name"], "Desserts");
+        assert_eq!((&body["items"][0]["id"], &body["items"][1]["id"]), (&serde_json::json!(22), &serde_json::json!(21)));
+
+        let (status, _) = call(&state, Method::Get, "/v1/menu/categories/404", "");
+        assert_eq!(status, Status::NotFound);
+
+        let (status, body) = call(&state, Method::Patch, "/v1/menu/1", r#"{"category_id": 404}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["invalid_category_id"], 404);
+    }
+
+    #[test]
+    fn test_filter_menu() {
+        let state = AppState::new(MemoryStorage::new());
+        let ids = |body: &serde_json::Value| body.as_array().unwrap().iter().map(|item| item["id"].as_i64().unwrap()).collect::<Vec<_>>();
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu?diet=vegan", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(ids(&body), vec![8, 9]);
+        assert_eq!(body[0]["allergens"], serde_json::json!(["celery"]));
+        assert_eq!(body[0]["diets"], serde_json::json!(["vegan", "vegetarian", "halal"]));
+
+        let (_, body) = call(&state, Method::Get, "/v1/menu?exclude_allergens=gluten,nuts&diet=vegan", "");
+        assert_eq!(ids(&body), vec![8]);
+
+        let (_, body) = call(&state, Method::Get, "/v1/menu?exclude_allergens=gluten,milk,fish", "");
+        assert_eq!(ids(&body), vec![8, 12]);
+
+        let (_, body) = call(&state, Method::Get, "/v1/menu?grouped=true&diet=halal", "");
+        assert_eq!(body["categories"][0]["items"], serde_json::json!([]));
+        assert_eq!(ids(&body["categories"][1]["items"]), vec![8, 10, 16]);
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu/categories/1?exclude_allergens=gluten", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(ids(&body["items"]), Vec::<i64>::new());
+
+        let (status, body) = call(&state, Method::Get, "/v1/menu?exclude_allergens=shellfish", "");
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body["detail"], "Invalid exclude_allergens: Undefined allergen shellfish");
+        let (status, _) = call(&state, Method::Get, "/v1/menu?diet=keto", "");
+        assert_eq!(status, Status::BadRequest);
+
+        let (status, body) = call(&state, Method::Post, "/v1/menu", r#"{"name": "Vegan Cheesecake", "time_to_cook_in_minutes": 2, "price": 750, "allergens": ["milk"], "diets": ["vegan"]}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["errors"][0]["field"], "diets");
+    }
+
+    #[test]
+    fn test_menu_item_errors() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Post, "/v1/menu", r#"{"name": "", "time_to_cook_in_minutes": -1, "price": -5, "currency": "usd"}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 4);
+
+        // A menu item can't be sold without a price
+        let (status, _) = call(&state, Method::Post, "/v1/menu", r#"{"name": "Borscht", "time_to_cook_in_minutes": 15}"#);
+        assert_eq!(status, Status::BadRequest);
+
+        let (status, _) = call(&state, Method::Patch, "/v1/menu/3", r#"{"name": "  "}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+
+        let (status, _) = call(&state, Method::Put, "/v1/menu/404", r#"{"name": "Borscht", "time_to_cook_in_minutes": 15, "price": 990}"#);
+        assert_eq!(status, Status::NotFound);
+
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 1, "menu_id": [3]}"#);
+        let (status, body) = call(&state, Method::Delete, "/v1/menu/3", "");
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Menu item 3 is referenced by 1 open orders");
     }
 }
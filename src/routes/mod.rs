@@ -1,18 +1,38 @@
 pub mod menu;
 pub mod tables;
 pub mod orders;
+pub mod stations;
+pub mod bills;
+pub mod payments;
 
-use crate::http::{router::Router, *};
+use crate::{
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
+};
 
-pub fn create(base: &'static str) -> Router<'static> {
+pub fn create(base: &'static str) -> Router<'static, AppState> {
     Router::new(base)
         .get("/", health_check)
 
         .merge_from(menu::create())
         .merge_from(tables::create())
         .merge_from(orders::create())
+        .merge_from(stations::create())
+        .merge_from(bills::create())
+        .merge_from(payments::create())
 }
 
-fn health_check(_: &str, _: &Vec<&str>) -> (String, String) {
-    (OK_RESPONSE.to_string(), "Version 1 is running".to_string())
+fn health_check(_: &Request, _: &AppState) -> Response {
+    Response::new(Status::Ok).text("Version 1 is running")
+}
+
+
+/// Sends a request through the `/v1` router and returns the response status and JSON body.
+#[cfg(test)]
+pub(crate) fn call(state: &AppState, method: crate::http::Method, path: &str, body: &str) -> (Status, serde_json::Value) {
+    let mut request = Request::new(method, path);
+    request.body = body.as_bytes().to_vec();
+
+    let response = crate::http::connection::dispatch(&create("/v1"), state, request);
+    (response.status, serde_json::from_slice(&response.body).unwrap_or_default())
 }
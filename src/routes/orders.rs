@@ -1,9 +1,8 @@
 use crate::{
-    http::{router::Router, *}, 
-    database::models::order::{
-        Order,
-        OrderParams,
-    },
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    database::models::order::{OrderParams, TransitionParams},
+    state::AppState,
 };
 
 /// Creates a router for `/orders` endpoint.
@@ -13,8 +12,10 @@ use crate::{
 /// GET `/orders/:order_id` returns a specified order data.
 ///
 /// POST `/orders` creates an order for a specified table with a specified list of menu items
-/// and returns all currently preparing items for this table.
-pub fn create() -> Router<'static> {
+/// and returns the created items.
+///
+/// POST `/orders/:order_id/transition` moves a specified order to another status and returns it.
+pub fn create() -> Router<'static, AppState> {
     Router::new("/orders")
         // The application must be able to give a quick snapshot of any or all items on its list at any time.
         .get("/", get_orders)
@@ -23,49 +24,173 @@ pub fn create() -> Router<'static> {
         // The client MUST be able to: add one or more items with a table number.
         // The application MUST, upon creation request, store the item, the table number, and how long the item will take to cook.
         .post("/", post_order)
+
+        // The kitchen and the waiters move the items along: accepted, cooking, ready, served, or cancelled and voided.
+        .post("/:order_id/transition", post_transition)
 }
 
-fn get_orders(_: &str, _: &Vec<&str>) -> (String, String) {
-    match Order::get_all() {
-        Ok(items) => (OK_RESPONSE.to_string(), serde_json::to_string(&items).unwrap()),
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+fn get_orders(_: &Request, state: &AppState) -> Response {
+    match state.storage.orders() {
+        Ok(items) => Response::new(Status::Ok).json(&items),
+        Err(error) => error.into(),
     }
 }
 
-fn get_one_order(_: &str, params: &Vec<&str>) -> (String, String) {
-    // params[0] is an order ID
-    let order_id = match params[0].parse::<i32>() {
-        Ok(id) => id,
-        _ => return (BAD_REQUEST.to_string(), "Invalid order identificator".to_string()),
+fn get_one_order(request: &Request, state: &AppState) -> Response {
+    let order_id = match request.param_as::<i32>("order_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid order identificator".to_string()).into(),
     };
 
-    match Order::get_one(order_id) {
-        Ok(order) => (OK_RESPONSE.to_string(), serde_json::to_string(&order).unwrap()),
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+    match state.storage.order(order_id) {
+        Ok(order) => Response::new(Status::Ok).json(&order),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_order(request: &Request, state: &AppState) -> Response {
+    match request.json::<OrderParams>() {
+        Ok(order_params) => match state.storage.create_order(order_params) {
+            Ok(orders) => Response::new(Status::Created).json(&orders),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid order data: {}", error)).into(),
     }
 }
 
-fn post_order(request: &str, _: &Vec<&str>) -> (String, String) {
-    let params_json = request
-        .split("\r\n\r\n")
-        .last()
-        .unwrap_or_default();
-
-    let order_params: Result<OrderParams, serde_json::Error> = serde_json::from_str(params_json);
-    match order_params {
-        Ok(order_params) => {
-            let table_id = order_params.table_id;
-            match Order::create(order_params) {
-                Ok(_) => {
-                    return match Order::get_for_tables(vec![table_id]) {
-                        Ok(orders) => (OK_RESPONSE.to_string(), serde_json::to_string(&orders).unwrap()),
-                        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
-                    }
-                },
-                Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
-            }
+fn post_transition(request: &Request, state: &AppState) -> Response {
+    let order_id = match request.param_as::<i32>("order_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid order identificator".to_string()).into(),
+    };
+
+    match request.json::<TransitionParams>() {
+        Ok(params) => match state.storage.transition_order(order_id, params.status) {
+            Ok(order) => Response::new(Status::Ok).json(&order),
+            Err(error) => error.into(),
         },
-        _ => (BAD_REQUEST.to_string(), "Error parcing json data".to_string()),
+        Err(error) => AppError::BadRequest(format!("Invalid transition data: {}", error)).into(),
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    #[test]
+    fn test_post_order() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "menu_id": [4, 5]}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[1]["menu_name"], "Chicken Caesar Salad");
+
+        let (status, body) = call(&state, Method::Get, "/v1/orders/2", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["table_id"], 3);
+    }
+
+    #[test]
+    fn test_post_order_lines() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let body = r#"{"table_id": 3, "lines": [{"menu_id": 18, "quantity": 2, "notes": "Medium rare", "modifiers": [7, 8]}]}"#;
+        let (status, body) = call(&state, Method::Post, "/v1/orders", body);
+        assert_eq!(status, Status::Created);
+        assert_eq!((body[0]["quantity"].clone(), body[0]["notes"].clone()), (2.into(), "Medium rare".into()));
+        assert_eq!(body[0]["modifiers"][0]["name"], "Add bacon");
+        assert_eq!(body[0]["modifiers"][1]["kind"], "remove");
+        assert_eq!((body[0]["price"].clone(), body[0]["amount"].clone()), (1350.into(), 3200.into()));
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "lines": [{"menu_id": 1, "quantity": 100}]}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["errors"][0]["field"], "lines[0].quantity");
+    }
+
+    #[test]
+    fn test_post_order_max_price() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let item = r#"{"name": "Caviar", "time_to_cook_in_minutes": 5, "price": 2147483647, "modifiers": [{"name": "Extra caviar", "kind": "add", "price_delta": 1}]}"#;
+        let (status, body) = call(&state, Method::Post, "/v1/menu", item);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["errors"][0]["field"], "price");
+
+        let item = r#"{"name": "Caviar", "time_to_cook_in_minutes": 1440, "price": 100000000, "modifiers": [{"name": "Extra caviar", "kind": "add", "price_delta": 100000000, "cook_time_delta": 1440}]}"#;
+        let (status, body) = call(&state, Method::Post, "/v1/menu", item);
+        assert_eq!(status, Status::Created);
+        let order = serde_json::json!({
+            "table_id": 3,
+            "lines": [{"menu_id": body["id"], "quantity": 20, "modifiers": [body["modifiers"][0]["id"]]}],
+        });
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", &order.to_string());
+        assert_eq!(status, Status::Created);
+        assert_eq!((body[0]["price"].clone(), body[0]["amount"].clone()), (100_000_000.into(), 4_000_000_000_i64.into()));
+    }
+
+    #[test]
+    fn test_post_order_allergies() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let table = r#"{"description": "Table 3", "capacity": 4, "zone": "main", "allergies": ["nuts", "milk", "nuts"]}"#;
+        let (status, body) = call(&state, Method::Put, "/v1/tables/3", table);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["allergies"], serde_json::json!(["milk", "nuts"]));
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "menu_id": [16, 8]}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!(body[0]["allergen_warning"], serde_json::json!({
+            "allergens": ["milk", "nuts"],
+            "message": "Indian Butter Chicken contains milk, nuts, which the table is allergic to",
+        }));
+        assert!(body[1].get("allergen_warning").is_none());
+
+        // The warning is only given when the order is placed
+        let (_, body) = call(&state, Method::Get, "/v1/orders/1", "");
+        assert!(body.get("allergen_warning").is_none());
+
+        let (_, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 4, "menu_id": [16]}"#);
+        assert!(body[0].get("allergen_warning").is_none());
+    }
+
+    #[test]
+    fn test_post_order_errors() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "menu_id": [4, 404]}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["invalid_menu_ids"], serde_json::json!([404]));
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", "{");
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body["code"], "bad_request");
+
+        let (status, _) = call(&state, Method::Get, "/v1/orders/1", "");
+        assert_eq!(status, Status::NotFound);
+    }
+
+    #[test]
+    fn test_post_transition() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "menu_id": [4]}"#);
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders/1/transition", r#"{"status": "accepted"}"#);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["status"], "accepted");
+        assert!(body["timestamps"]["accepted_at"].is_string());
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders/1/transition", r#"{"status": "served"}"#);
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Order 1 can't go from accepted to served, it may only become cooking or cancelled");
+
+        let (status, _) = call(&state, Method::Post, "/v1/orders/1/transition", r#"{"status": "eaten"}"#);
+        assert_eq!(status, Status::BadRequest);
+
+        let (status, _) = call(&state, Method::Post, "/v1/orders/2/transition", r#"{"status": "accepted"}"#);
+        assert_eq!(status, Status::NotFound);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    database::models::payment::RefundParams,
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
+};
+
+/// Creates a router for `/payments` endpoint.
+///
+/// GET `/payments/:payment_id` returns a specified payment with its refunds.
+///
+/// POST `/payments/:payment_id/refunds` gives back a part of a specified payment and returns the refund.
+///
+/// The payments are created by POST `/bills/:bill_id/payments`.
+pub fn create() -> Router<'static, AppState> {
+    Router::new("/payments")
+        .get("/:payment_id", get_payment)
+        .post("/:payment_id/refunds", post_refund)
+}
+
+fn get_payment(request: &Request, state: &AppState) -> Response {
+    let payment_id = match request.param_as::<i32>("payment_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid payment identificator".to_string()).into(),
+    };
+
+    match state.storage.payment(payment_id) {
+        Ok(payment) => Response::new(Status::Ok).json(&payment),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_refund(request: &Request, state: &AppState) -> Response {
+    let payment_id = match request.param_as::<i32>("payment_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid payment identificator".to_string()).into(),
+    };
+
+    // The body is optional: no body refunds everything left of the payment
+    let params = if request.body.is_empty() {
+        Ok(RefundParams::default())
+    } else {
+        request.json::<RefundParams>()
+    };
+
+    match params {
+        Ok(params) => match state.storage.refund(payment_id, params) {
+            Ok(refund) => Response::new(Status::Created).json(&refund),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid refund data: {}", error)).into(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    #[test]
+    fn test_post_refund() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 2, "menu_id": [11]}"#);
+        for status in ["accepted", "cooking", "ready", "served"] {
+            call(&state, Method::Post, "/v1/orders/1/transition", &format!(r#"{{"status": "{}"}}"#, status));
+        }
+        call(&state, Method::Post, "/v1/tables/2/checkout", "");
+        call(&state, Method::Post, "/v1/bills/1/payments", r#"{"card_number": "4242 4242 4242 4242"}"#);
+
+        let (status, body) = call(&state, Method::Post, "/v1/payments/1/refunds", r#"{"amount": 300, "reason": "The soup was cold"}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!((&body["amount"], &body["reason"]), (&300.into(), &"The soup was cold".into()));
+
+        // The refund opens the bill again
+        let (_, bill) = call(&state, Method::Get, "/v1/bills/1", "");
+        assert_eq!((&bill["paid"], &bill["status"]), (&550.into(), &"partially_paid".into()));
+
+        let (status, _) = call(&state, Method::Post, "/v1/payments/1/refunds", "");
+        assert_eq!(status, Status::Created);
+        let (status, body) = call(&state, Method::Post, "/v1/payments/1/refunds", "");
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Payment 1 has been refunded in full");
+
+        let (status, body) = call(&state, Method::Get, "/v1/payments/1", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!((&body["refunded"], body["refunds"].as_array().unwrap().len()), (&850.into(), 2));
+        let (status, _) = call(&state, Method::Get, "/v1/payments/2", "");
+        assert_eq!(status, Status::NotFound);
+    }
+}
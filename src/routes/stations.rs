@@ -0,0 +1,66 @@
+use crate::{
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
+};
+
+/// Creates a router for `/stations` endpoint.
+///
+/// GET `/stations` returns a list of all kitchen stations.
+///
+/// GET `/stations/:station_id/queue` returns the items a specified station still has to cook, in the order it cooks them.
+pub fn create() -> Router<'static, AppState> {
+    Router::new("/stations")
+        .get("/", get_stations)
+
+        // Every line cook's screen only shows the tickets of their station.
+        .get("/:station_id/queue", get_queue)
+}
+
+fn get_stations(_: &Request, state: &AppState) -> Response {
+    match state.storage.stations() {
+        Ok(stations) => Response::new(Status::Ok).json(&stations),
+        Err(error) => error.into(),
+    }
+}
+
+fn get_queue(request: &Request, state: &AppState) -> Response {
+    let station_id = match request.param_as::<i32>("station_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid station identificator".to_string()).into(),
+    };
+
+    match state.storage.station_queue(station_id) {
+        Ok(orders) => Response::new(Status::Ok).json(&orders),
+        Err(error) => error.into(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    #[test]
+    fn test_get_queue() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 3, "menu_id": [20, 1, 5, 19]}"#);
+
+        let (status, body) = call(&state, Method::Get, "/v1/stations", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body[2]["name"], "cold");
+
+        let (status, body) = call(&state, Method::Get, "/v1/stations/3/queue", "");
+        assert_eq!(status, Status::Ok);
+        let menu_ids: Vec<i64> = body.as_array().unwrap().iter().map(|order| order["menu_id"].as_i64().unwrap()).collect();
+        assert_eq!(menu_ids, vec![5, 19]);
+        assert_eq!(body[0]["station_id"], 3);
+
+        let (status, _) = call(&state, Method::Get, "/v1/stations/404/queue", "");
+        assert_eq!(status, Status::NotFound);
+
+        let (status, _) = call(&state, Method::Get, "/v1/stations/grill/queue", "");
+        assert_eq!(status, Status::BadRequest);
+    }
+}
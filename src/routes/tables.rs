@@ -1,23 +1,41 @@
-use crate::http::{router::Router, *};
-use crate::database::models::{
-    table::Table,
-    order::Order,
+use crate::{
+    database::models::{bill::{CheckoutParams, SplitParams}, order::{OrderStatus, TableOrders}, table::Table},
+    error::AppError,
+    http::{router::Router, Request, Response, Status},
+    state::AppState,
 };
 
 /// Creates a router for `/tables` endpoint
 /// 
 /// GET `/tables` returns a list of all tables.
 /// 
-/// GET `/tables/:table_id_list/orders/` returns all items for a specified table list.
+/// GET `/tables/:table_id` returns a specified table.
+/// 
+/// POST `/tables` adds a table and returns it, PUT `/tables/:table_id` replaces a specified table and returns it.
+/// 
+/// DELETE `/tables/:table_id` removes a specified table, unless it has orders.
+/// 
+/// GET `/tables/:table_id_list/orders/` returns all items for a specified table list
+/// and the amounts each table has to pay.
 /// 
 /// GET `/tables/:table_id/orders/:order_id` returns a specified item for a specified table number.
 /// 
-/// DELETE `/tables/:table_id/orders/:order_id` removes a specified item for a specified table number
-/// and returns all currently preparing items for this table.
-pub fn create() -> Router<'static> {
+/// DELETE `/tables/:table_id/orders/:order_id` cancels a specified item for a specified table number
+/// and returns all items for this table and the amount it has to pay.
+/// 
+/// POST `/tables/:table_id/checkout` bills everything a specified table has ordered since its previous bill
+/// and returns the bill.
+/// 
+/// POST `/tables/:table_id/bills/split` bills it the same way in several bills: evenly, by seat or by item,
+/// and returns the bills.
+pub fn create() -> Router<'static, AppState> {
     Router::new("/tables")
         .get("/", get_tables)
-        
+        .get("/:table_id", get_table)
+        .post("/", post_table)
+        .put("/:table_id", put_table)
+        .delete("/:table_id", delete_table)
+
         // The application MUST, upon query request, show all items for a specified table number.
         // The client MAY limit the number of specific tables in its requests to a finite set (at least 100).
         .get("/:table_id_list/orders/", get_table_orders)
@@ -27,78 +45,290 @@ pub fn create() -> Router<'static> {
 
         // The application MUST, upon deletion request, remove a specified item for a specified table number.
         .delete("/:table_id/orders/:order_id", delete_order_for_table)
+
+        // The table pays and its session is closed, the next guests start a new one.
+        .post("/:table_id/checkout", post_checkout)
+        .post("/:table_id/bills/split", post_split)
 }
 
 
-fn get_tables(_: &str, _: &Vec<&str>) -> (String, String) {
-    match Table::get_all_tables() {
-        Ok(items) => (OK_RESPONSE.to_string(), serde_json::to_string(&items).unwrap()),
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+fn get_tables(_: &Request, state: &AppState) -> Response {
+    match state.storage.tables() {
+        Ok(items) => Response::new(Status::Ok).json(&items),
+        Err(error) => error.into(),
     }
 }
 
-fn get_table_orders(_: &str, params: &Vec<&str>) -> (String, String) {
-    // params[0] is a list of table IDs (or just one table ID)
-    let table_list_str: Vec<&str> = params[0].split(",").collect();
+fn get_table(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
+    };
+
+    match state.storage.table(table_id) {
+        Ok(table) => Response::new(Status::Ok).json(&table),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_table(request: &Request, state: &AppState) -> Response {
+    match request.json::<Table>() {
+        Ok(table) => match state.storage.create_table(table) {
+            Ok(table) => Response::new(Status::Created).json(&table),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid table data: {}", error)).into(),
+    }
+}
+
+fn put_table(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
+    };
+
+    match request.json::<Table>() {
+        Ok(table) => match state.storage.update_table(table_id, table) {
+            Ok(table) => Response::new(Status::Ok).json(&table),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid table data: {}", error)).into(),
+    }
+}
+
+fn delete_table(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
+    };
+
+    match state.storage.delete_table(table_id) {
+        Ok(()) => Response::new(Status::NoContent),
+        Err(error) => error.into(),
+    }
+}
+
+fn get_table_orders(request: &Request, state: &AppState) -> Response {
+    // A list of table IDs (or just one table ID)
+    let table_list_str: Vec<&str> = request.param("table_id_list").unwrap_or_default().split(',').collect();
     // Parse the string IDs to i32, filtering out any invalid IDs
     let table_list_i32: Vec<i32> = table_list_str.iter()
         .filter_map(|&s| s.parse::<i32>().ok())
         .collect();
 
     if table_list_i32.is_empty() {
-        return (BAD_REQUEST.to_string(), "Invalid table identificator".to_string());
+        return AppError::BadRequest("Invalid table identificator".to_string()).into();
     }
 
-    match Order::get_for_tables(table_list_i32) {
-        Ok(orders) => (OK_RESPONSE.to_string(), serde_json::to_string(&orders).unwrap()),
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+    match state.storage.orders_for_tables(&table_list_i32) {
+        Ok(orders) => Response::new(Status::Ok).json(&TableOrders::new(orders)),
+        Err(error) => error.into(),
     }
 }
 
-fn get_order_for_table(_: &str, params: &Vec<&str>) -> (String, String) {
-    // params[0] is a table ID
-    let table_id = match params[0].parse::<i32>() {
-        Ok(id) => id,
-        _ => return (BAD_REQUEST.to_string(), "Invalid table identificator".to_string()),
+fn get_order_for_table(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
     };
-    
-    // params[1] is an order ID
-    let order_id = match params[1].parse::<i32>() {
-        Ok(id) => id,
-        _ => return (BAD_REQUEST.to_string(), "Invalid order identificator".to_string()),
+
+    let order_id = match request.param_as::<i32>("order_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid order identificator".to_string()).into(),
     };
 
-    match Order::get_one_for_table(table_id, order_id) {
-        Ok(order) => (OK_RESPONSE.to_string(), serde_json::to_string(&order).unwrap()),
-        _ => (BAD_REQUEST.to_string(), "Invalid order identificator for the requested table".to_string()),
+    match state.storage.order_for_table(table_id, order_id) {
+        Ok(order) => Response::new(Status::Ok).json(&order),
+        Err(error) => error.into(),
     }
 }
 
-fn delete_order_for_table(_: &str, params: &Vec<&str>) -> (String, String) {
-    // params[0] is a table ID
-    let table_id = match params[0].parse::<i32>() {
-        Ok(id) => id,
-        _ => return (BAD_REQUEST.to_string(), "Invalid table identificator".to_string()),
+fn delete_order_for_table(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
     };
-    
-    // params[1] is an order ID
-    let order_id = match params[1].parse::<i32>() {
-        Ok(id) => id,
-        _ => return (BAD_REQUEST.to_string(), "Invalid order identificator".to_string()),
+
+    let order_id = match request.param_as::<i32>("order_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid order identificator".to_string()).into(),
     };
 
-    match Order::delete_one_for_table(table_id, order_id) {
-        Ok(rows_modified) => {
-            if rows_modified > 0 {
-                return match Order::get_for_tables(vec![table_id]) {
-                    Ok(orders) => (OK_RESPONSE.to_string(), serde_json::to_string(&orders).unwrap()),
-                    Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
-                }
-            }
+    // Make sure the order belongs to the table before it's cancelled
+    let result = state.storage
+        .order_for_table(table_id, order_id)
+        .and_then(|_| state.storage.transition_order(order_id, OrderStatus::Cancelled))
+        .and_then(|_| state.storage.orders_for_tables(&[table_id]));
+
+    match result {
+        Ok(orders) => Response::new(Status::Ok).json(&TableOrders::new(orders)),
+        Err(error) => error.into(),
+    }
+}
+
+fn post_checkout(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
+    };
+
+    // The body is optional: no body means no tip
+    let params = if request.body.is_empty() {
+        Ok(CheckoutParams::default())
+    } else {
+        request.json::<CheckoutParams>()
+    };
 
-            (BAD_REQUEST.to_string(), "No order deleted".to_string())
+    match params {
+        Ok(params) => match state.storage.checkout(table_id, params) {
+            Ok(bill) => Response::new(Status::Created).json(&bill),
+            Err(error) => error.into(),
         },
-        Err(error) => (INTERNAL_SERVER_ERROR.to_string(), error),
+        Err(error) => AppError::BadRequest(format!("Invalid checkout data: {}", error)).into(),
     }
 }
 
+fn post_split(request: &Request, state: &AppState) -> Response {
+    let table_id = match request.param_as::<i32>("table_id") {
+        Some(id) => id,
+        _ => return AppError::BadRequest("Invalid table identificator".to_string()).into(),
+    };
+
+    match request.json::<SplitParams>() {
+        Ok(params) => match state.storage.split_bill(table_id, params) {
+            Ok(bills) => Response::new(Status::Created).json(&bills),
+            Err(error) => error.into(),
+        },
+        Err(error) => AppError::BadRequest(format!("Invalid split data: {}", error)).into(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, routes::call, storage::MemoryStorage};
+
+    #[test]
+    fn test_delete_order_for_table() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 1, "menu_id": [2, 3]}"#);
+
+        let (status, _) = call(&state, Method::Delete, "/v1/tables/2/orders/1", "");
+        assert_eq!(status, Status::NotFound);
+
+        let (status, body) = call(&state, Method::Delete, "/v1/tables/1/orders/1", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["orders"][0]["status"], "cancelled");
+        assert_eq!(body["orders"][0]["is_deleted"], true);
+        // Only the remaining Vegetarian Lasagna is paid for
+        assert_eq!(body["totals"], serde_json::json!([{ "table_id": 1, "currency": "USD", "amount": 1350, "items": 1 }]));
+
+        let (status, body) = call(&state, Method::Delete, "/v1/tables/1/orders/1", "");
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Order 1 is already cancelled");
+
+        // Once the kitchen has started cooking, the order can only be voided
+        call(&state, Method::Post, "/v1/orders/2/transition", r#"{"status": "accepted"}"#);
+        call(&state, Method::Post, "/v1/orders/2/transition", r#"{"status": "cooking"}"#);
+        let (status, _) = call(&state, Method::Delete, "/v1/tables/1/orders/2", "");
+        assert_eq!(status, Status::Conflict);
+    }
+
+    #[test]
+    fn test_get_table_orders() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 1, "menu_id": [2]}"#);
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 2, "menu_id": [3, 3]}"#);
+
+        let (status, body) = call(&state, Method::Get, "/v1/tables/1,2/orders", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["orders"].as_array().unwrap().len(), 3);
+        assert_eq!(body["orders"][0]["price"], 1450);
+        assert_eq!(body["totals"][1]["amount"], 2700);
+
+        // The placed orders keep their price when the menu changes
+        call(&state, Method::Patch, "/v1/menu/3", r#"{"price": 1500}"#);
+        let (_, body) = call(&state, Method::Get, "/v1/tables/2/orders", "");
+        assert_eq!(body["totals"][0]["amount"], 2700);
+
+        let (status, _) = call(&state, Method::Get, "/v1/tables/x/orders", "");
+        assert_eq!(status, Status::BadRequest);
+    }
+
+    #[test]
+    fn test_checkout() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 5, "menu_id": [1, 11]}"#);
+
+        let (status, _) = call(&state, Method::Post, "/v1/tables/5/checkout", r#"{"tip": 100, "tip_percent": 10}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+
+        let (status, body) = call(&state, Method::Post, "/v1/tables/5/checkout", r#"{"tip_percent": 10}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!((&body["subtotal"], &body["tip"], &body["total"]), (&serde_json::json!(2050), &serde_json::json!(205), &serde_json::json!(2255)));
+
+        // The bill is kept, and the table has nothing more to pay
+        let (status, bill) = call(&state, Method::Get, "/v1/bills/1", "");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(bill, body);
+        let (_, body) = call(&state, Method::Get, "/v1/tables/5/orders", "");
+        assert_eq!(body["totals"], serde_json::json!([]));
+        assert_eq!(body["orders"][0]["bill_id"], 1);
+    }
+
+    #[test]
+    fn test_split() {
+        let state = AppState::new(MemoryStorage::new());
+        call(&state, Method::Post, "/v1/orders", r#"{"table_id": 6, "menu_id": [1, 11, 3]}"#);
+        for order_id in 1..=3 {
+            for status in ["accepted", "cooking", "ready", "served"] {
+                let body = format!(r#"{{"status": "{}"}}"#, status);
+                call(&state, Method::Post, &format!("/v1/orders/{}/transition", order_id), &body);
+            }
+        }
+
+        let (status, _) = call(&state, Method::Post, "/v1/tables/6/bills/split", r#"{"mode": "even"}"#);
+        assert_eq!(status, Status::BadRequest);
+        let (status, body) = call(&state, Method::Post, "/v1/tables/6/bills/split", r#"{"mode": "seat"}"#);
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Table 6 has no items with a seat, split it evenly or by items instead");
+        let (status, body) = call(&state, Method::Post, "/v1/tables/6/bills/split", r#"{"mode": "items", "bills": [[1], [2]]}"#);
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["missing_order_ids"], serde_json::json!([3]));
+
+        let (status, body) = call(&state, Method::Post, "/v1/tables/6/bills/split", r#"{"mode": "even", "ways": 3}"#);
+        assert_eq!(status, Status::Created);
+        // 34.00 in three bills
+        let subtotals: Vec<&serde_json::Value> = body.as_array().unwrap().iter().map(|bill| &bill["subtotal"]).collect();
+        assert_eq!(subtotals, vec![1134, 1133, 1133]);
+        let (status, _) = call(&state, Method::Get, "/v1/bills/3", "");
+        assert_eq!(status, Status::Ok);
+    }
+
+    #[test]
+    fn test_table_management() {
+        let state = AppState::new(MemoryStorage::new());
+
+        let (status, body) = call(&state, Method::Post, "/v1/tables", r#"{"id": 40, "description": "Terrace", "capacity": 6, "zone": "patio"}"#);
+        assert_eq!(status, Status::Created);
+        assert_eq!(body["is_active"], true);
+
+        let (status, body) = call(&state, Method::Put, "/v1/tables/40", r#"{"description": "Terrace", "capacity": 6, "zone": "patio", "is_active": false}"#);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["is_active"], false);
+
+        let (status, body) = call(&state, Method::Post, "/v1/orders", r#"{"table_id": 40, "menu_id": [1]}"#);
+        assert_eq!(status, Status::Conflict);
+        assert_eq!(body["detail"], "Table 40 is inactive and doesn't take orders");
+
+        let (status, _) = call(&state, Method::Post, "/v1/tables", r#"{"description": "Roof", "capacity": 2, "zone": "roof"}"#);
+        assert_eq!(status, Status::BadRequest);
+
+        let (status, _) = call(&state, Method::Delete, "/v1/tables/40", "");
+        assert_eq!(status, Status::NoContent);
+
+        let (status, _) = call(&state, Method::Get, "/v1/tables/40", "");
+        assert_eq!(status, Status::NotFound);
+    }
+}
@@ -0,0 +1,294 @@
+//! Estimates when the order items will be cooked.
+//!
+//! The kitchen is modelled as a number of slots, e.g. cooks or burners, each cooking one item at a time.
+//! Items being cooked keep their slots, the waiting items are put into the first free slot
+//! one by one in the queue order (greedy list scheduling).
+//!
+//! Every station has its own slots and queue, the items without a station are cooked by the general kitchen.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// The order in which the waiting items are cooked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulingPolicy {
+    /// In the order they were placed.
+    #[default]
+    Fifo,
+    /// Higher priority first, in the order they were placed within the same priority.
+    Priority,
+}
+
+impl FromStr for SchedulingPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fifo" => Ok(SchedulingPolicy::Fifo),
+            "priority" => Ok(SchedulingPolicy::Priority),
+            _ => Err("expected fifo or priority".to_string()),
+        }
+    }
+}
+
+/// An order item that hasn't been cooked yet.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: i32,
+    /// The station that cooks the item, `None` for the general kitchen.
+    pub station_id: Option<i32>,
+    pub priority: i32,
+    pub placed_at: SystemTime,
+    /// When the cooking has started, `None` if the item is still waiting.
+    pub started_at: Option<SystemTime>,
+    /// The previous estimate of when the item will be cooked, `None` if it hasn't been estimated yet.
+    pub estimated_at: Option<SystemTime>,
+    pub cook_time: Duration,
+}
+
+impl Job {
+    /// Returns when the waiting job can start: the start planned by its previous estimate if it has passed,
+    /// but not before the job was placed, `now` otherwise.
+    fn earliest_start(&self, now: SystemTime) -> SystemTime {
+        self.estimated_at
+            .and_then(|estimated_at| estimated_at.checked_sub(self.cook_time))
+            .map_or(now, |planned_at| planned_at.max(self.placed_at).min(now))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Scheduler {
+    /// Number of items cooked at the same time by the general kitchen.
+    pub slots: usize,
+    pub policy: SchedulingPolicy,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self { slots: 4, policy: SchedulingPolicy::Fifo }
+    }
+}
+
+impl Scheduler {
+    /// Returns the estimated time each job will be cooked at, by the job ID.
+    ///
+    /// A job being cooked for longer than expected is estimated to be ready `now`, and keeps its slot till then.
+    /// A waiting job whose previous estimate planned it to start in the past keeps that start if its slot is free by then,
+    /// so the estimates don't move later while the queue stays the same.
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - the items being cooked or waiting, in any order;
+    /// * `now` - the current time, nothing starts earlier.
+    pub fn schedule(&self, jobs: &[Job], now: SystemTime) -> HashMap<i32, SystemTime> {
+        let mut cooked_at = HashMap::new();
+
+        let queue = self.queue(jobs);
+        let (running, waiting): (Vec<&Job>, Vec<&Job>) = queue.into_iter().partition(|job| job.started_at.is_some());
+
+        let mut running_until: Vec<SystemTime> = running
+            .iter()
+            .map(|job| {
+                let until = (job.started_at.unwrap_or(now) + job.cook_time).max(now);
+                cooked_at.insert(job.id, until);
+                until
+            })
+            .collect();
+
+        // If more items are being cooked than there are slots, a slot is freed when enough of them are ready,
+        // so only the latest ones matter
+        running_until.sort();
+        let slots = self.slots.max(1);
+        let mut free_at: Vec<SystemTime> = running_until.into_iter().rev().take(slots).collect();
+        free_at.resize(slots, SystemTime::UNIX_EPOCH);
+
+        for job in waiting {
+            let slot = free_at
+                .iter_mut()
+                .min()
+                .expect("There is at least one slot");
+            *slot = (*slot).max(job.earliest_start(now)) + job.cook_time;
+            cooked_at.insert(job.id, *slot);
+        }
+
+        cooked_at
+    }
+
+    /// Schedules every station separately, see `schedule`.
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - the items being cooked or waiting at all the stations;
+    /// * `station_slots` - the number of slots by the station ID, the general kitchen's number for unknown stations;
+    /// * `now` - the current time, nothing starts earlier.
+    pub fn schedule_stations(&self, jobs: &[Job], station_slots: &HashMap<i32, usize>, now: SystemTime) -> HashMap<i32, SystemTime> {
+        let mut stations: HashMap<Option<i32>, Vec<Job>> = HashMap::new();
+        for job in jobs {
+            stations.entry(job.station_id).or_default().push(job.clone());
+        }
+
+        stations
+            .into_iter()
+            .flat_map(|(station_id, jobs)| {
+                let slots = station_id.and_then(|id| station_slots.get(&id)).copied().unwrap_or(self.slots);
+                Scheduler { slots, ..*self }.schedule(&jobs, now)
+            })
+            .collect()
+    }
+
+    /// Returns the jobs in the cook order: the ones being cooked in the order they were started,
+    /// then the waiting ones in the order of the policy.
+    pub fn queue<'a>(&self, jobs: &'a [Job]) -> Vec<&'a Job> {
+        let (mut running, mut waiting): (Vec<&Job>, Vec<&Job>) = jobs.iter().partition(|job| job.started_at.is_some());
+
+        running.sort_by_key(|job| (job.started_at, job.id));
+        match self.policy {
+            SchedulingPolicy::Fifo => waiting.sort_by_key(|job| (job.placed_at, job.id)),
+            SchedulingPolicy::Priority => waiting.sort_by_key(|job| (Reverse(job.priority), job.placed_at, job.id)),
+        }
+
+        running.extend(waiting);
+        running
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(minutes * 60)
+    }
+
+    fn job(id: i32, priority: i32, placed_at: u64, cook_minutes: u64) -> Job {
+        Job {
+            id,
+            station_id: None,
+            priority,
+            placed_at: at(placed_at),
+            started_at: None,
+            estimated_at: None,
+            cook_time: Duration::from_secs(cook_minutes * 60),
+        }
+    }
+
+    #[test]
+    fn test_fifo() {
+        let scheduler = Scheduler { slots: 2, policy: SchedulingPolicy::Fifo };
+        let jobs = [job(1, 0, 0, 10), job(2, 0, 1, 5), job(3, 0, 2, 3), job(4, 0, 3, 1)];
+
+        let cooked_at = scheduler.schedule(&jobs, at(5));
+        assert_eq!(cooked_at[&1], at(15));
+        assert_eq!(cooked_at[&2], at(10));
+        // The second slot is free first
+        assert_eq!(cooked_at[&3], at(13));
+        assert_eq!(cooked_at[&4], at(14));
+    }
+
+    #[test]
+    fn test_priority() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Priority };
+        let jobs = [job(1, 0, 0, 10), job(2, 5, 1, 5), job(3, 5, 2, 3)];
+
+        let cooked_at = scheduler.schedule(&jobs, at(0));
+        assert_eq!((cooked_at[&2], cooked_at[&3], cooked_at[&1]), (at(5), at(8), at(18)));
+    }
+
+    #[test]
+    fn test_running_jobs_keep_slots() {
+        let scheduler = Scheduler { slots: 2, policy: SchedulingPolicy::Fifo };
+        let mut cooking = job(1, 0, 0, 10);
+        cooking.started_at = Some(at(2));
+        let mut late = job(2, 0, 0, 1);
+        late.started_at = Some(at(0));
+        let jobs = [cooking, late, job(3, 0, 1, 4), job(4, 0, 1, 4)];
+
+        let cooked_at = scheduler.schedule(&jobs, at(5));
+        assert_eq!(cooked_at[&1], at(12));
+        // Overdue: expected to be ready any moment
+        assert_eq!(cooked_at[&2], at(5));
+        assert_eq!(cooked_at[&3], at(9));
+        assert_eq!(cooked_at[&4], at(13));
+    }
+
+    #[test]
+    fn test_stable_estimates() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Fifo };
+        let mut jobs = [job(1, 0, 0, 10), job(2, 0, 0, 5)];
+
+        let cooked_at = scheduler.schedule(&jobs, at(0));
+        assert_eq!((cooked_at[&1], cooked_at[&2]), (at(10), at(15)));
+
+        // The same queue later keeps the estimates
+        for job in &mut jobs {
+            job.estimated_at = Some(cooked_at[&job.id]);
+        }
+        let cooked_at = scheduler.schedule(&jobs, at(3));
+        assert_eq!((cooked_at[&1], cooked_at[&2]), (at(10), at(15)));
+
+        // A new item is queued after them
+        let cooked_at = scheduler.schedule(&[jobs[0].clone(), jobs[1].clone(), job(3, 0, 3, 1)], at(3));
+        assert_eq!((cooked_at[&1], cooked_at[&2], cooked_at[&3]), (at(10), at(15), at(16)));
+    }
+
+    #[test]
+    fn test_estimates_move_earlier() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Fifo };
+        let mut waiting = job(2, 0, 0, 5);
+        waiting.estimated_at = Some(at(15));
+
+        // The item before it has been cancelled
+        let cooked_at = scheduler.schedule(&[waiting], at(3));
+        assert_eq!(cooked_at[&2], at(8));
+    }
+
+    #[test]
+    fn test_more_running_jobs_than_slots() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Fifo };
+        let mut first = job(1, 0, 0, 10);
+        first.started_at = Some(at(0));
+        let mut second = job(2, 0, 0, 20);
+        second.started_at = Some(at(0));
+
+        let cooked_at = scheduler.schedule(&[first, second, job(3, 0, 1, 5)], at(0));
+        assert_eq!(cooked_at[&3], at(25));
+    }
+
+    #[test]
+    fn test_stations() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Fifo };
+        let mut grill = [job(1, 0, 0, 10), job(2, 0, 1, 10)];
+        for job in &mut grill {
+            job.station_id = Some(7);
+        }
+        let jobs = [grill[0].clone(), grill[1].clone(), job(3, 0, 2, 5), job(4, 0, 3, 5)];
+
+        // The grill cooks two items at once, the general kitchen only one
+        let cooked_at = scheduler.schedule_stations(&jobs, &HashMap::from([(7, 2)]), at(0));
+        assert_eq!((cooked_at[&1], cooked_at[&2]), (at(10), at(10)));
+        assert_eq!((cooked_at[&3], cooked_at[&4]), (at(5), at(10)));
+    }
+
+    #[test]
+    fn test_queue() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Priority };
+        let mut cooking = job(1, 0, 5, 10);
+        cooking.started_at = Some(at(6));
+        let jobs = [job(2, 0, 0, 1), cooking, job(3, 1, 2, 1)];
+
+        let queue: Vec<i32> = scheduler.queue(&jobs).iter().map(|job| job.id).collect();
+        assert_eq!(queue, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_policy_from_str() {
+        assert_eq!("priority".parse::<SchedulingPolicy>(), Ok(SchedulingPolicy::Priority));
+        assert!("lifo".parse::<SchedulingPolicy>().is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A flag that is raised when the server should stop.
+///
+/// Cloned instances share the same flag, so it can be handed to every connection.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raises the flag when the process receives SIGINT or SIGTERM.
+    ///
+    /// May be called only once per process.
+    pub fn listen_for_signals(&self) -> Result<(), String> {
+        let shutdown = self.clone();
+        ctrlc::set_handler(move || {
+            println!("Shutdown signal received.");
+            shutdown.request();
+        })
+        .map_err(|error| error.to_string())
+    }
+
+    /// Raises the flag.
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the server should stop.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
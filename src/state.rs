@@ -0,0 +1,14 @@
+use crate::storage::Storage;
+
+/// The state shared by all route handlers.
+pub struct AppState {
+    pub storage: Box<dyn Storage>,
+}
+
+impl AppState {
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        Self {
+            storage: Box::new(storage),
+        }
+    }
+}
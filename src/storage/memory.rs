@@ -0,0 +1,1138 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+use crate::{
+    billing::BillingRules,
+    clock::{Clock, SystemClock},
+    database::models::{
+        allergen::{self, Allergen, Diet},
+        bill::{self, Bill, BillOutput, CheckoutParams, SplitParams},
+        category::{self, CategoryOutput},
+        menu::{self, Menu, MenuOutput, Modifier, ModifierKind, ModifierParams, DEFAULT_CURRENCY},
+        order::{self, OrderOutput, OrderParams, OrderStatus, OrderTimestamps},
+        payment::{self, Payment, PaymentOutput, PaymentParams, PaymentStatus, Refund, RefundOutput, RefundParams},
+        station::{self, StationOutput},
+        table::{self, Table, TableOutput, Zone},
+    },
+    error::AppError,
+    payments::{FakeProvider, PaymentProvider},
+    scheduler::{Job, Scheduler},
+};
+
+use super::Storage;
+
+/// Keeps the data in the process memory. Everything is lost when the server stops.
+///
+/// Starts with the same tables, stations and menu as a freshly migrated database.
+pub struct MemoryStorage {
+    state: Mutex<State>,
+    clock: Arc<dyn Clock>,
+    scheduler: Scheduler,
+    billing: BillingRules,
+    payment_provider: Arc<dyn PaymentProvider>,
+}
+
+struct State {
+    tables: BTreeMap<i32, TableOutput>,
+    menu: BTreeMap<i32, StoredMenuItem>,
+    stations: BTreeMap<i32, StationOutput>,
+    categories: BTreeMap<i32, CategoryOutput>,
+    orders: BTreeMap<i32, StoredOrder>,
+    bills: BTreeMap<i32, BillOutput>,
+    payments: BTreeMap<i32, PaymentOutput>,
+    next_menu_id: i32,
+    next_modifier_id: i32,
+    next_order_id: i32,
+    next_refund_id: i32,
+}
+
+struct StoredMenuItem {
+    item: MenuOutput,
+    is_deleted: bool,
+}
+
+struct StoredOrder {
+    id: i32,
+    table_id: i32,
+    menu_id: i32,
+    quantity: i32,
+    notes: Option<String>,
+    modifiers: Vec<Modifier>,
+    cooked_at: SystemTime,
+    priority: i32,
+    time_to_cook_in_minutes: i32,
+    station_id: Option<i32>,
+    price: i32,
+    currency: String,
+    seat: Option<i32>,
+    bill_id: Option<i32>,
+    status: OrderStatus,
+    timestamps: OrderTimestamps,
+}
+
+impl MemoryStorage {
+    /// Creates a storage filled with the sample tables and menu.
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates a storage filled with the sample tables and menu that computes the cooking status with the specified clock.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let mut tables: BTreeMap<i32, TableOutput> = (1..=10)
+            .map(|id| (id, sample_table(id, format!("Table {}", id))))
+            .collect();
+        tables.insert(21, sample_table(21, "Table for tests".to_string()));
+
+        let mut menu: BTreeMap<i32, StoredMenuItem> = SAMPLE_MENU
+            .iter()
+            .zip(SAMPLE_ALLERGENS)
+            .zip(1..)
+            .map(|((&(name, time_to_cook_in_minutes, station_id, category_id, price), (allergens, diets)), id)| (id, StoredMenuItem {
+                item: MenuOutput {
+                    id,
+                    name: name.to_string(),
+                    description: format!("Description for {}", name),
+                    time_to_cook_in_minutes,
+                    station_id,
+                    category_id,
+                    display_order: 0,
+                    allergens: allergens.to_vec(),
+                    diets: diets.to_vec(),
+                    price,
+                    currency: DEFAULT_CURRENCY.to_string(),
+                    modifiers: Vec::new(),
+                },
+                is_deleted: false,
+            }))
+            .collect();
+        for (&(menu_id, name, kind, price_delta, cook_time_delta), id) in SAMPLE_MODIFIERS.iter().zip(1..) {
+            if let Some(stored) = menu.get_mut(&menu_id) {
+                stored.item.modifiers.push(Modifier { id, name: name.to_string(), kind, price_delta, cook_time_delta });
+            }
+        }
+
+        let stations: BTreeMap<i32, StationOutput> = SAMPLE_STATIONS
+            .iter()
+            .zip(1..)
+            .map(|(&(name, slots), id)| (id, StationOutput { id, name: name.to_string(), slots }))
+            .collect();
+
+        let categories: BTreeMap<i32, CategoryOutput> = SAMPLE_CATEGORIES
+            .iter()
+            .zip(1..)
+            .map(|(&(name, display_order), id)| (id, CategoryOutput { id, name: name.to_string(), display_order }))
+            .collect();
+
+        Self {
+            state: Mutex::new(State {
+                tables,
+                next_menu_id: menu.len() as i32 + 1,
+                next_modifier_id: SAMPLE_MODIFIERS.len() as i32 + 1,
+                menu,
+                stations,
+                categories,
+                orders: BTreeMap::new(),
+                bills: BTreeMap::new(),
+                payments: BTreeMap::new(),
+                next_order_id: 1,
+                next_refund_id: 1,
+            }),
+            clock,
+            scheduler: Scheduler::default(),
+            billing: BillingRules::default(),
+            payment_provider: Arc::new(FakeProvider::new()),
+        }
+    }
+
+    /// Sets the kitchen model the cooking times are estimated with.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Sets the service charge and the taxes added to the bills.
+    pub fn with_billing(mut self, billing: BillingRules) -> Self {
+        self.billing = billing;
+        self
+    }
+
+    /// Sets the payment provider the bills are paid with, `FakeProvider` by default.
+    pub fn with_payment_provider(mut self, payment_provider: Arc<dyn PaymentProvider>) -> Self {
+        self.payment_provider = payment_provider;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stores the bills the `prepare` closure makes of the orders the table hasn't been billed for yet and returns them.
+    fn check_out(
+        &self,
+        table_id: i32,
+        prepare: impl FnOnce(&[OrderOutput], SystemTime) -> Result<Vec<Bill>, AppError>,
+    ) -> Result<Vec<BillOutput>, AppError> {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        if !state.tables.contains_key(&table_id) {
+            return Err(table::not_found(table_id));
+        }
+
+        let orders = state.select(now, |order| order.table_id == table_id && order.bill_id.is_none());
+        let next_bill_id = state.bills.keys().next_back().map_or(1, |id| id + 1);
+        let bills: Vec<BillOutput> = prepare(&orders, now)?
+            .into_iter()
+            .zip(next_bill_id..)
+            .map(|(bill, bill_id)| BillOutput::new(bill, bill_id))
+            .collect();
+
+        for (order_id, bill_id) in bill::closing_bills(&orders, &bills) {
+            if let Some(stored) = state.orders.get_mut(&order_id) {
+                stored.bill_id = Some(bill_id);
+            }
+        }
+        for bill in &bills {
+            state.bills.insert(bill.id, bill.clone());
+        }
+
+        Ok(bills)
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    fn output(&self, order: &StoredOrder, now: SystemTime) -> OrderOutput {
+        let menu_item = &self.menu[&order.menu_id].item;
+
+        OrderOutput {
+            id: order.id,
+            table_id: order.table_id,
+            menu_id: order.menu_id,
+            menu_name: menu_item.name.clone(),
+            menu_description: menu_item.description.clone(),
+            quantity: order.quantity,
+            notes: order.notes.clone(),
+            modifiers: order.modifiers.clone(),
+            station_id: order.station_id,
+            price: order.price,
+            amount: order::unit_price(order.price, &order.modifiers) * i64::from(order.quantity),
+            currency: order.currency.clone(),
+            seat: order.seat,
+            bill_id: order.bill_id,
+            status: order.status,
+            priority: order.priority,
+            seconds_left: order::seconds_left(order.cooked_at, now),
+            is_prepared: order.status.is_prepared(),
+            is_deleted: order.status.is_deleted(),
+            timestamps: order.timestamps.clone(),
+            allergen_warning: None,
+        }
+    }
+
+    fn select(&self, now: SystemTime, filter: impl Fn(&StoredOrder) -> bool) -> Vec<OrderOutput> {
+        self.orders
+            .values()
+            .filter(|order| filter(order))
+            .map(|order| self.output(order, now))
+            .collect()
+    }
+
+    /// Describes the items the kitchen still has to cook for the scheduler.
+    fn jobs(&self, now: SystemTime, filter: impl Fn(&StoredOrder) -> bool) -> Vec<Job> {
+        self.orders
+            .values()
+            .filter(|order| order.status.is_scheduled() && filter(order))
+            .map(|order| order::job(
+                order.id,
+                order.station_id,
+                order.priority,
+                order.timestamps.placed_at.map_or(now, SystemTime::from),
+                order.timestamps.cooking_at.map(SystemTime::from),
+                Some(order.cooked_at),
+                order.time_to_cook_in_minutes,
+            ))
+            .collect()
+    }
+
+    /// Estimates the cooking times of all the items the kitchen still has to cook.
+    fn reschedule(&mut self, scheduler: &Scheduler, now: SystemTime) {
+        let jobs = self.jobs(now, |_| true);
+        let station_slots: HashMap<i32, usize> = self.stations
+            .values()
+            .map(|station| (station.id, station.slots.max(1) as usize))
+            .collect();
+
+        for (order_id, cooked_at) in scheduler.schedule_stations(&jobs, &station_slots, now) {
+            if let Some(order) = self.orders.get_mut(&order_id) {
+                order.cooked_at = cooked_at;
+            }
+        }
+    }
+
+    /// Checks that the station of the item exists, returns `AppError::Validation` otherwise.
+    fn check_station(&self, item: &Menu) -> Result<(), AppError> {
+        match item.station_id {
+            Some(station_id) if !self.stations.contains_key(&station_id) => Err(station::invalid_station(station_id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the category of the item exists, returns `AppError::Validation` otherwise.
+    fn check_category(&self, item: &Menu) -> Result<(), AppError> {
+        match item.category_id {
+            Some(category_id) if !self.categories.contains_key(&category_id) => Err(category::invalid_category(category_id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gives the modifiers of a menu item their IDs.
+    fn modifiers(&mut self, modifiers: &[ModifierParams]) -> Vec<Modifier> {
+        modifiers
+            .iter()
+            .map(|modifier| {
+                let id = self.next_modifier_id;
+                self.next_modifier_id += 1;
+                Modifier {
+                    id,
+                    name: modifier.name.trim().to_string(),
+                    kind: modifier.kind,
+                    price_delta: modifier.price_delta,
+                    cook_time_delta: modifier.cook_time_delta,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the menu item if it hasn't been deleted.
+    fn menu_item(&mut self, menu_id: i32) -> Result<&mut MenuOutput, AppError> {
+        match self.menu.get_mut(&menu_id) {
+            Some(stored) if !stored.is_deleted => Ok(&mut stored.item),
+            _ => Err(menu::not_found(menu_id)),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn menu_items(&self) -> Result<Vec<MenuOutput>, AppError> {
+        let state = self.lock();
+        let mut items: Vec<MenuOutput> = state.menu
+            .values()
+            .filter(|stored| !stored.is_deleted)
+            .map(|stored| stored.item.clone())
+            .collect();
+
+        let categories: Vec<CategoryOutput> = state.categories.values().cloned().collect();
+        menu::sort_items(&mut items, &categories);
+        Ok(items)
+    }
+
+    fn menu_item(&self, menu_id: i32) -> Result<MenuOutput, AppError> {
+        self.lock().menu_item(menu_id).map(|item| item.clone())
+    }
+
+    fn create_menu_item(&self, item: Menu) -> Result<MenuOutput, AppError> {
+        item.validate()?;
+
+        let mut state = self.lock();
+        state.check_station(&item)?;
+        state.check_category(&item)?;
+        let modifiers = state.modifiers(item.modifiers.as_deref().unwrap_or_default());
+        let created = MenuOutput {
+            id: state.next_menu_id,
+            name: item.name.trim().to_string(),
+            description: item.description().to_string(),
+            time_to_cook_in_minutes: item.time_to_cook_in_minutes,
+            station_id: item.station_id,
+            category_id: item.category_id,
+            display_order: item.display_order,
+            allergens: allergen::normalize(&item.allergens),
+            diets: allergen::normalize(&item.diets),
+            price: item.price,
+            currency: item.currency().to_string(),
+            modifiers,
+        };
+        state.next_menu_id += 1;
+        state.menu.insert(created.id, StoredMenuItem { item: created.clone(), is_deleted: false });
+
+        Ok(created)
+    }
+
+    fn update_menu_item(&self, menu_id: i32, item: Menu) -> Result<MenuOutput, AppError> {
+        item.validate()?;
+
+        let mut state = self.lock();
+        state.check_station(&item)?;
+        state.check_category(&item)?;
+        state.menu_item(menu_id)?;
+        let modifiers = item.modifiers.as_deref().map(|modifiers| state.modifiers(modifiers));
+        let stored = state.menu_item(menu_id)?;
+        stored.name = item.name.trim().to_string();
+        stored.description = item.description().to_string();
+        stored.time_to_cook_in_minutes = item.time_to_cook_in_minutes;
+        stored.station_id = item.station_id;
+        stored.category_id = item.category_id;
+        stored.display_order = item.display_order;
+        stored.allergens = allergen::normalize(&item.allergens);
+        stored.diets = allergen::normalize(&item.diets);
+        stored.price = item.price;
+        stored.currency = item.currency().to_string();
+        if let Some(modifiers) = modifiers {
+            stored.modifiers = modifiers;
+        }
+
+        Ok(stored.clone())
+    }
+
+    fn delete_menu_item(&self, menu_id: i32) -> Result<(), AppError> {
+        let mut state = self.lock();
+        state.menu_item(menu_id)?;
+
+        let open_orders = state.orders
+            .values()
+            .filter(|order| order.menu_id == menu_id && order.status.is_open())
+            .count();
+        if open_orders > 0 {
+            return Err(AppError::Conflict(format!(
+                "Menu item {} is referenced by {} open orders",
+                menu_id,
+                open_orders,
+            )));
+        }
+
+        if let Some(stored) = state.menu.get_mut(&menu_id) {
+            stored.is_deleted = true;
+        }
+        Ok(())
+    }
+
+    fn categories(&self) -> Result<Vec<CategoryOutput>, AppError> {
+        let mut categories: Vec<CategoryOutput> = self.lock().categories.values().cloned().collect();
+        categories.sort_by_key(|category| (category.display_order, category.id));
+        Ok(categories)
+    }
+
+    fn category(&self, category_id: i32) -> Result<CategoryOutput, AppError> {
+        self.lock().categories.get(&category_id).cloned().ok_or_else(|| category::not_found(category_id))
+    }
+
+    fn tables(&self) -> Result<Vec<TableOutput>, AppError> {
+        Ok(self.lock().tables.values().cloned().collect())
+    }
+
+    fn table(&self, table_id: i32) -> Result<TableOutput, AppError> {
+        self.lock().tables.get(&table_id).cloned().ok_or_else(|| table::not_found(table_id))
+    }
+
+    fn create_table(&self, table: Table) -> Result<TableOutput, AppError> {
+        table.validate()?;
+
+        let mut state = self.lock();
+        let id = match table.id {
+            Some(id) if state.tables.contains_key(&id) => return Err(table::already_exists(id)),
+            Some(id) => id,
+            None => state.tables.keys().next_back().map_or(1, |id| id + 1),
+        };
+        let created = TableOutput {
+            id,
+            description: table.description.trim().to_string(),
+            capacity: table.capacity,
+            zone: table.zone,
+            is_active: table.is_active(),
+            allergies: allergen::normalize(&table.allergies),
+        };
+        state.tables.insert(id, created.clone());
+
+        Ok(created)
+    }
+
+    fn update_table(&self, table_id: i32, table: Table) -> Result<TableOutput, AppError> {
+        table.validate()?;
+
+        let mut state = self.lock();
+        let stored = state.tables.get_mut(&table_id).ok_or_else(|| table::not_found(table_id))?;
+        stored.description = table.description.trim().to_string();
+        stored.capacity = table.capacity;
+        stored.zone = table.zone;
+        stored.is_active = table.is_active();
+        stored.allergies = allergen::normalize(&table.allergies);
+
+        Ok(stored.clone())
+    }
+
+    fn delete_table(&self, table_id: i32) -> Result<(), AppError> {
+        let mut state = self.lock();
+        if !state.tables.contains_key(&table_id) {
+            return Err(table::not_found(table_id));
+        }
+        if state.orders.values().any(|order| order.table_id == table_id) {
+            return Err(table::has_orders_error(table_id));
+        }
+
+        state.tables.remove(&table_id);
+        Ok(())
+    }
+
+    fn stations(&self) -> Result<Vec<StationOutput>, AppError> {
+        Ok(self.lock().stations.values().cloned().collect())
+    }
+
+    fn station_queue(&self, station_id: i32) -> Result<Vec<OrderOutput>, AppError> {
+        let now = self.clock.now();
+        let state = self.lock();
+        if !state.stations.contains_key(&station_id) {
+            return Err(station::not_found(station_id));
+        }
+
+        let jobs = state.jobs(now, |order| order.station_id == Some(station_id));
+        Ok(self.scheduler
+            .queue(&jobs)
+            .into_iter()
+            .map(|job| state.output(&state.orders[&job.id], now))
+            .collect())
+    }
+
+    fn orders(&self) -> Result<Vec<OrderOutput>, AppError> {
+        Ok(self.lock().select(self.clock.now(), |_| true))
+    }
+
+    fn orders_for_tables(&self, table_ids: &[i32]) -> Result<Vec<OrderOutput>, AppError> {
+        Ok(self.lock().select(self.clock.now(), |order| table_ids.contains(&order.table_id)))
+    }
+
+    fn order(&self, order_id: i32) -> Result<OrderOutput, AppError> {
+        self.lock()
+            .select(self.clock.now(), |order| order.id == order_id)
+            .pop()
+            .ok_or_else(|| order::not_found(order_id))
+    }
+
+    fn order_for_table(&self, table_id: i32, order_id: i32) -> Result<OrderOutput, AppError> {
+        self.lock()
+            .select(self.clock.now(), |order| order.id == order_id && order.table_id == table_id)
+            .pop()
+            .ok_or_else(|| AppError::NotFound(format!("Order {} not found for table {}", order_id, table_id)))
+    }
+
+    fn create_order(&self, params: OrderParams) -> Result<Vec<OrderOutput>, AppError> {
+        params.validate()?;
+        let lines = params.lines();
+
+        let mut state = self.lock();
+        // The cooking time, station, price and modifiers are copied, so later menu changes don't affect the order
+        let menu_items: HashMap<i32, MenuOutput> = lines
+            .iter()
+            .filter_map(|line| match state.menu.get(&line.menu_id) {
+                Some(stored) if !stored.is_deleted => Some((line.menu_id, stored.item.clone())),
+                _ => None,
+            })
+            .collect();
+        let modifiers: HashMap<i32, (i32, Modifier)> = menu_items
+            .values()
+            .flat_map(|item| item.modifiers.iter().map(|modifier| (modifier.id, (item.id, modifier.clone()))))
+            .collect();
+        params.check_references(state.tables.get(&params.table_id).map(|table| table.is_active), &menu_items, &modifiers)?;
+
+        let line_modifiers: Vec<Vec<Modifier>> = lines
+            .iter()
+            .map(|line| line.modifiers.iter().map(|id| modifiers[id].1.clone()).collect())
+            .collect();
+        let cook_times: Vec<i32> = lines
+            .iter()
+            .zip(&line_modifiers)
+            .map(|(line, modifiers)| {
+                order::cook_time(menu_items[&line.menu_id].time_to_cook_in_minutes, modifiers)
+                    .ok_or_else(|| order::cook_time_out_of_range(line.menu_id))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let now = self.clock.now();
+        let mut created = Vec::new();
+        for ((line, line_modifiers), time_to_cook_in_minutes) in lines.iter().zip(line_modifiers).zip(cook_times) {
+            let item = &menu_items[&line.menu_id];
+            let mut order = StoredOrder {
+                id: state.next_order_id,
+                table_id: params.table_id,
+                menu_id: line.menu_id,
+                quantity: line.quantity,
+                notes: line.notes().map(str::to_string),
+                cooked_at: now,
+                priority: params.priority,
+                time_to_cook_in_minutes,
+                modifiers: line_modifiers,
+                station_id: item.station_id,
+                price: item.price,
+                currency: item.currency.clone(),
+                seat: params.seat,
+                bill_id: None,
+                status: OrderStatus::Placed,
+                timestamps: OrderTimestamps::default(),
+            };
+            order.timestamps.set(OrderStatus::Placed, now);
+            created.push(order.id);
+            state.next_order_id += 1;
+            state.orders.insert(order.id, order);
+        }
+
+        state.reschedule(&self.scheduler, now);
+        let mut orders: Vec<OrderOutput> = created.iter().map(|order_id| state.output(&state.orders[order_id], now)).collect();
+
+        let allergies = &state.tables[&params.table_id].allergies;
+        let menu_allergens: HashMap<i32, Vec<Allergen>> = menu_items
+            .into_iter()
+            .map(|(menu_id, item)| (menu_id, item.allergens))
+            .collect();
+        order::warn_allergies(&mut orders, allergies, &menu_allergens);
+        Ok(orders)
+    }
+
+    fn transition_order(&self, order_id: i32, status: OrderStatus) -> Result<OrderOutput, AppError> {
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let order = state.orders.get_mut(&order_id).ok_or_else(|| order::not_found(order_id))?;
+        order.status.check_transition(status, order_id)?;
+        order.status = status;
+        order.timestamps.set(status, now);
+        // A ready item has been cooked now, whatever the estimate was
+        if status == OrderStatus::Ready {
+            order.cooked_at = now;
+        }
+
+        state.reschedule(&self.scheduler, now);
+        let order = &state.orders[&order_id];
+        Ok(state.output(order, now))
+    }
+
+    fn checkout(&self, table_id: i32, params: CheckoutParams) -> Result<BillOutput, AppError> {
+        let tip = params.tip()?;
+
+        let mut bills = self.check_out(table_id, |orders, now| {
+            Ok(vec![Bill::prepare(table_id, orders, tip, &self.billing, now)?])
+        })?;
+        Ok(bills.remove(0))
+    }
+
+    fn split_bill(&self, table_id: i32, params: SplitParams) -> Result<Vec<BillOutput>, AppError> {
+        let tip = params.validate()?;
+
+        self.check_out(table_id, |orders, now| {
+            Bill::prepare_split(table_id, orders, &params.split, tip, &self.billing, now)
+        })
+    }
+
+    fn bill(&self, bill_id: i32) -> Result<BillOutput, AppError> {
+        self.lock().bills.get(&bill_id).cloned().ok_or_else(|| bill::not_found(bill_id))
+    }
+
+    fn pay(&self, bill_id: i32, params: PaymentParams) -> Result<PaymentOutput, AppError> {
+        let card = params.validate()?;
+        let now = self.clock.now();
+
+        let (amount, currency) = {
+            let state = self.lock();
+            let bill = state.bills.get(&bill_id).ok_or_else(|| bill::not_found(bill_id))?;
+            (payment::payment_amount(bill_id, bill.total, bill.paid, &bill.currency, params.amount)?, bill.currency.clone())
+        };
+
+        // The state isn't locked while the provider is asked, as it may take long to answer
+        let payment = Payment::attempt(bill_id, &card, amount, currency, self.payment_provider.as_ref(), now);
+
+        let mut state = self.lock();
+        if payment.status == PaymentStatus::Approved {
+            let bill = state.bills.get_mut(&bill_id).expect("Bills are never deleted");
+            // Another payment may have covered the amount meanwhile
+            if let Err(error) = payment::payment_amount(bill_id, bill.total, bill.paid, &bill.currency, Some(amount)) {
+                drop(state);
+                payment.void(self.payment_provider.as_ref());
+                return Err(error);
+            }
+            bill.set_paid(bill.paid + amount);
+        }
+
+        let payment_id = state.payments.keys().next_back().map_or(1, |id| id + 1);
+        let payment = PaymentOutput::new(payment, payment_id);
+        state.payments.insert(payment_id, payment.clone());
+
+        Ok(payment)
+    }
+
+    fn payments(&self, bill_id: i32) -> Result<Vec<PaymentOutput>, AppError> {
+        let state = self.lock();
+        if !state.bills.contains_key(&bill_id) {
+            return Err(bill::not_found(bill_id));
+        }
+
+        Ok(state.payments.values().filter(|payment| payment.bill_id == bill_id).cloned().collect())
+    }
+
+    fn payment(&self, payment_id: i32) -> Result<PaymentOutput, AppError> {
+        self.lock().payments.get(&payment_id).cloned().ok_or_else(|| payment::not_found(payment_id))
+    }
+
+    fn refund(&self, payment_id: i32, params: RefundParams) -> Result<RefundOutput, AppError> {
+        params.validate()?;
+        let now = self.clock.now();
+        let mut state = self.lock();
+
+        let payment = state.payments.get(&payment_id).ok_or_else(|| payment::not_found(payment_id))?;
+        let amount = payment::refund_amount(payment, params.amount)?;
+        let refund = Refund::attempt(payment, amount, params.reason, self.payment_provider.as_ref(), now)?;
+        let refund = RefundOutput::new(refund, state.next_refund_id);
+        state.next_refund_id += 1;
+
+        let payment = state.payments.get_mut(&payment_id).expect("The payment has been found");
+        payment.refunded += amount;
+        payment.refunds.push(refund.clone());
+        let bill_id = payment.bill_id;
+        if let Some(bill) = state.bills.get_mut(&bill_id) {
+            bill.set_paid(bill.paid - amount);
+        }
+
+        Ok(refund)
+    }
+}
+
+/// A table as the `0002_seed_tables_and_menu` migration creates it.
+fn sample_table(id: i32, description: String) -> TableOutput {
+    TableOutput { id, description, capacity: 4, zone: Zone::Main, is_active: true, allergies: Vec::new() }
+}
+
+/// The same stations as the `0007_stations` migration: names and slots, the IDs start with 1.
+const SAMPLE_STATIONS: [(&str, i32); 4] = [
+    ("grill", 2),
+    ("fryer", 2),
+    ("cold", 2),
+    ("bar", 1),
+];
+
+/// The same categories as the `0013_menu_categories` migration: names and display orders, the IDs start with 1.
+const SAMPLE_CATEGORIES: [(&str, i32); 4] = [
+    ("Starters", 1),
+    ("Mains", 2),
+    ("Desserts", 3),
+    ("Drinks", 4),
+];
+
+/// A sample menu item: name, cooking time in minutes, station, category and price in cents.
+type SampleMenuItem = (&'static str, i32, Option<i32>, Option<i32>, i32);
+
+/// The same menu as the `0002_seed_tables_and_menu`, `0007_stations`, `0008_prices` and `0013_menu_categories` migrations.
+const SAMPLE_MENU: [SampleMenuItem; 20] = [
+    ("Classic Margherita Pizza", 1, None, Some(2), 1200),
+    ("Spaghetti Carbonara", 5, None, Some(2), 1450),
+    ("Vegetarian Lasagna", 3, None, Some(2), 1350),
+    ("Thai Green Curry", 4, None, Some(2), 1500),
+    ("Chicken Caesar Salad", 2, Some(3), Some(1), 1100),
+    ("Beef Stroganoff", 9, None, Some(2), 1800),
+    ("Grilled Salmon with Dill Sauce", 3, Some(1), Some(2), 2200),
+    ("Moroccan Chickpea Stew", 2, None, Some(2), 1250),
+    ("Szechuan Tofu Stir-Fry", 1, None, Some(2), 1300),
+    ("Lamb Rogan Josh", 1, None, Some(2), 1900),
+    ("French Onion Soup", 2, None, Some(1), 850),
+    ("Korean Bibimbap", 5, None, Some(2), 1400),
+    ("Mexican Chicken Fajitas", 2, Some(1), Some(2), 1600),
+    ("Italian Risotto", 1, None, Some(2), 1550),
+    ("Greek Moussaka", 4, None, Some(2), 1700),
+    ("Indian Butter Chicken", 6, None, Some(2), 1650),
+    ("Spanish Paella", 2, None, Some(2), 2100),
+    ("American Cheeseburger", 1, Some(1), Some(2), 1350),
+    ("Japanese Sushi Rolls", 4, Some(3), Some(1), 1800),
+    ("English Fish and Chips", 5, Some(2), Some(2), 1500),
+];
+
+/// The same allergens and diets as the `0014_allergens` migration, in the order of `SAMPLE_MENU`.
+const SAMPLE_ALLERGENS: [(&[Allergen], &[Diet]); 20] = [
+    (&[Allergen::Gluten, Allergen::Milk], &[Diet::Vegetarian]),
+    (&[Allergen::Eggs, Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Eggs, Allergen::Gluten, Allergen::Milk], &[Diet::Vegetarian]),
+    (&[Allergen::Crustaceans, Allergen::Fish], &[]),
+    (&[Allergen::Eggs, Allergen::Fish, Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Fish, Allergen::Milk], &[]),
+    (&[Allergen::Celery], &[Diet::Vegan, Diet::Vegetarian, Diet::Halal]),
+    (&[Allergen::Gluten, Allergen::Peanuts, Allergen::Sesame, Allergen::Soya], &[Diet::Vegan, Diet::Vegetarian]),
+    (&[Allergen::Milk], &[Diet::Halal]),
+    (&[Allergen::Celery, Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Eggs, Allergen::Sesame, Allergen::Soya], &[]),
+    (&[Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Celery, Allergen::Milk, Allergen::Sulphites], &[Diet::Vegetarian]),
+    (&[Allergen::Eggs, Allergen::Gluten, Allergen::Milk], &[]),
+    (&[Allergen::Milk, Allergen::Nuts], &[Diet::Halal]),
+    (&[Allergen::Celery, Allergen::Crustaceans, Allergen::Fish, Allergen::Molluscs], &[]),
+    (&[Allergen::Gluten, Allergen::Milk, Allergen::Mustard, Allergen::Sesame], &[]),
+    (&[Allergen::Fish, Allergen::Gluten, Allergen::Sesame, Allergen::Soya], &[]),
+    (&[Allergen::Fish, Allergen::Gluten], &[]),
+];
+
+/// The same modifiers as the `0012_order_lines` migration: menu item IDs, names, kinds, price and cooking time deltas.
+/// The IDs start with 1.
+const SAMPLE_MODIFIERS: [(i32, &str, ModifierKind, i32, i32); 8] = [
+    (1, "Extra mozzarella", ModifierKind::Add, 200, 1),
+    (1, "No basil", ModifierKind::Remove, 0, 0),
+    (2, "No bacon", ModifierKind::Remove, 0, 0),
+    (2, "Extra parmesan", ModifierKind::Add, 150, 0),
+    (5, "No croutons", ModifierKind::Remove, 0, 0),
+    (5, "Extra chicken", ModifierKind::Add, 350, 1),
+    (18, "Add bacon", ModifierKind::Add, 250, 1),
+    (18, "No onions", ModifierKind::Remove, 0, 0),
+];
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+    use crate::{
+        billing::TaxRule,
+        clock::TestClock,
+        database::models::{bill::Split, order::OrderLine},
+        payments::{Card, PaymentError},
+        scheduler::SchedulingPolicy,
+    };
+
+    fn order(table_id: i32, menu_id: Vec<i32>) -> OrderParams {
+        OrderParams { table_id, menu_id, lines: Vec::new(), priority: 0, seat: None }
+    }
+
+    #[test]
+    fn test_create_order() {
+        let storage = MemoryStorage::new();
+
+        let created = storage.create_order(order(2, vec![6, 1, 6])).unwrap();
+        assert_eq!(created.iter().map(|order| order.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(created[0].menu_name, "Beef Stroganoff");
+        assert_eq!(created[0].seconds_left, 9 * 60);
+        assert!(!created[0].is_prepared);
+
+        assert_eq!(storage.orders_for_tables(&[2]).unwrap().len(), 3);
+        assert_eq!(storage.orders_for_tables(&[1, 3]).unwrap().len(), 0);
+        assert_eq!(storage.order_for_table(2, 2).unwrap().menu_id, 1);
+    }
+
+    #[test]
+    fn test_create_order_validation() {
+        let storage = MemoryStorage::new();
+
+        match storage.create_order(order(99, vec![1, 50, 50])).unwrap_err() {
+            AppError::Validation { extensions, .. } => {
+                assert_eq!(extensions["invalid_table_id"], 99);
+                assert_eq!(extensions["invalid_menu_ids"], serde_json::json!([50]));
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert!(storage.orders().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_order_lines() {
+        let storage = MemoryStorage::new();
+        // Two carbonaras, one of them without bacon, and a pizza with extra mozzarella
+        let lines = vec![
+            OrderLine { quantity: 2, ..OrderLine::new(2) },
+            OrderLine { notes: Some("Well done".to_string()), modifiers: vec![3], ..OrderLine::new(2) },
+            OrderLine { modifiers: vec![1], ..OrderLine::new(1) },
+        ];
+
+        let created = storage.create_order(OrderParams { lines, ..order(3, Vec::new()) }).unwrap();
+        assert_eq!(created.iter().map(|order| (order.quantity, order.amount)).collect::<Vec<_>>(), vec![(2, 2900), (1, 1450), (1, 1400)]);
+        assert_eq!((created[1].notes.as_deref(), created[1].modifiers[0].name.as_str()), (Some("Well done"), "No bacon"));
+        // The extra mozzarella takes a minute more
+        assert_eq!(created[2].seconds_left, 2 * 60);
+
+        // A modifier of another item is rejected
+        let lines = vec![OrderLine { modifiers: vec![1], ..OrderLine::new(2) }];
+        match storage.create_order(OrderParams { lines, ..order(3, Vec::new()) }).unwrap_err() {
+            AppError::Validation { extensions, .. } => assert_eq!(extensions["invalid_modifier_ids"], serde_json::json!([1])),
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        // Replaced modifiers get new IDs, the orders keep their copies
+        let item = |modifiers| Menu {
+            id: None,
+            name: "Spaghetti Carbonara".to_string(),
+            description: None,
+            time_to_cook_in_minutes: 5,
+            station_id: None,
+            category_id: Some(2),
+            display_order: 0,
+            allergens: vec![Allergen::Gluten, Allergen::Eggs, Allergen::Milk],
+            diets: Vec::new(),
+            price: 1450,
+            currency: None,
+            modifiers,
+        };
+        let no_egg = ModifierParams { name: "No egg".to_string(), kind: ModifierKind::Remove, price_delta: 0, cook_time_delta: 0 };
+        let updated = storage.update_menu_item(2, item(Some(vec![no_egg]))).unwrap();
+        assert_eq!(updated.modifiers.iter().map(|modifier| modifier.id).collect::<Vec<_>>(), vec![SAMPLE_MODIFIERS.len() as i32 + 1]);
+        assert_eq!(storage.order(2).unwrap().modifiers[0].name, "No bacon");
+        // Without modifiers the item keeps its own
+        assert_eq!(storage.update_menu_item(2, item(None)).unwrap().modifiers.len(), 1);
+    }
+
+    #[test]
+    fn test_cooking_time() {
+        let clock = Arc::new(TestClock::default());
+        let storage = MemoryStorage::with_clock(clock.clone());
+        storage.create_order(order(1, vec![2, 1])).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        let orders = storage.orders().unwrap();
+        assert_eq!(orders[0].seconds_left, 4 * 60);
+        assert_eq!(orders[1].seconds_left, 0);
+
+        // The status only changes when the kitchen moves the order
+        clock.advance(Duration::from_secs(5 * 60));
+        let orders = storage.orders().unwrap();
+        assert_eq!((orders[0].seconds_left, orders[0].status, orders[0].is_prepared), (-60, OrderStatus::Placed, false));
+    }
+
+    #[test]
+    fn test_transition_order() {
+        let clock = Arc::new(TestClock::default());
+        let storage = MemoryStorage::with_clock(clock.clone());
+        storage.create_order(order(1, vec![2])).unwrap();
+
+        for status in [OrderStatus::Accepted, OrderStatus::Cooking, OrderStatus::Ready] {
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(storage.transition_order(1, status).unwrap().status, status);
+        }
+        assert!(matches!(storage.transition_order(1, OrderStatus::Cancelled), Err(AppError::Conflict(_))));
+
+        let ready = storage.order(1).unwrap();
+        assert!(ready.is_prepared);
+        assert!(ready.timestamps.placed_at < ready.timestamps.ready_at);
+        assert_eq!(ready.timestamps.served_at, None);
+
+        let voided = storage.transition_order(1, OrderStatus::Voided).unwrap();
+        assert!(voided.is_deleted && !voided.is_prepared);
+        assert!(matches!(storage.transition_order(404, OrderStatus::Accepted), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_scheduling() {
+        let scheduler = Scheduler { slots: 1, policy: SchedulingPolicy::Priority };
+        let storage = MemoryStorage::new().with_scheduler(scheduler);
+
+        let created = storage.create_order(order(1, vec![2, 1])).unwrap();
+        assert_eq!((created[0].seconds_left, created[1].seconds_left), (5 * 60, 6 * 60));
+
+        // A rush order goes first and delays the waiting items
+        let rush = storage.create_order(OrderParams { priority: 1, ..order(2, vec![9]) }).unwrap();
+        assert_eq!(rush[0].seconds_left, 60);
+        assert_eq!(storage.order(2).unwrap().seconds_left, 7 * 60);
+
+        // Cancelled items free the kitchen for the next ones
+        storage.transition_order(1, OrderStatus::Cancelled).unwrap();
+        assert_eq!(storage.order(2).unwrap().seconds_left, 2 * 60);
+    }
+
+    #[test]
+    fn test_station_queue() {
+        let clock = Arc::new(TestClock::default());
+        let storage = MemoryStorage::with_clock(clock.clone());
+
+        // Grilled Salmon, Cheeseburger and Fajitas are grilled, the pizza is cooked by the general kitchen
+        storage.create_order(order(1, vec![7, 1, 18])).unwrap();
+        clock.advance(Duration::from_secs(60));
+        storage.create_order(order(2, vec![13])).unwrap();
+        storage.transition_order(3, OrderStatus::Accepted).unwrap();
+        storage.transition_order(3, OrderStatus::Cooking).unwrap();
+
+        let queue = storage.station_queue(1).unwrap();
+        assert_eq!(queue.iter().map(|order| order.id).collect::<Vec<_>>(), vec![3, 1, 4]);
+        // The grill has 2 slots: the salmon keeps the slot it was planned in a minute ago, the fajitas wait for the cheeseburger
+        assert_eq!((queue[1].seconds_left, queue[2].seconds_left), (2 * 60, 3 * 60));
+
+        storage.transition_order(1, OrderStatus::Cancelled).unwrap();
+        assert_eq!(storage.station_queue(1).unwrap().len(), 2);
+        assert!(storage.station_queue(4).unwrap().is_empty());
+        assert!(matches!(storage.station_queue(404), Err(AppError::NotFound(_))));
+
+        let item = Menu {
+            id: None,
+            name: "Mojito".to_string(),
+            description: None,
+            time_to_cook_in_minutes: 1,
+            station_id: Some(404),
+            category_id: Some(4),
+            display_order: 0,
+            allergens: Vec::new(),
+            diets: vec![Diet::Vegan],
+            price: 900,
+            currency: None,
+            modifiers: None,
+        };
+        assert!(matches!(storage.create_menu_item(item), Err(AppError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_checkout() {
+        let rules = BillingRules {
+            service_charge_rate: 1000,
+            taxes: vec![TaxRule { name: "Sales tax".to_string(), rate: 800, on_service_charge: false }],
+        };
+        let storage = MemoryStorage::new().with_billing(rules);
+        let tip = |tip| CheckoutParams { tip: Some(tip), tip_percent: None };
+
+        // Margherita Pizza and French Onion Soup, the lasagna is cancelled and the soup is still being cooked
+        storage.create_order(order(4, vec![1, 11, 3])).unwrap();
+        storage.transition_order(3, OrderStatus::Cancelled).unwrap();
+        for status in [OrderStatus::Accepted, OrderStatus::Cooking, OrderStatus::Ready, OrderStatus::Served] {
+            storage.transition_order(1, status).unwrap();
+        }
+        storage.transition_order(2, OrderStatus::Accepted).unwrap();
+        let bill = storage.checkout(4, tip(200)).unwrap();
+        assert_eq!(bill.lines.iter().map(|line| line.order_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!((bill.subtotal, bill.service_charge.amount, bill.taxes[0].amount, bill.tip), (2050, 205, 164, 200));
+        assert_eq!(bill.total, 2619);
+        assert_eq!(storage.bill(bill.id).unwrap().total, 2619);
+
+        // The session is closed: the next order starts a new one
+        assert!(storage.orders_for_tables(&[4]).unwrap().iter().all(|order| order.bill_id == Some(bill.id)));
+        assert!(matches!(storage.checkout(4, tip(0)), Err(AppError::Conflict(_))));
+        // The billed soup is still served as usual
+        assert_eq!(storage.transition_order(2, OrderStatus::Cooking).unwrap().bill_id, Some(bill.id));
+        assert!(matches!(storage.checkout(404, tip(0)), Err(AppError::NotFound(_))));
+        assert!(matches!(storage.bill(404), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_split_bill() {
+        let rules = BillingRules {
+            service_charge_rate: 1000,
+            taxes: vec![TaxRule { name: "Sales tax".to_string(), rate: 800, on_service_charge: false }],
+        };
+        let storage = MemoryStorage::new().with_billing(rules);
+
+        // A pizza for the second seat, a soup for the first one and a lasagna to share
+        storage.create_order(OrderParams { seat: Some(2), ..order(2, vec![1]) }).unwrap();
+        storage.create_order(OrderParams { seat: Some(1), ..order(2, vec![11]) }).unwrap();
+        storage.create_order(order(2, vec![3])).unwrap();
+        for order_id in 1..=3 {
+            for status in [OrderStatus::Accepted, OrderStatus::Cooking, OrderStatus::Ready, OrderStatus::Served] {
+                storage.transition_order(order_id, status).unwrap();
+            }
+        }
+
+        let params = SplitParams { split: Split::Seat, tip: CheckoutParams { tip: Some(100), tip_percent: None } };
+        let bills = storage.split_bill(2, params).unwrap();
+        assert_eq!(bills.iter().map(|bill| (bill.id, bill.seat)).collect::<Vec<_>>(), vec![(1, Some(1)), (2, Some(2))]);
+        assert_eq!((bills[0].subtotal, bills[0].service_charge.amount, bills[0].taxes[0].amount, bills[0].tip), (1525, 153, 122, 45));
+        assert_eq!((bills[1].subtotal, bills[1].service_charge.amount, bills[1].taxes[0].amount, bills[1].tip), (1875, 187, 150, 55));
+        // The same as a single bill: 34.00 with 3.40 of service charge, 2.72 of tax and 1.00 of tip
+        assert_eq!(bills[0].total + bills[1].total, 4112);
+
+        // The shared lasagna goes with the first bill
+        let bill_ids: Vec<Option<i32>> = storage.orders_for_tables(&[2]).unwrap().iter().map(|order| order.bill_id).collect();
+        assert_eq!(bill_ids, vec![Some(2), Some(1), Some(1)]);
+        assert_eq!(storage.bill(2).unwrap().lines[0].menu_name, "Classic Margherita Pizza");
+    }
+
+    /// Charges after a pause, so that two payments can be made at once, and remembers the refunds.
+    #[derive(Default)]
+    struct SlowProvider {
+        fake: FakeProvider,
+        refunds: Mutex<Vec<(String, i64)>>,
+    }
+
+    impl PaymentProvider for SlowProvider {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn charge(&self, card: &Card, amount: i64, currency: &str) -> Result<String, PaymentError> {
+            thread::sleep(Duration::from_millis(200));
+            self.fake.charge(card, amount, currency)
+        }
+
+        fn refund(&self, charge: &str, amount: i64, currency: &str) -> Result<String, PaymentError> {
+            self.refunds.lock().unwrap().push((charge.to_string(), amount));
+            self.fake.refund(charge, amount, currency)
+        }
+    }
+
+    #[test]
+    fn test_concurrent_payments() {
+        let provider = Arc::new(SlowProvider::default());
+        let storage = Arc::new(MemoryStorage::new().with_payment_provider(provider.clone()));
+        storage.create_order(order(4, vec![1])).unwrap();
+        for status in [OrderStatus::Accepted, OrderStatus::Cooking, OrderStatus::Ready, OrderStatus::Served] {
+            storage.transition_order(1, status).unwrap();
+        }
+        let bill = storage.checkout(4, CheckoutParams { tip: None, tip_percent: None }).unwrap();
+
+        // Both guests pay the whole bill, but only the first one to be stored is charged
+        let payers: Vec<_> = (0..2)
+            .map(|_| {
+                let storage = storage.clone();
+                let params = PaymentParams { card_number: "4242 4242 4242 4242".to_string(), amount: None };
+                thread::spawn(move || storage.pay(bill.id, params))
+            })
+            .collect();
+        let results: Vec<_> = payers.into_iter().map(|payer| payer.join().unwrap()).collect();
+
+        let approved: Vec<&PaymentOutput> = results.iter().filter_map(|result| result.as_ref().ok()).collect();
+        assert_eq!(approved.len(), 1);
+        assert!(results.iter().any(|result| matches!(result, Err(AppError::Conflict(_)))));
+        assert_eq!(storage.payments(bill.id).unwrap().len(), 1);
+        assert_eq!(storage.bill(bill.id).unwrap().paid, bill.total);
+
+        let refunds = provider.refunds.lock().unwrap();
+        assert_eq!(refunds.len(), 1);
+        assert_ne!(Some(&refunds[0].0), approved[0].reference.as_ref());
+        assert_eq!(refunds[0].1, bill.total);
+    }
+
+    #[test]
+    fn test_delete_menu_item() {
+        let storage = MemoryStorage::new();
+        storage.create_order(order(1, vec![2])).unwrap();
+
+        assert!(matches!(storage.delete_menu_item(2), Err(AppError::Conflict(_))));
+
+        storage.transition_order(1, OrderStatus::Cancelled).unwrap();
+        storage.delete_menu_item(2).unwrap();
+        assert!(matches!(storage.menu_item(2), Err(AppError::NotFound(_))));
+        assert!(matches!(storage.delete_menu_item(2), Err(AppError::NotFound(_))));
+        assert_eq!(storage.menu_items().unwrap().len(), SAMPLE_MENU.len() - 1);
+
+        // The past orders still show the item, but it can't be ordered any more
+        assert_eq!(storage.order(1).unwrap().menu_name, "Spaghetti Carbonara");
+        assert!(matches!(storage.create_order(order(1, vec![2])), Err(AppError::Validation { .. })));
+    }
+
+    #[test]
+    fn test_tables() {
+        let storage = MemoryStorage::new();
+        let table = |id, is_active| Table {
+            id,
+            description: "Bar stool".to_string(),
+            capacity: 1,
+            zone: Zone::Bar,
+            is_active: Some(is_active),
+            allergies: Vec::new(),
+        };
+
+        assert_eq!(storage.create_table(table(None, true)).unwrap().id, 22);
+        assert_eq!(storage.create_table(table(Some(30), true)).unwrap().id, 30);
+        assert!(matches!(storage.create_table(table(Some(30), true)), Err(AppError::Conflict(_))));
+
+        storage.update_table(30, table(None, false)).unwrap();
+        assert!(matches!(storage.create_order(order(30, vec![1])), Err(AppError::Conflict(_))));
+        // Unknown IDs are reported before the table state
+        assert!(matches!(storage.create_order(order(30, vec![404])), Err(AppError::Validation { .. })));
+
+        storage.create_order(order(22, vec![1])).unwrap();
+        assert!(matches!(storage.delete_table(22), Err(AppError::Conflict(_))));
+        storage.delete_table(30).unwrap();
+        assert!(matches!(storage.table(30), Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,144 @@
+//! Storage backends.
+//!
+//! The route handlers work with the data through the `Storage` trait, so the backend is chosen at startup:
+//! `PostgresStorage` keeps the data in the database, `MemoryStorage` keeps it in the process memory
+//! and needs no external services, which is handy for tests and demos.
+
+pub mod memory;
+pub mod postgres;
+
+pub use self::{memory::MemoryStorage, postgres::PostgresStorage};
+
+use crate::{
+    database::models::{
+        bill::{BillOutput, CheckoutParams, SplitParams},
+        category::CategoryOutput,
+        menu::{Menu, MenuOutput},
+        order::{OrderOutput, OrderParams, OrderStatus},
+        payment::{PaymentOutput, PaymentParams, RefundOutput, RefundParams},
+        station::StationOutput,
+        table::{Table, TableOutput},
+    },
+    error::AppError,
+};
+
+/// Menu, category, table, station, order, bill and payment operations used by the route handlers.
+///
+/// Implementations are shared by all worker threads, so they must be thread-safe.
+pub trait Storage: Send + Sync {
+    /// Returns the entire menu, without the deleted items, in the order it is shown, see `menu::sort_items`.
+    fn menu_items(&self) -> Result<Vec<MenuOutput>, AppError>;
+
+    /// Returns the menu item with the specified ID, or `AppError::NotFound` if there is none or it has been deleted.
+    fn menu_item(&self, menu_id: i32) -> Result<MenuOutput, AppError>;
+
+    /// Adds a menu item and returns it.
+    ///
+    /// Returns `AppError::Validation` if the item is invalid.
+    fn create_menu_item(&self, item: Menu) -> Result<MenuOutput, AppError>;
+
+    /// Replaces the menu item with the specified ID and returns the updated item.
+    ///
+    /// Returns `AppError::NotFound` if there is no such item, `AppError::Validation` if the new data is invalid.
+    fn update_menu_item(&self, menu_id: i32, item: Menu) -> Result<MenuOutput, AppError>;
+
+    /// Marks the menu item as deleted, so it can't be ordered any more.
+    ///
+    /// Returns `AppError::NotFound` if there is no such item,
+    /// `AppError::Conflict` if some orders for it are still open.
+    fn delete_menu_item(&self, menu_id: i32) -> Result<(), AppError>;
+
+    /// Returns all menu categories in the order they are shown.
+    fn categories(&self) -> Result<Vec<CategoryOutput>, AppError>;
+
+    /// Returns the menu category with the specified ID, or `AppError::NotFound`.
+    fn category(&self, category_id: i32) -> Result<CategoryOutput, AppError>;
+
+    /// Returns all tables, including the inactive ones.
+    fn tables(&self) -> Result<Vec<TableOutput>, AppError>;
+
+    /// Returns the table with the specified ID, or `AppError::NotFound`.
+    fn table(&self, table_id: i32) -> Result<TableOutput, AppError>;
+
+    /// Adds a table and returns it. The next free ID is taken if it's not specified.
+    ///
+    /// Returns `AppError::Validation` if the table is invalid,
+    /// `AppError::Conflict` if a table with the specified ID already exists.
+    fn create_table(&self, table: Table) -> Result<TableOutput, AppError>;
+
+    /// Replaces the table with the specified ID and returns the updated table.
+    ///
+    /// Returns `AppError::NotFound` if there is no such table, `AppError::Validation` if the new data is invalid.
+    fn update_table(&self, table_id: i32, table: Table) -> Result<TableOutput, AppError>;
+
+    /// Removes the table.
+    ///
+    /// Returns `AppError::NotFound` if there is no such table,
+    /// `AppError::Conflict` if it has orders: such a table can only be deactivated.
+    fn delete_table(&self, table_id: i32) -> Result<(), AppError>;
+
+    /// Returns all kitchen stations.
+    fn stations(&self) -> Result<Vec<StationOutput>, AppError>;
+
+    /// Returns the items the station still has to cook, in the order it cooks them.
+    ///
+    /// Returns `AppError::NotFound` if there is no such station.
+    fn station_queue(&self, station_id: i32) -> Result<Vec<OrderOutput>, AppError>;
+
+    /// Returns all orders, including the cancelled and voided ones.
+    fn orders(&self) -> Result<Vec<OrderOutput>, AppError>;
+
+    /// Returns all orders for the specified tables.
+    fn orders_for_tables(&self, table_ids: &[i32]) -> Result<Vec<OrderOutput>, AppError>;
+
+    /// Returns the order with the specified ID, or `AppError::NotFound`.
+    fn order(&self, order_id: i32) -> Result<OrderOutput, AppError>;
+
+    /// Returns the order with the specified ID if it belongs to the specified table, or `AppError::NotFound`.
+    fn order_for_table(&self, table_id: i32, order_id: i32) -> Result<OrderOutput, AppError>;
+
+    /// Creates order items for the table and returns them.
+    ///
+    /// Returns `AppError::Validation` if the table or any of the menu items doesn't exist,
+    /// `AppError::Conflict` if the table is inactive. Nothing is created then.
+    fn create_order(&self, params: OrderParams) -> Result<Vec<OrderOutput>, AppError>;
+
+    /// Moves the order to another status and returns the updated order.
+    ///
+    /// Returns `AppError::NotFound` if there is no such order,
+    /// `AppError::Conflict` if the order can't get this status from its current one.
+    fn transition_order(&self, order_id: i32, status: OrderStatus) -> Result<OrderOutput, AppError>;
+
+    /// Bills everything the table has ordered since its previous bill and returns the bill.
+    ///
+    /// Returns `AppError::NotFound` if there is no such table, `AppError::Validation` if the tip is invalid,
+    /// `AppError::Conflict` if there is nothing to pay for.
+    fn checkout(&self, table_id: i32, params: CheckoutParams) -> Result<BillOutput, AppError>;
+
+    /// Bills everything the table has ordered since its previous bill in several bills and returns them.
+    ///
+    /// Returns `AppError::NotFound` if there is no such table, `AppError::Validation` if the split or the tip is invalid,
+    /// `AppError::Conflict` if the table can't be checked out or split this way.
+    fn split_bill(&self, table_id: i32, params: SplitParams) -> Result<Vec<BillOutput>, AppError>;
+
+    /// Returns the bill with the specified ID, or `AppError::NotFound`.
+    fn bill(&self, bill_id: i32) -> Result<BillOutput, AppError>;
+
+    /// Charges a card for the bill and returns the payment, whether it has been approved or not.
+    ///
+    /// Returns `AppError::NotFound` if there is no such bill, `AppError::Validation` if the card or the amount is invalid,
+    /// `AppError::Conflict` if the bill has been paid or the amount is more than what is left to pay.
+    fn pay(&self, bill_id: i32, params: PaymentParams) -> Result<PaymentOutput, AppError>;
+
+    /// Returns the payments of the bill with their refunds, or `AppError::NotFound` if there is no such bill.
+    fn payments(&self, bill_id: i32) -> Result<Vec<PaymentOutput>, AppError>;
+
+    /// Returns the payment with the specified ID and its refunds, or `AppError::NotFound`.
+    fn payment(&self, payment_id: i32) -> Result<PaymentOutput, AppError>;
+
+    /// Gives back a part of an approved payment and returns the refund.
+    ///
+    /// Returns `AppError::NotFound` if there is no such payment, `AppError::Validation` if the amount is invalid,
+    /// `AppError::Conflict` if the payment can't be refunded that much, `AppError::Unavailable` if the provider doesn't answer.
+    fn refund(&self, payment_id: i32, params: RefundParams) -> Result<RefundOutput, AppError>;
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use crate::{
+    billing::BillingRules,
+    clock::{Clock, SystemClock},
+    database::models::{
+        bill::{Bill, BillOutput, CheckoutParams, SplitParams},
+        category::{Category, CategoryOutput},
+        menu::{Menu, MenuOutput},
+        order::{Order, OrderOutput, OrderParams, OrderStatus},
+        payment::{Payment, PaymentOutput, PaymentParams, RefundOutput, RefundParams},
+        station::{Station, StationOutput},
+        table::{Table, TableOutput},
+    },
+    error::AppError,
+    payments::{FakeProvider, PaymentProvider},
+    scheduler::Scheduler,
+};
+
+use super::Storage;
+
+/// Keeps the data in PostgreSQL through the models.
+///
+/// The database connection pool must be initialized with `database::init_pool` first.
+pub struct PostgresStorage {
+    clock: Arc<dyn Clock>,
+    scheduler: Scheduler,
+    billing: BillingRules,
+    payment_provider: Arc<dyn PaymentProvider>,
+}
+
+impl PostgresStorage {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates a storage that computes the cooking status with the specified clock.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            scheduler: Scheduler::default(),
+            billing: BillingRules::default(),
+            payment_provider: Arc::new(FakeProvider::new()),
+        }
+    }
+
+    /// Sets the kitchen model the cooking times are estimated with.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Sets the service charge and the taxes added to the bills.
+    pub fn with_billing(mut self, billing: BillingRules) -> Self {
+        self.billing = billing;
+        self
+    }
+
+    /// Sets the payment provider the bills are paid with, `FakeProvider` by default.
+    pub fn with_payment_provider(mut self, payment_provider: Arc<dyn PaymentProvider>) -> Self {
+        self.payment_provider = payment_provider;
+        self
+    }
+}
+
+impl Default for PostgresStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn menu_items(&self) -> Result<Vec<MenuOutput>, AppError> {
+        Menu::get_all_items()
+    }
+
+    fn menu_item(&self, menu_id: i32) -> Result<MenuOutput, AppError> {
+        Menu::get_one(menu_id)
+    }
+
+    fn create_menu_item(&self, item: Menu) -> Result<MenuOutput, AppError> {
+        Menu::create(item)
+    }
+
+    fn update_menu_item(&self, menu_id: i32, item: Menu) -> Result<MenuOutput, AppError> {
+        Menu::update(menu_id, item)
+    }
+
+    fn delete_menu_item(&self, menu_id: i32) -> Result<(), AppError> {
+        Menu::delete(menu_id)
+    }
+
+    fn categories(&self) -> Result<Vec<CategoryOutput>, AppError> {
+        Category::get_all()
+    }
+
+    fn category(&self, category_id: i32) -> Result<CategoryOutput, AppError> {
+        Category::get_one(category_id)
+    }
+
+    fn tables(&self) -> Result<Vec<TableOutput>, AppError> {
+        Table::get_all_tables()
+    }
+
+    fn table(&self, table_id: i32) -> Result<TableOutput, AppError> {
+        Table::get_one(table_id)
+    }
+
+    fn create_table(&self, table: Table) -> Result<TableOutput, AppError> {
+        Table::create(table)
+    }
+
+    fn update_table(&self, table_id: i32, table: Table) -> Result<TableOutput, AppError> {
+        Table::update(table_id, table)
+    }
+
+    fn delete_table(&self, table_id: i32) -> Result<(), AppError> {
+        Table::delete(table_id)
+    }
+
+    fn stations(&self) -> Result<Vec<StationOutput>, AppError> {
+        Station::get_all()
+    }
+
+    fn station_queue(&self, station_id: i32) -> Result<Vec<OrderOutput>, AppError> {
+        Order::get_station_queue(station_id, &self.scheduler, self.clock.now())
+    }
+
+    fn orders(&self) -> Result<Vec<OrderOutput>, AppError> {
+        Order::get_all(self.clock.now())
+    }
+
+    fn orders_for_tables(&self, table_ids: &[i32]) -> Result<Vec<OrderOutput>, AppError> {
+        Order::get_for_tables(table_ids.to_vec(), self.clock.now())
+    }
+
+    fn order(&self, order_id: i32) -> Result<OrderOutput, AppError> {
+        Order::get_one(order_id, self.clock.now())
+    }
+
+    fn order_for_table(&self, table_id: i32, order_id: i32) -> Result<OrderOutput, AppError> {
+        Order::get_one_for_table(table_id, order_id, self.clock.now())
+    }
+
+    fn create_order(&self, params: OrderParams) -> Result<Vec<OrderOutput>, AppError> {
+        Order::create(params, &self.scheduler, self.clock.now())
+    }
+
+    fn transition_order(&self, order_id: i32, status: OrderStatus) -> Result<OrderOutput, AppError> {
+        Order::transition(order_id, status, &self.scheduler, self.clock.now())
+    }
+
+    fn checkout(&self, table_id: i32, params: CheckoutParams) -> Result<BillOutput, AppError> {
+        Bill::checkout(table_id, params, &self.billing, self.clock.now())
+    }
+
+    fn split_bill(&self, table_id: i32, params: SplitParams) -> Result<Vec<BillOutput>, AppError> {
+        Bill::split(table_id, params, &self.billing, self.clock.now())
+    }
+
+    fn bill(&self, bill_id: i32) -> Result<BillOutput, AppError> {
+        Bill::get_one(bill_id)
+    }
+
+    fn pay(&self, bill_id: i32, params: PaymentParams) -> Result<PaymentOutput, AppError> {
+        Payment::create(bill_id, params, self.payment_provider.as_ref(), self.clock.now())
+    }
+
+    fn payments(&self, bill_id: i32) -> Result<Vec<PaymentOutput>, AppError> {
+        Payment::get_for_bill(bill_id)
+    }
+
+    fn payment(&self, payment_id: i32) -> Result<PaymentOutput, AppError> {
+        Payment::get_one(payment_id)
+    }
+
+    fn refund(&self, payment_id: i32, params: RefundParams) -> Result<RefundOutput, AppError> {
+        Payment::refund(payment_id, params, self.payment_provider.as_ref(), self.clock.now())
+    }
+}
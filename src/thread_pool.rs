@@ -1,13 +1,31 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
+/// How often to check if the workers have finished during a shutdown.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+/// The reasons a job may be rejected by `ThreadPool::try_execute`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExecuteError {
+    /// All workers are busy and the queue is full.
+    Full,
+    /// The pool is shutting down.
+    Closed,
+}
+
 pub struct ThreadPool {
     workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
+    sender: Option<mpsc::SyncSender<Job>>,
+    panics: Arc<AtomicUsize>,
 }
 
 impl ThreadPool {
@@ -15,29 +33,40 @@ impl ThreadPool {
     ///
     /// # Arguments
     /// 
-    /// * `size` - a number of threads in the pool.
+    /// * `size` - a number of threads in the pool;
+    /// * `queue_capacity` - how many jobs may wait for a free worker.
+    ///   With zero capacity a job is accepted only if some worker is idle.
     ///
     /// # Panics
     ///
     /// The `new` function will panic if the size is zero.
-    pub fn new(size: usize) -> ThreadPool {
+    pub fn new(size: usize, queue_capacity: usize) -> ThreadPool {
         assert!(size > 0);
 
-        let (sender, receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::sync_channel(queue_capacity);
         let receiver = Arc::new(Mutex::new(receiver));
+        let panics = Arc::new(AtomicUsize::new(0));
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&panics)));
         }
 
         ThreadPool {
             workers,
-            sender: Some(sender)
+            sender: Some(sender),
+            panics,
         }
     }
 
+    /// Returns how many jobs have panicked since the pool was created.
+    pub fn panic_count(&self) -> usize {
+        self.panics.load(Ordering::SeqCst)
+    }
+
     /// Executes a pool job.
+    ///
+    /// Blocks until there is room in the queue.
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
@@ -46,22 +75,98 @@ impl ThreadPool {
 
         self.sender.as_ref().unwrap().send(job).unwrap();
     }
+
+    /// Executes a pool job if there is room for it.
+    ///
+    /// Returns `ExecuteError::Full` immediately instead of waiting when the pool is saturated.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+
+        match self.sender.as_ref().map(|sender| sender.try_send(job)) {
+            Some(Ok(())) => Ok(()),
+            Some(Err(mpsc::TrySendError::Full(_))) => Err(ExecuteError::Full),
+            _ => Err(ExecuteError::Closed),
+        }
+    }
+
+    /// Stops accepting jobs and waits for the workers to finish the queued ones.
+    ///
+    /// Workers still busy when the `timeout` expires are detached and left behind.
+    ///
+    /// Returns the number of workers that did not finish in time.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - how long to wait for the queued jobs.
+    pub fn shutdown(mut self, timeout: Duration) -> usize {
+        drop(self.sender.take());
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.workers.iter().all(Worker::is_finished) {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+
+        // Dropping a `JoinHandle` detaches the thread, so `Drop` won't wait for the unfinished workers.
+        let (finished, unfinished): (Vec<Worker>, Vec<Worker>) = self.workers
+            .drain(..)
+            .partition(Worker::is_finished);
+        self.workers = finished;
+
+        for worker in &unfinished {
+            println!("Worker {} did not finish in time.", worker.id);
+        }
+
+        unfinished.len()
+    }
 }
 
 
+type Receiver = Arc<Mutex<mpsc::Receiver<Job>>>;
+
 struct Worker {
     id: usize,
-    thread: Option<thread::JoinHandle<()>>,
+    /// The current thread of the worker.
+    ///
+    /// Shared with the thread itself, which puts its replacement here after a panic.
+    thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+    fn new(id: usize, receiver: Receiver, panics: Arc<AtomicUsize>) -> Worker {
+        let worker = Worker {
+            id,
+            thread: Arc::new(Mutex::new(None)),
+        };
+        Worker::spawn(id, receiver, panics, Arc::clone(&worker.thread));
+
+        worker
+    }
+
+    /// Starts a worker thread and stores its handle in the slot.
+    ///
+    /// Every job runs under `catch_unwind`. If a job panics, the panic is counted
+    /// and the thread is replaced by a fresh one, so the pool keeps its size.
+    fn spawn(id: usize, receiver: Receiver, panics: Arc<AtomicUsize>, slot: Arc<Mutex<Option<thread::JoinHandle<()>>>>) {
+        // The slot stays locked until the handle is stored,
+        // so a replacement spawned by a quickly panicking thread can't be overwritten with its predecessor.
+        let mut current = lock(&slot);
+        let thread_slot = Arc::clone(&slot);
+
         let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv();
+            let message = lock(&receiver).recv();
             match message {
                 Ok(job) => {
                     // println!("Worker {id} got a job; executing."); // for testing purposes
-                    job();
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        let count = panics.fetch_add(1, Ordering::SeqCst) + 1;
+                        println!("Worker {id} panicked ({count} panics in total); respawning.");
+
+                        Worker::spawn(id, receiver, panics, thread_slot);
+                        break;
+                    }
                 }
                 Err(_) => {
                     println!("Worker {id} disconnected; shutting down.");
@@ -70,13 +175,24 @@ impl Worker {
             }
         });
 
-        Worker {
-            id,
-            thread: Some(thread),
+        *current = Some(thread);
+    }
+
+    fn is_finished(&self) -> bool {
+        match &*lock(&self.thread) {
+            Some(thread) => thread.is_finished(),
+            None => true,
         }
     }
 }
 
+/// Locks the mutex, ignoring the poisoning.
+///
+/// The data behind the pool's mutexes stays consistent even if a thread panicked while holding the lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         drop(self.sender.take());
@@ -84,9 +200,88 @@ impl Drop for ThreadPool {
         for worker in &mut self.workers {
             println!("Shutting down worker {}", worker.id);
 
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+            // A worker that panics on its last job puts a replacement into the slot, which has to be joined, too.
+            loop {
+                let thread = lock(&worker.thread).take();
+                match thread {
+                    Some(thread) => {
+                        if thread.join().is_err() {
+                            println!("Worker {} has panicked.", worker.id);
+                        }
+                    },
+                    None => break,
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_execute_full() {
+        let pool = ThreadPool::new(1, 1);
+        let (release, wait) = mpsc::channel::<()>();
+        let (started, is_started) = mpsc::channel();
+
+        // Occupy the only worker
+        pool.try_execute(move || {
+            started.send(()).unwrap();
+            wait.recv().unwrap();
+        }).unwrap();
+        is_started.recv().unwrap();
+
+        // One job fits in the queue, the next one doesn't
+        assert_eq!(pool.try_execute(|| {}), Ok(()));
+        assert_eq!(pool.try_execute(|| {}), Err(ExecuteError::Full));
+
+        release.send(()).unwrap();
+        assert_eq!(pool.shutdown(Duration::from_secs(5)), 0);
+    }
+
+    #[test]
+    fn test_shutdown_finishes_queued_jobs() {
+        let pool = ThreadPool::new(2, 10);
+        let done = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..6 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(20));
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        assert_eq!(pool.shutdown(Duration::from_secs(5)), 0);
+        assert_eq!(done.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        let pool = ThreadPool::new(2, 10);
+        pool.execute(|| thread::sleep(Duration::from_secs(2)));
+
+        let started = Instant::now();
+        assert_eq!(pool.shutdown(Duration::from_millis(100)), 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_panic_respawns_worker() {
+        let pool = ThreadPool::new(1, 10);
+        let (sender, receiver) = mpsc::channel();
+
+        pool.execute(|| panic!("job failed"));
+        pool.execute(|| panic!("job failed again"));
+
+        // The only worker has been replaced twice and still takes jobs
+        pool.execute(move || sender.send("done").unwrap());
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok("done"));
+
+        assert_eq!(pool.panic_count(), 2);
+        assert_eq!(pool.shutdown(Duration::from_secs(5)), 0);
+    }
+}